@@ -0,0 +1,57 @@
+//! Compares `LALRBuilder` with `NaiveLALRBuilder` on the grammar of `examples/http`, also
+//! measures `MinimalLRBuilder`.
+//!
+//! Run with `cargo bench --bench lalr`.
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use server::utils::*;
+use server::lalr::*;
+
+/// Extracts the request grammar from the template literal of `examples/http/grammar.js`.
+fn http_grammar() -> String {
+  let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/http/grammar.js");
+  let source = fs::read_to_string(path).expect("Error in reading of grammar");
+  let start = source.find("const grammar = (isRequest) => `").expect("Not found grammar") + 32;
+  let end = start + source[start..].find('`').expect("Not found end of grammar");
+  let text = source[start..end].replace("${isRequest == true ? 'request_line' : 'status_line'}", "request_line");
+
+  // unescape the template literal
+  let mut result = String::with_capacity(text.len());
+  let mut chars = text.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => result.extend(chars.next()),
+      _ => result.push(c)
+    }
+  }
+  result
+}
+
+fn measure<T: StatesBuilder>(grammar: &Grammar, iterations: u32) -> (Duration, usize) {
+  let start = Instant::now();
+  let mut len = 0;
+  for _ in 0..iterations {
+    let goto_states = T::build_goto_states(grammar);
+    let action_states = T::build_action_states(grammar, &goto_states).expect("Error in build");
+    len = action_states.len();
+  }
+  (start.elapsed() / iterations, len)
+}
+
+fn main() {
+  let grammar = GrammarBuilder::from_text(http_grammar()).expect("Error in grammar");
+  println!("examples/http: {} productions", grammar.len());
+
+  let (elapsed, len) = measure::<LALRBuilder>(&grammar, 10);
+  println!("LALRBuilder       {:>10.3?} per build, {} states", elapsed, len);
+
+  let (naive_elapsed, naive_len) = measure::<NaiveLALRBuilder>(&grammar, 1);
+  println!("NaiveLALRBuilder  {:>10.3?} per build, {} states", naive_elapsed, naive_len);
+
+  println!("speedup           {:>10.1}x", naive_elapsed.as_secs_f64() / elapsed.as_secs_f64());
+
+  let (minimal_elapsed, minimal_len) = measure::<MinimalLRBuilder>(&grammar, 10);
+  println!("MinimalLRBuilder  {:>10.3?} per build, {} states", minimal_elapsed, minimal_len);
+}
@@ -0,0 +1,144 @@
+use super::utils::*;
+use super::error::Error;
+use super::parser::ParseTree;
+use super::lex::IntFormat;
+
+///
+/// Operations available to a lexer action.
+///
+pub trait LexActionContext {
+  /// Gets value of the recognized token.
+  fn get(&self) -> &[u8];
+
+  /// Replaces value of the token.
+  fn set(&mut self, value: Vec<u8>);
+
+  /// Replaces name of the token.
+  fn set_name(&mut self, name: &str);
+
+  /// Replaces name of the token by the hash of name.
+  fn set_name_from_hash(&mut self, name: usize);
+
+  /// Skips the token.
+  fn pass(&mut self);
+
+  /// Reads the token `tkn_name` after the token as the integer of the format, the value of the
+  /// token is the decimal text of the integer.
+  fn read_int(&mut self, tkn_name: &str, format: IntFormat);
+
+  /// Reads the token `tkn_name` after the token as the bytes prefixed by their length in the format.
+  fn read_blob(&mut self, tkn_name: &str, format: IntFormat);
+
+  /// Switches the lexer to the mode from the next token.
+  fn begin(&mut self, mode: &str);
+
+  /// Saves the current mode of the lexer on the stack and switches to the mode.
+  fn push_mode(&mut self, mode: &str);
+
+  /// Switches the lexer to the mode saved on the stack.
+  fn pop_mode(&mut self);
+}
+
+///
+/// Lexer actions.
+///
+/// `lex_action` is called for every recognized token, `action` is the text of the
+/// rule action (the code in braces) if the rule has one.
+///
+pub trait LexActions {
+  #[allow(unused_variables)]
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    Ok(())
+  }
+
+  /// Receives the data of a token read by size, `end` is true for the last part.
+  #[allow(unused_variables)]
+  fn tkn_data(&mut self, tkn_name: usize, data: &[u8], end: bool) -> Result<(), String> {
+    Ok(())
+  }
+}
+
+///
+/// Operations available to a grammar action.
+///
+/// Symbols of the production are addressed by index counting from the rightmost symbol at 0.
+///
+pub trait ParserActionContext {
+  /// Binds an id to the new symbol.
+  fn bind(&mut self, id: usize);
+
+  /// Gets id bound to the symbol.
+  fn id(&self, index: usize) -> Option<usize>;
+
+  /// Gets value of the lookahead symbol.
+  fn lookup(&self) -> Option<&[u8]>;
+
+  /// Gets value of the symbol.
+  fn get(&self, index: usize) -> Option<&[u8]>;
+
+  /// Sets value of the new symbol from the symbol.
+  fn set(&mut self, index: usize);
+
+  /// Sets value of the new symbol.
+  fn set_val(&mut self, value: Vec<u8>);
+
+  /// Replaces name of the new symbol.
+  fn set_name(&mut self, name: &str);
+
+  /// Replaces name of the new symbol by the hash of name.
+  fn set_name_from_hash(&mut self, name: usize);
+
+  /// Inserts a symbol (or reads a token of `size` bytes or up to the delimiter `stop_codes`)
+  /// after the symbol `name`.
+  fn push_after(&mut self, name: &str, insert_name: Option<&str>,
+    insert_value: Option<Vec<u8>>, size: Option<usize>, stop_codes: Option<Vec<u8>>);
+
+  /// Reads a token `insert_name` of `size` bytes after the symbol `name` (after the lookahead
+  /// symbol if `name` is empty), the read bytes are the value of the token.
+  fn read_bytes(&mut self, name: &str, insert_name: &str, size: usize);
+
+  /// Switches the lexer to the mode, the lookahead symbol is already read in the previous mode.
+  fn begin(&mut self, mode: &str);
+
+  /// Saves the current mode of the lexer on the stack and switches to the mode.
+  fn push_mode(&mut self, mode: &str);
+
+  /// Switches the lexer to the mode saved on the stack.
+  fn pop_mode(&mut self);
+}
+
+///
+/// Grammar actions.
+///
+/// `parser_action` is called on every reduce of a production without rust action,
+/// `action` is the text of the production action (the code in braces) if any.
+///
+pub trait ParserActions: LexActions {
+  #[allow(unused_variables)]
+  fn parser_action(&mut self, production: &GrammarProduction, action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    Ok(())
+  }
+
+  /// Called when the parser recovers from the syntax error by the `error` production.
+  #[allow(unused_variables)]
+  fn parse_error(&mut self, err: &Error) -> Result<(), String> {
+    Ok(())
+  }
+
+  /// Called by the GLR parser with the parse trees of the accepted input.
+  #[allow(unused_variables)]
+  fn parse_trees(&mut self, trees: Vec<ParseTree>) -> Result<(), String> {
+    Ok(())
+  }
+}
+
+///
+/// Actions which do nothing.
+///
+pub struct NoActions;
+
+impl LexActions for NoActions {}
+
+impl ParserActions for NoActions {}
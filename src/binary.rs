@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::utils::*;
+use super::error::*;
+use super::dfa;
+use super::lalr;
+use super::lalr::{GrammarBuilder, StatesBuilder, LALRBuilder, LRBuilder, MinimalLRBuilder, GLRBuilder, ActionStatesOpt};
+use super::parser::ParserType;
+
+const MAGIC: &[u8] = b"MRDT";
+const VERSION: usize = 6;
+
+///
+/// Writer of the binary format
+///
+/// Integers are written in LEB128. Names of symbols are written as indexes in the table of
+/// original names, because the hash of name depends on the order of `hash` calls.
+///
+#[derive(Default)]
+pub struct BinaryWriter {
+  data: Vec<u8>,
+  names: Vec<usize>,
+  indexes: HashMap<usize, usize>
+}
+
+impl BinaryWriter {
+  pub fn new() -> Self {
+    Self {
+      data: vec!(),
+      names: vec!(),
+      indexes: HashMap::new()
+    }
+  }
+
+  pub fn write_usize(&mut self, value: usize) {
+    write_leb128(&mut self.data, value);
+  }
+
+  pub fn write_bool(&mut self, value: bool) {
+    self.data.push(value as u8);
+  }
+
+  /// Writes `None` as 0 and `Some(value)` as `value + 1`.
+  pub fn write_option(&mut self, value: Option<usize>) {
+    match value {
+      Some(value) => self.write_usize(value + 1),
+      _ => self.write_usize(0)
+    }
+  }
+
+  pub fn write_bytes(&mut self, value: &[u8]) {
+    self.write_usize(value.len());
+    self.data.extend_from_slice(value);
+  }
+
+  pub fn write_str(&mut self, value: &str) {
+    self.write_bytes(value.as_bytes());
+  }
+
+  pub fn write_name(&mut self, name: usize) {
+    let index = match self.indexes.get(&name) {
+      Some(index) => *index,
+      _ => {
+        let index = self.names.len();
+        self.names.push(name);
+        self.indexes.insert(name, index);
+        index
+      }
+    };
+    self.write_usize(index);
+  }
+
+  /// Returns the header, the table of names and the written data.
+  pub fn into_bytes(self) -> Vec<u8> {
+    let mut result = MAGIC.to_vec();
+    write_leb128(&mut result, VERSION);
+    write_leb128(&mut result, self.names.len());
+    for name in self.names {
+      let name = get_original_name(name).unwrap_or_default();
+      write_leb128(&mut result, name.len());
+      result.extend_from_slice(name.as_bytes());
+    }
+    result.extend(self.data);
+    result
+  }
+}
+
+fn write_leb128(data: &mut Vec<u8>, mut value: usize) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      data.push(byte);
+      break;
+    }
+    data.push(byte | 0x80);
+  }
+}
+
+///
+/// Reader of the binary format written by `BinaryWriter`
+///
+pub struct BinaryReader<'a> {
+  data: &'a [u8],
+  position: usize,
+  names: Vec<usize>
+}
+
+impl<'a> BinaryReader<'a> {
+  /// Checks the header and reads the table of names.
+  pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+    if !data.starts_with(MAGIC) {
+      return Err(Error::InvalidBinary {message: "Invalid header".to_string()});
+    }
+    let mut reader = Self {
+      data,
+      position: MAGIC.len(),
+      names: vec!()
+    };
+    let version = reader.read_usize()?;
+    if version != VERSION {
+      return Err(Error::InvalidBinary {message: format!("Unsupported version {}", version)});
+    }
+    let len = reader.read_usize()?;
+    for _ in 0..len {
+      let name = reader.read_string()?;
+      reader.names.push(hash(&name));
+    }
+    Ok(reader)
+  }
+
+  fn error(&self) -> Error {
+    Error::InvalidBinary {message: format!("Unexpected end of data at byte {}", self.position)}
+  }
+
+  pub fn read_usize(&mut self) -> Result<usize, Error> {
+    let mut value: usize = 0;
+    let mut shift = 0;
+    loop {
+      let byte = *self.data.get(self.position).ok_or_else(|| self.error())?;
+      self.position += 1;
+      if shift >= usize::BITS {
+        return Err(Error::InvalidBinary {message: format!("Invalid integer at byte {}", self.position)});
+      }
+      value |= ((byte & 0x7f) as usize) << shift;
+      if byte & 0x80 == 0 {break}
+      shift += 7;
+    }
+    Ok(value)
+  }
+
+  pub fn read_bool(&mut self) -> Result<bool, Error> {
+    Ok(self.read_usize()? != 0)
+  }
+
+  pub fn read_option(&mut self) -> Result<Option<usize>, Error> {
+    match self.read_usize()? {
+      0 => Ok(None),
+      value => Ok(Some(value - 1))
+    }
+  }
+
+  pub fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+    let len = self.read_usize()?;
+    let end = self.position.checked_add(len).filter(|end| *end <= self.data.len()).ok_or_else(|| self.error())?;
+    let value = self.data[self.position..end].to_vec();
+    self.position = end;
+    Ok(value)
+  }
+
+  pub fn read_string(&mut self) -> Result<String, Error> {
+    let value = self.read_bytes()?;
+    String::from_utf8(value).map_err(|err| Error::InvalidBinary {message: err.to_string()})
+  }
+
+  pub fn read_name(&mut self) -> Result<usize, Error> {
+    let index = self.read_usize()?;
+    match self.names.get(index) {
+      Some(name) => Ok(*name),
+      _ => Err(Error::InvalidBinary {message: format!("Invalid index of name {}", index)})
+    }
+  }
+}
+
+///
+/// Compiled tables of the lexer and the parser
+///
+#[derive(Clone)]
+pub struct Tables {
+  lex_states: Rc<Vec<Option<dfa::State>>>,
+  lex_goto_states: Rc<dfa::GotoStatesOpt>,
+  grammar: Rc<Grammar>,
+  goto_states: Rc<lalr::GotoStatesOpt>,
+  action_states: Rc<ActionStatesOpt>,
+  lex_stats: Option<dfa::DFAStats>
+}
+
+impl Tables {
+  /// Builds tables from the text of regular definitions and the text of grammar.
+  pub fn build(reg_exp: String, grammar: String, parser_type: ParserType) -> Result<Tables, Error> {
+    let (lex_states, lex_goto_states, lex_stats) = dfa::build_with_stats(reg_exp)?;
+    let grammar = GrammarBuilder::from_text(grammar)?;
+    let goto_states;
+    let action_states;
+    match parser_type {
+      ParserType::LALR1 => {
+        goto_states = LALRBuilder::build_goto_states(&grammar);
+        action_states = LALRBuilder::build_action_states(&grammar, &goto_states)?;
+      },
+      ParserType::LR1 => {
+        goto_states = LRBuilder::build_goto_states(&grammar);
+        action_states = LRBuilder::build_action_states(&grammar, &goto_states)?;
+      },
+      ParserType::MinimalLR1 => {
+        goto_states = MinimalLRBuilder::build_goto_states(&grammar);
+        action_states = MinimalLRBuilder::build_action_states(&grammar, &goto_states)?;
+      },
+      ParserType::GLR => {
+        goto_states = GLRBuilder::build_goto_states(&grammar);
+        action_states = GLRBuilder::build_action_states(&grammar, &goto_states)?;
+      }
+    }
+    Ok(Tables {
+      lex_states: Rc::new(lex_states),
+      lex_goto_states: Rc::new(lex_goto_states),
+      grammar: Rc::new(grammar),
+      goto_states: Rc::new(goto_states),
+      action_states: Rc::new(action_states),
+      lex_stats: Some(lex_stats)
+    })
+  }
+
+  pub fn lex_states(&self) -> &Rc<Vec<Option<dfa::State>>> {
+    &self.lex_states
+  }
+
+  pub fn lex_goto_states(&self) -> &Rc<dfa::GotoStatesOpt> {
+    &self.lex_goto_states
+  }
+
+  pub fn grammar(&self) -> &Rc<Grammar> {
+    &self.grammar
+  }
+
+  pub fn goto_states(&self) -> &Rc<lalr::GotoStatesOpt> {
+    &self.goto_states
+  }
+
+  pub fn action_states(&self) -> &Rc<ActionStatesOpt> {
+    &self.action_states
+  }
+
+  /// Number of states of the lexer DFA before and after minimization, is not kept in the binary format.
+  pub fn lex_stats(&self) -> Option<dfa::DFAStats> {
+    self.lex_stats
+  }
+
+  /// Serializes tables, the resolved conflicts of grammar are not kept.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut writer = BinaryWriter::new();
+    writer.write_usize(self.lex_states.len());
+    for state in self.lex_states.iter() {
+      match state {
+        Some(state) => {
+          writer.write_bool(true);
+          state.write(&mut writer);
+        },
+        _ => writer.write_bool(false)
+      }
+    }
+    self.lex_goto_states.write(&mut writer);
+    self.grammar.write(&mut writer);
+    self.goto_states.write(&mut writer);
+    self.action_states.write(&mut writer, &self.grammar);
+    writer.into_bytes()
+  }
+
+  pub fn from_bytes(data: &[u8]) -> Result<Tables, Error> {
+    let mut reader = BinaryReader::new(data)?;
+    let len = reader.read_usize()?;
+    let mut lex_states = Vec::with_capacity(len.min(data.len()));
+    for _ in 0..len {
+      lex_states.push(match reader.read_bool()? {
+        true => Some(dfa::State::read(&mut reader)?),
+        false => None
+      });
+    }
+    let lex_goto_states = dfa::GotoStatesOpt::read(&mut reader)?;
+    let grammar = Grammar::read(&mut reader)?;
+    let goto_states = lalr::GotoStatesOpt::read(&mut reader)?;
+    let action_states = ActionStatesOpt::read(&mut reader, &grammar)?;
+    Ok(Tables {
+      lex_states: Rc::new(lex_states),
+      lex_goto_states: Rc::new(lex_goto_states),
+      grammar: Rc::new(grammar),
+      goto_states: Rc::new(goto_states),
+      action_states: Rc::new(action_states),
+      lex_stats: None
+    })
+  }
+}
@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::utils::*;
+use super::error::Error;
+use super::lalr::ActionState;
+use super::binary::Tables;
+use super::rust_action::{RustFunction, RustExpr, RustStatement};
+
+/// Runtime of the generated module: the lexer and the parser driven by the static tables.
+const RUNTIME: &str = r#"
+/// Returns the symbol by its name.
+pub fn symbol(name: &str) -> Option<usize> {
+  SYMBOLS.iter().position(|item| *item == name)
+}
+
+fn find<T: Copy>(items: &[(usize, T)], key: usize) -> Option<T> {
+  items.binary_search_by_key(&key, |item| item.0).ok().map(|index| items[index].1)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+  /// No lexer rule matches the input starting at the byte offset.
+  LexNoMatch {offset: usize},
+  /// Symbol is not expected in the parser state.
+  UnexpectedSymbol {state: usize, symbol: usize, offset: usize},
+  /// Failure of the lexer or grammar action.
+  Action {message: String}
+}
+
+///
+/// Actions of the lexer rules and the grammar productions
+///
+pub trait Actions {
+  /// Called for the token recognized by the rule with action (the code in braces), returns
+  /// the symbol of token or `None` to skip the token.
+  #[allow(unused_variables)]
+  fn lex_action(&mut self, symbol: usize, action: &str, value: &mut Vec<u8>) -> Result<Option<usize>, String> {
+    Ok(Some(symbol))
+  }
+
+  /// Called on reduce of the production without rust action, `action` is the text of the action
+  /// in braces, `values` are the values of symbols of the production from left to right, returns
+  /// the value of new symbol.
+  #[allow(unused_variables)]
+  fn reduce(&mut self, production: usize, action: Option<&str>, values: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    Ok(vec!())
+  }
+}
+
+///
+/// Lexer of the whole input
+///
+pub struct Lexer<'a> {
+  input: &'a [u8],
+  position: usize
+}
+
+impl<'a> Lexer<'a> {
+  pub fn new(input: &'a [u8]) -> Self {
+    Self {
+      input,
+      position: 0
+    }
+  }
+
+  /// Returns the symbol, the value and the byte offset of next token, `S_TERM` at the end of input.
+  pub fn next_token<A: Actions>(&mut self, actions: &mut A) -> Result<(usize, Vec<u8>, usize), Error> {
+    loop {
+      let start = self.position;
+      if start >= self.input.len() {
+        return Ok((S_TERM, vec!(), start));
+      }
+      let mut state = 0;
+      let mut accepted = None;
+      for position in start..self.input.len() {
+        let transitions = LEX_GOTO[state];
+        match transitions.binary_search_by_key(&self.input[position], |item| item.0) {
+          Ok(index) => state = transitions[index].1,
+          Err(_) => break
+        }
+        if let Some(accept) = LEX_ACCEPT[state] {
+          accepted = Some((accept, position + 1));
+        }
+      }
+      let ((symbol, action), end) = accepted.ok_or(Error::LexNoMatch {offset: start})?;
+      self.position = end;
+      let mut value = self.input[start..end].to_vec();
+      let symbol = match action {
+        Some(action) => actions.lex_action(symbol, action, &mut value).map_err(|message| Error::Action {message})?,
+        _ => Some(symbol)
+      };
+      if let Some(symbol) = symbol {
+        return Ok((symbol, value, start));
+      }
+    }
+  }
+}
+
+/// Parses the input, returns the value of the start symbol.
+pub fn parse<A: Actions>(input: &[u8], actions: &mut A) -> Result<Vec<u8>, Error> {
+  let mut lexer = Lexer::new(input);
+  let mut stack: Vec<(usize, Vec<u8>)> = vec!((0, vec!()));
+  let (mut symbol, mut value, mut offset) = lexer.next_token(actions)?;
+  loop {
+    let state = stack[stack.len() - 1].0;
+    let (action, is_e_symbol) = match find(ACTION[state], symbol) {
+      Some(action) => (action, false),
+      _ => match find(ACTION[state], E_TERM) {
+        Some(action) => (action, true),
+        _ => return Err(Error::UnexpectedSymbol {state, symbol, offset})
+      }
+    };
+    match action {
+      Action::Shift(goto) => {
+        if is_e_symbol {
+          stack.push((goto, vec!()));
+        } else {
+          stack.push((goto, std::mem::take(&mut value)));
+          let token = lexer.next_token(actions)?;
+          symbol = token.0;
+          value = token.1;
+          offset = token.2;
+        }
+      },
+      Action::Reduce(production) => {
+        let len = PRODUCTIONS[production].1;
+        let values: Vec<Vec<u8>> = stack.drain(stack.len() - len..).map(|item| item.1).collect();
+        let lookup = if symbol == S_TERM {None} else {Some(&value[..])};
+        let (name, new_value) = reduce(actions, production, &values, lookup).map_err(|message| Error::Action {message})?;
+        let state = stack[stack.len() - 1].0;
+        let goto = find(GOTO[state], name).ok_or(Error::UnexpectedSymbol {state, symbol: name, offset})?;
+        stack.push((goto, new_value));
+      },
+      Action::Accept => return Ok(stack.pop().map(|item| item.1).unwrap_or_default())
+    }
+  }
+}
+"#;
+
+/// Runtime of the compiled rust actions, it is generated if some action does more than
+/// the concatenation of values of symbols.
+const ACTIONS_RUNTIME: &str = r#"
+/// Value of the expression of rust action, the numeric value is converted to the decimal text
+/// when it is set.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+  None,
+  Bytes(Vec<u8>),
+  Int(i64)
+}
+
+#[allow(dead_code)]
+impl Value {
+  fn option(value: Option<&[u8]>) -> Self {
+    match value {
+      Some(value) => Value::Bytes(value.to_vec()),
+      _ => Value::None
+    }
+  }
+
+  fn bool(value: bool) -> Self {
+    Value::Int(value as i64)
+  }
+
+  fn concat(values: Vec<Value>) -> Self {
+    let mut result: Option<Vec<u8>> = None;
+    for value in values {
+      if let Some(value) = value.into_bytes() {
+        result.get_or_insert_with(Vec::new).extend(value);
+      }
+    }
+    result.map(Value::Bytes).unwrap_or(Value::None)
+  }
+
+  fn into_bytes(self) -> Option<Vec<u8>> {
+    match self {
+      Value::None => None,
+      Value::Bytes(value) => Some(value),
+      Value::Int(value) => Some(value.to_string().into_bytes())
+    }
+  }
+
+  fn into_string(self) -> Option<String> {
+    self.into_bytes().map(|value| String::from_utf8_lossy(&value).to_string())
+  }
+
+  fn map_bytes<F: Fn(Vec<u8>) -> Vec<u8>>(self, f: F) -> Self {
+    match self.into_bytes() {
+      Some(value) => Value::Bytes(f(value)),
+      _ => Value::None
+    }
+  }
+
+  fn parse(&self, radix: u32) -> Result<i64, String> {
+    match self {
+      Value::Int(value) => Ok(*value),
+      Value::Bytes(value) => {
+        let text = String::from_utf8_lossy(value);
+        i64::from_str_radix(text.trim(), radix).map_err(|_| format!("Invalid number '{}'", text))
+      },
+      Value::None => Err("Number is expected, got none".to_string())
+    }
+  }
+
+  /// Parses the bytes as the big-endian or the little-endian unsigned integer.
+  fn parse_binary(self, big_endian: bool) -> Result<i64, String> {
+    let mut value = match self {
+      Value::Int(value) => return Ok(value),
+      Value::Bytes(value) => value,
+      Value::None => return Err("Number is expected, got none".to_string())
+    };
+    if value.len() > 8 {
+      return Err(format!("Invalid number of {} bytes", value.len()));
+    }
+    if !big_endian {
+      value.reverse();
+    }
+    Ok(value.iter().fold(0u64, |result, code| (result << 8) | *code as u64) as i64)
+  }
+
+  fn equals(self, other: Value) -> bool {
+    match (self, other) {
+      (Value::Int(value1), Value::Int(value2)) => value1 == value2,
+      (value1, value2) => value1.into_bytes() == value2.into_bytes()
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    match self {
+      Value::None => true,
+      Value::Bytes(value) => value.is_empty(),
+      Value::Int(_) => false
+    }
+  }
+
+  fn is_true(&self) -> bool {
+    match self {
+      Value::None => false,
+      Value::Bytes(value) => !value.is_empty(),
+      Value::Int(value) => *value != 0
+    }
+  }
+}
+"#;
+
+///
+/// Generator of the standalone Rust module with static tables of the lexer and the parser
+///
+/// Symbols of the generated module are indexes in `SYMBOLS`. Rust actions of productions are
+/// compiled to arms of `match` over the production index, other actions are passed as text
+/// to the `Actions` trait. Error recovery, GLR parsing, lexer modes, `bind`, `push_after` and
+/// `read_bytes` are not supported.
+///
+pub struct RustGenerator<'a> {
+  tables: &'a Tables,
+  symbols: Vec<usize>,
+  indexes: HashMap<usize, usize>
+}
+
+impl<'a> RustGenerator<'a> {
+  pub fn new(tables: &'a Tables) -> Self {
+    let mut generator = Self {
+      tables,
+      symbols: vec!(),
+      indexes: HashMap::new()
+    };
+    generator.index(GrammarSymbol::e_term().name());
+    generator.index(GrammarSymbol::s_term().name());
+    for symbol in tables.grammar().symbols() {
+      generator.index(symbol.name());
+    }
+    for state in tables.lex_states().iter().flatten() {
+      generator.index(state.accept());
+    }
+    generator
+  }
+
+  fn index(&mut self, name: usize) -> usize {
+    match self.indexes.get(&name) {
+      Some(index) => *index,
+      _ => {
+        let index = self.symbols.len();
+        self.symbols.push(name);
+        self.indexes.insert(name, index);
+        index
+      }
+    }
+  }
+
+  fn symbol(&self, name: usize) -> usize {
+    self.indexes[&name]
+  }
+
+  /// Returns the code of the module or the error if the tables use the unsupported feature.
+  pub fn generate(&self) -> Result<String, Error> {
+    self.check()?;
+    let mut code = String::new();
+    self.write(&mut code).unwrap();
+    Ok(code)
+  }
+
+  fn check(&self) -> Result<(), Error> {
+    let unsupported = |message: String| Err(Error::Unsupported {message});
+    if self.tables.action_states().has_alternatives() {
+      return unsupported("conflicting actions of GLR parser in the generated module".to_string());
+    }
+    if !self.tables.lex_goto_states().modes().is_empty() {
+      return unsupported("lexer modes in the generated module".to_string());
+    }
+    let grammar = self.tables.grammar();
+    for i in 0..grammar.len() {
+      let production = grammar.production(i);
+      let rust_action = production.attr(&hash("rust_action")).and_then(|action| action.as_rust_action());
+      if let Some(name) = rust_action.and_then(|rust_action| unsupported_statement(rust_action.statements())) {
+        return unsupported(format!("{} in the generated module, production {}", name, production));
+      }
+    }
+    Ok(())
+  }
+
+  fn write(&self, code: &mut String) -> std::fmt::Result {
+    let grammar = self.tables.grammar();
+    writeln!(code, "// Generated by server::codegen, do not edit.")?;
+    writeln!(code)?;
+
+    writeln!(code, "/// Names of symbols, the symbol is the index of its name.")?;
+    writeln!(code, "pub static SYMBOLS: [&str; {}] = [", self.symbols.len())?;
+    for name in &self.symbols {
+      writeln!(code, "  {:?},", get_original_name(*name).unwrap_or_default())?;
+    }
+    writeln!(code, "];")?;
+    writeln!(code, "pub const E_TERM: usize = {};", self.symbol(GrammarSymbol::e_term().name()))?;
+    writeln!(code, "pub const S_TERM: usize = {};", self.symbol(GrammarSymbol::s_term().name()))?;
+    writeln!(code)?;
+
+    let lex_states = self.tables.lex_states();
+    let lex_goto_states = self.tables.lex_goto_states();
+    let len = lex_states.len().max(lex_goto_states.len());
+    writeln!(code, "/// Accepted symbol and action of the lexer state.")?;
+    writeln!(code, "static LEX_ACCEPT: [Option<(usize, Option<&str>)>; {}] = [", len)?;
+    for state in 0..len {
+      match lex_states.get(state) {
+        Some(Some(state)) => {
+          let action = match state.action() {
+            Some(action) => format!("Some({:?})", action.as_str()),
+            _ => "None".to_string()
+          };
+          writeln!(code, "  Some(({}, {})),", self.symbol(state.accept()), action)?;
+        },
+        _ => writeln!(code, "  None,")?
+      }
+    }
+    writeln!(code, "];")?;
+    writeln!(code, "/// Transitions of the lexer state as pairs of byte and goto state.")?;
+    writeln!(code, "static LEX_GOTO: [&[(u8, usize)]; {}] = [", len)?;
+    for state in 0..len {
+      let transitions: Vec<String> = lex_goto_states.transitions(state).iter()
+        .map(|(code, goto)| format!("({}, {})", code, goto)).collect();
+      writeln!(code, "  &[{}],", transitions.join(", "))?;
+    }
+    writeln!(code, "];")?;
+    writeln!(code)?;
+
+    let action_states = self.tables.action_states();
+    let goto_states = self.tables.goto_states();
+    let len = action_states.len().max(goto_states.len());
+    writeln!(code, "#[derive(Clone, Copy, Debug, PartialEq)]")?;
+    writeln!(code, "pub enum Action {{")?;
+    writeln!(code, "  Shift(usize),")?;
+    writeln!(code, "  Reduce(usize),")?;
+    writeln!(code, "  Accept")?;
+    writeln!(code, "}}")?;
+    writeln!(code)?;
+    writeln!(code, "/// Actions of the parser state as pairs of terminal and action.")?;
+    writeln!(code, "static ACTION: [&[(usize, Action)]; {}] = [", len)?;
+    for state in 0..len {
+      let mut actions: Vec<(usize, String)> = action_states.actions(state).iter().map(|(name, action)| {
+        let production = match action.production() {
+          Some(production) => grammar.production_index(production).unwrap_or_default(),
+          _ => 0
+        };
+        let action = match action.state() {
+          ActionState::Shift => format!("Action::Shift({})", action.goto().unwrap_or_default()),
+          ActionState::Reduce => format!("Action::Reduce({})", production),
+          ActionState::Accept => "Action::Accept".to_string()
+        };
+        (self.symbol(*name), action)
+      }).collect();
+      actions.sort();
+      let actions: Vec<String> = actions.iter().map(|(symbol, action)| format!("({}, {})", symbol, action)).collect();
+      writeln!(code, "  &[{}],", actions.join(", "))?;
+    }
+    writeln!(code, "];")?;
+
+    let nonterms: Vec<usize> = (0..grammar.len()).map(|i| grammar.production(i).name()).collect();
+    writeln!(code, "/// Goto states of the parser state as pairs of nonterminal and goto state.")?;
+    writeln!(code, "static GOTO: [&[(usize, usize)]; {}] = [", len)?;
+    for state in 0..len {
+      let mut gotos: Vec<(usize, usize)> = goto_states.transitions(state).into_iter()
+        .filter(|(name, _)| nonterms.contains(name))
+        .map(|(name, goto)| (self.symbol(name), goto)).collect();
+      gotos.sort();
+      let gotos: Vec<String> = gotos.iter().map(|(symbol, goto)| format!("({}, {})", symbol, goto)).collect();
+      writeln!(code, "  &[{}],", gotos.join(", "))?;
+    }
+    writeln!(code, "];")?;
+    writeln!(code, "/// Nonterminal and length of the production.")?;
+    writeln!(code, "pub static PRODUCTIONS: [(usize, usize); {}] = [", grammar.len())?;
+    for i in 0..grammar.len() {
+      let production = grammar.production(i);
+      writeln!(code, "  ({}, {}), // {}", self.symbol(production.name()), production.len(), production)?;
+    }
+    writeln!(code, "];")?;
+    writeln!(code)?;
+
+    let mut compiled = false;
+    let mut arms = String::new();
+    for i in 0..grammar.len() {
+      let production = grammar.production(i);
+      let rust_action = production.attr(&hash("rust_action")).and_then(|action| action.as_rust_action());
+      let action = production.attr(&hash("action")).and_then(|action| action.as_string());
+      let indexes = rust_action.and_then(|rust_action| rust_action.symbols());
+      if let Some(indexes) = indexes {
+        let indexes: Vec<String> = indexes.iter()
+          .map(|index| format!("&values[{}][..]", production.len().saturating_sub(index + 1))).collect();
+        writeln!(arms, "    {} => Ok((name, [{}].concat())),", i, indexes.join(", "))?;
+      } else if let Some(rust_action) = rust_action {
+        let statements = rust_action.statements();
+        compiled = true;
+        writeln!(arms, "    // {}", rust_action.text())?;
+        writeln!(arms, "    {} => {{", i)?;
+        if contains(statements, &|statement| matches!(statement, RustStatement::SetName(_))) {
+          writeln!(arms, "      let mut name = name;")?;
+        }
+        if contains(statements, &|statement| matches!(statement, RustStatement::Set(_))) {
+          writeln!(arms, "      let mut value = vec!();")?;
+        } else {
+          writeln!(arms, "      let value = vec!();")?;
+        }
+        write_statements(&mut arms, statements, production.len(), "      ")?;
+        writeln!(arms, "      Ok((name, value))")?;
+        writeln!(arms, "    }},")?;
+      } else if let Some(action) = action {
+        writeln!(arms, "    {} => Ok((name, actions.reduce(production, Some({:?}), values)?)),", i, action)?;
+      }
+    }
+    writeln!(code, "/// Executes the action of the production, returns the symbol and the value of new symbol.")?;
+    writeln!(code, "#[allow(unused_variables, unused_assignments)]")?;
+    writeln!(code, "fn reduce<A: Actions>(actions: &mut A, production: usize, values: &[Vec<u8>],")?;
+    writeln!(code, "  lookup: Option<&[u8]>) -> Result<(usize, Vec<u8>), String> {{")?;
+    writeln!(code, "  let name = PRODUCTIONS[production].0;")?;
+    if arms.is_empty() {
+      writeln!(code, "  Ok((name, actions.reduce(production, None, values)?))")?;
+    } else {
+      writeln!(code, "  match production {{")?;
+      write!(code, "{}", arms)?;
+      writeln!(code, "    _ => Ok((name, actions.reduce(production, None, values)?))")?;
+      writeln!(code, "  }}")?;
+    }
+    writeln!(code, "}}")?;
+    write!(code, "{}", RUNTIME)?;
+    if compiled {
+      write!(code, "{}", ACTIONS_RUNTIME)?;
+    }
+    Ok(())
+  }
+}
+
+/// Returns the name of the first statement which the generated module does not support.
+fn unsupported_statement(statements: &[RustStatement]) -> Option<&'static str> {
+  statements.iter().find_map(|statement| match statement {
+    RustStatement::Set(_) | RustStatement::SetName(_) => None,
+    RustStatement::Bind(_) => Some("bind"),
+    RustStatement::PushAfter(_) => Some("push_after"),
+    RustStatement::ReadBytes(_) => Some("read_bytes"),
+    RustStatement::Begin(_) | RustStatement::PushMode(_) | RustStatement::PopMode => Some("lexer modes"),
+    RustStatement::If(_, statements, else_statements) =>
+      unsupported_statement(statements).or_else(|| unsupported_statement(else_statements))
+  })
+}
+
+fn contains(statements: &[RustStatement], predicate: &dyn Fn(&RustStatement) -> bool) -> bool {
+  statements.iter().any(|statement| match statement {
+    RustStatement::If(_, statements, else_statements) =>
+      contains(statements, predicate) || contains(else_statements, predicate),
+    statement => predicate(statement)
+  })
+}
+
+fn write_statements(code: &mut String, statements: &[RustStatement], len: usize, indent: &str) -> std::fmt::Result {
+  for statement in statements {
+    match statement {
+      RustStatement::Set(args) => {
+        writeln!(code, "{}value = Value::concat(vec!({})).into_bytes().unwrap_or(value);", indent, exprs(args, len))?;
+      },
+      RustStatement::SetName(arg) => {
+        writeln!(code, "{}let symbol_name = {}.into_string().ok_or(\"Name is expected, got none\")?;", indent, expr(arg, len))?;
+        writeln!(code, "{}name = symbol(&symbol_name).ok_or_else(|| format!(\"Unknown symbol {{}}\", symbol_name))?;", indent)?;
+      },
+      RustStatement::If(condition, statements, else_statements) => {
+        write!(code, "{}", indent)?;
+        write_if(code, condition, statements, else_statements, len, indent)?;
+        writeln!(code)?;
+      },
+      // rejected by `RustGenerator::check`
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+fn write_if(code: &mut String, condition: &RustExpr, statements: &[RustStatement],
+  else_statements: &[RustStatement], len: usize, indent: &str) -> std::fmt::Result {
+  let inner = format!("{}  ", indent);
+  writeln!(code, "if {} {{", self::condition(condition, len))?;
+  write_statements(code, statements, len, &inner)?;
+  write!(code, "{}}}", indent)?;
+  match else_statements {
+    [] => {},
+    [RustStatement::If(condition, statements, else_statements)] => {
+      write!(code, " else ")?;
+      write_if(code, condition, statements, else_statements, len, indent)?;
+    },
+    _ => {
+      writeln!(code, " else {{")?;
+      write_statements(code, else_statements, len, &inner)?;
+      write!(code, "{}}}", indent)?;
+    }
+  }
+  Ok(())
+}
+
+fn exprs(args: &[RustExpr], len: usize) -> String {
+  args.iter().map(|arg| expr(arg, len)).collect::<Vec<String>>().join(", ")
+}
+
+/// Returns the code of the expression of type `Value`, `len` is the length of the production.
+fn expr(expr: &RustExpr, len: usize) -> String {
+  let (function, args) = match expr {
+    RustExpr::Symbol(index) if *index < len => return format!("Value::Bytes(values[{}].clone())", len - index - 1),
+    RustExpr::Symbol(_) | RustExpr::None => return "Value::None".to_string(),
+    RustExpr::Bytes(value) => return format!("Value::Bytes(b\"{}\".to_vec())", value.escape_ascii()),
+    RustExpr::Call(function, args) => (function, args)
+  };
+  let arg = |index: usize| self::expr(&args[index], len);
+  match function {
+    RustFunction::Concat => format!("Value::concat(vec!({}))", exprs(args, len)),
+    RustFunction::Trim => format!("{}.map_bytes(|value| value.trim_ascii().to_vec())", arg(0)),
+    RustFunction::Lower => format!("{}.map_bytes(|value| value.to_ascii_lowercase())", arg(0)),
+    RustFunction::Upper => format!("{}.map_bytes(|value| value.to_ascii_uppercase())", arg(0)),
+    RustFunction::ParseInt => format!("Value::Int({}.parse(10)?)", arg(0)),
+    RustFunction::ParseHex => format!("Value::Int({}.parse(16)?)", arg(0)),
+    RustFunction::Eq | RustFunction::Ne | RustFunction::Lt | RustFunction::Gt | RustFunction::Not |
+      RustFunction::And | RustFunction::Or | RustFunction::Empty => format!("Value::bool({})", self::condition(expr, len)),
+    RustFunction::Lookup => "Value::option(lookup)".to_string(),
+    RustFunction::ParseBE => format!("Value::Int({}.parse_binary(true)?)", arg(0)),
+    RustFunction::ParseLE => format!("Value::Int({}.parse_binary(false)?)", arg(0))
+  }
+}
+
+/// Returns the code of the expression of type `bool`.
+fn condition(expr: &RustExpr, len: usize) -> String {
+  let (function, args) = match expr {
+    RustExpr::Call(function, args) => (function, args),
+    expr => return format!("{}.is_true()", self::expr(expr, len))
+  };
+  let arg = |index: usize| self::expr(&args[index], len);
+  let all = |operator: &str| args.iter().map(|arg| {
+    let condition = self::condition(arg, len);
+    if matches!(arg, RustExpr::Call(RustFunction::And | RustFunction::Or, _)) {format!("({})", condition)} else {condition}
+  }).collect::<Vec<String>>().join(operator);
+  match function {
+    RustFunction::Eq => format!("{}.equals({})", arg(0), arg(1)),
+    RustFunction::Ne => format!("!{}.equals({})", arg(0), arg(1)),
+    RustFunction::Lt => format!("{}.parse(10)? < {}.parse(10)?", arg(0), arg(1)),
+    RustFunction::Gt => format!("{}.parse(10)? > {}.parse(10)?", arg(0), arg(1)),
+    RustFunction::Not => match &args[0] {
+      RustExpr::Call(RustFunction::And | RustFunction::Or | RustFunction::Lt | RustFunction::Gt, _) =>
+        format!("!({})", self::condition(&args[0], len)),
+      arg => format!("!{}", self::condition(arg, len))
+    },
+    RustFunction::And => all(" && "),
+    RustFunction::Or => all(" || "),
+    RustFunction::Empty => format!("{}.is_empty()", arg(0)),
+    _ => format!("{}.is_true()", self::expr(expr, len))
+  }
+}
+
+/// Generates the Rust module for the compiled tables.
+pub fn generate_rust(tables: &Tables) -> Result<String, Error> {
+  RustGenerator::new(tables).generate()
+}
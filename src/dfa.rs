@@ -1,24 +1,30 @@
 use std::collections::{HashMap, BTreeMap, HashSet};
 use std::rc::Rc;
-use js_sys::Function;
-use wasm_bindgen::prelude::JsValue;
 
 use super::utils::*;
 use super::lex::*;
+use super::error::*;
 use super::parser::*;
 use super::dfa_grammar::*;
+use super::binary::{BinaryWriter, BinaryReader};
+
+/// Accepted token name and the action of the rule.
+pub type Accept = (usize, Option<Rc<String>>);
 
 #[derive(Debug, Clone)]
 pub struct State {
   accept: usize,
-  action: Option<Rc<Function>>
+  action: Option<Rc<String>>,
+  /// Tokens of the rules defined later which are also accepted by the state.
+  alternatives: Vec<Accept>
 }
 
 impl State {
-  fn new(accept: usize, action: Option<Rc<Function>>) -> Self {
+  fn new(accept: usize, action: Option<Rc<String>>) -> Self {
     Self {
       accept,
-      action
+      action,
+      alternatives: vec!()
     }
   }
 
@@ -26,9 +32,61 @@ impl State {
     self.accept
   }
 
-  pub fn action(&self) -> &Option<Rc<Function>> {
+  pub fn action(&self) -> &Option<Rc<String>> {
     &self.action
   }
+
+  pub fn alternatives(&self) -> &Vec<Accept> {
+    &self.alternatives
+  }
+
+  /// Returns the first accepted token which satisfies the predicate, the tokens are in the
+  /// order of definition of the rules.
+  pub fn find_accept<P: Fn(usize) -> bool>(&self, predicate: P) -> Option<Accept> {
+    if predicate(self.accept) {
+      return Some((self.accept, self.action.clone()));
+    }
+    self.alternatives.iter().find(|(accept, _)| predicate(*accept)).cloned()
+  }
+
+  fn write_action(writer: &mut BinaryWriter, action: &Option<Rc<String>>) {
+    match action {
+      Some(ref action) => {
+        writer.write_bool(true);
+        writer.write_str(action);
+      },
+      _ => writer.write_bool(false)
+    }
+  }
+
+  fn read_action(reader: &mut BinaryReader) -> Result<Option<Rc<String>>, Error> {
+    Ok(match reader.read_bool()? {
+      true => Some(Rc::new(reader.read_string()?)),
+      false => None
+    })
+  }
+
+  pub fn write(&self, writer: &mut BinaryWriter) {
+    writer.write_name(self.accept);
+    Self::write_action(writer, &self.action);
+    writer.write_usize(self.alternatives.len());
+    for (accept, action) in &self.alternatives {
+      writer.write_name(*accept);
+      Self::write_action(writer, action);
+    }
+  }
+
+  pub fn read(reader: &mut BinaryReader) -> Result<Self, Error> {
+    let accept = reader.read_name()?;
+    let action = Self::read_action(reader)?;
+    let mut state = Self::new(accept, action);
+    let len = reader.read_usize()?;
+    for _ in 0..len {
+      let accept = reader.read_name()?;
+      state.alternatives.push((accept, Self::read_action(reader)?));
+    }
+    Ok(state)
+  }
 }
 
 #[derive(Debug)]
@@ -120,7 +178,6 @@ impl GotoStates {
     state1.set_state(code, goto_state);
   }
 
-  #[allow(dead_code)]
   pub fn state_exists(&self, state: usize) -> bool {
     self.states.get(&state).is_some()
   }
@@ -128,7 +185,9 @@ impl GotoStates {
 
 #[derive(Debug)]
 pub struct GotoStatesOpt {
-  states: Vec<Option<GotoStates1Opt>>
+  states: Vec<Option<GotoStates1Opt>>,
+  /// Start states of the modes of the lexer.
+  modes: Vec<(usize, usize)>
 }
 
 impl GotoStatesOpt {
@@ -150,10 +209,22 @@ impl GotoStatesOpt {
       };
     }
     Self {
-      states: states_opt
+      states: states_opt,
+      modes: vec!()
     }
   }
 
+  /// Start state of the mode of the lexer, the start state of the initial mode is 0.
+  pub fn mode_state(&self, mode: usize) -> Option<usize> {
+    if mode == hash(INITIAL_MODE) {return Some(0)}
+    self.modes.iter().find(|item| item.0 == mode).map(|item| item.1)
+  }
+
+  /// Modes of the lexer other than the initial mode with their start states.
+  pub fn modes(&self) -> &Vec<(usize, usize)> {
+    &self.modes
+  }
+
   pub fn state(&self, state: usize, code: usize) -> Option<&usize> {
     match self.states.get(state) {
       Some(st) => match st {
@@ -171,8 +242,69 @@ impl GotoStatesOpt {
     }
   }
 
+  /// Number of states, including the states without transitions.
+  pub fn len(&self) -> usize {
+    self.states.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.states.is_empty()
+  }
+
+  /// Returns pairs of code and goto state of the state.
+  pub fn transitions(&self, state: usize) -> Vec<(usize, usize)> {
+    match self.states.get(state) {
+      Some(Some(st)) => st.states.iter().enumerate()
+        .filter_map(|(code, goto)| goto.map(|goto| (code, goto))).collect(),
+      _ => vec!()
+    }
+  }
+
+  /// Writes transitions of every state as pairs of code and goto state and the start
+  /// states of the modes.
+  pub fn write(&self, writer: &mut BinaryWriter) {
+    writer.write_usize(self.states.len());
+    for state in 0..self.states.len() {
+      let transitions = self.transitions(state);
+      writer.write_usize(transitions.len());
+      for (code, goto) in transitions {
+        writer.write_usize(code);
+        writer.write_usize(goto);
+      }
+    }
+    writer.write_usize(self.modes.len());
+    for (mode, state) in &self.modes {
+      writer.write_name(*mode);
+      writer.write_usize(*state);
+    }
+  }
+
+  pub fn read(reader: &mut BinaryReader) -> Result<Self, Error> {
+    let mut states = GotoStates::new();
+    let len = reader.read_usize()?;
+    for state in 0..len {
+      let count = reader.read_usize()?;
+      for _ in 0..count {
+        let code = reader.read_usize()?;
+        let goto = reader.read_usize()?;
+        states.set_state(state, code, goto);
+      }
+    }
+    let mut goto_states = Self::from(&states);
+    let len = reader.read_usize()?;
+    for _ in 0..len {
+      let mode = reader.read_name()?;
+      let state = reader.read_usize()?;
+      goto_states.modes.push((mode, state));
+    }
+    Ok(goto_states)
+  }
+
 }
 
+/// Modes of the lexer with the ids of the roots of their trees.
+type ModeRoots = Vec<(usize, Option<usize>)>;
+
 pub struct DFABuilder {
 }
 
@@ -182,34 +314,39 @@ impl DFABuilder {
     }
   }
 
-  fn parse(parser: &mut Parser, text: String, exec_context: ExecContext) -> Result<ExecContext, JsValue> {
-    let id = 0;
-    let js_context = cast_exec_context_to_js_value(id, exec_context);
+  fn parse(parser: &mut Parser, text: String, mut exec_context: ExecContext, line: usize, column: usize)
+    -> Result<ExecContext, Error> {
     parser.set_text(text);
-    let result = parser.parse(&js_context)?;
+    let result = match parser.parse(&mut exec_context) {
+      Ok(result) => result,
+      Err(err) => return Err(Error::RegExpSyntax {line, column, message: err.to_string()})
+    };
     if result == ParseResult::ParseWait {
-      return Err(JsValue::from("Error parse"));
+      return Err(Error::RegExpSyntax {line, column, message: "Unexpected end of expression".to_string()});
     }
-    let exec_context = return_exec_context(id);
     Ok(exec_context)
   }
 
-  fn build_ast(re_def: String) -> Result<ExecContext, JsValue> {
+  /// Builds the tree of every rule and the alternation of the rules of every mode, returns
+  /// the modes with the ids of their trees, the initial mode is the first.
+  fn build_ast(re_def: String) -> Result<(ExecContext, ModeRoots), Error> {
     let mut dfa_lex = Box::new(Lex::new("".to_string()));
-    dfa_lex.set_regular_definition_text(reg_exp().to_string());
+    dfa_lex.set_regular_definition_text(reg_exp().to_string())?;
     let mut dfa_parser = Parser::new(dfa_lex);
     dfa_parser.disable_state_logging();
-    let _ = dfa_parser.set_grammar(grammar().to_string(), ParserType::LALR1);
+    dfa_parser.set_grammar(grammar().to_string(), ParserType::LALR1)?;
 
     let mut lex = Lex::new("".to_string());
-    lex.set_regular_definition_text(re_def);
-
-    let mut v_context: Vec<ExecContext> = vec!(ExecContext::new());
-    for i in 0..lex.rules().len() {
-      let rule = &lex.rules()[i];
-      let exec_context = v_context.pop().unwrap();
-      let prev_root_id = exec_context.last_item_id();
-      let mut exec_context = DFABuilder::parse(&mut dfa_parser, rule.expression().as_str().to_string(), exec_context)?;
+    lex.set_regular_definition_text(re_def)?;
+    if lex.rules().is_empty() {
+      return Err(Error::RegExpSyntax {line: 0, column: 0, message: "No rules are defined".to_string()});
+    }
+
+    let mut exec_context = ExecContext::new();
+    let mut roots: ModeRoots = vec!((hash(INITIAL_MODE), None));
+    for rule in lex.rules() {
+      exec_context = DFABuilder::parse(&mut dfa_parser, rule.expression().as_str().to_string(),
+        exec_context, rule.line(), rule.column())?;
       let builder = exec_context.builder();
       let mut attrs = Attributes::new();
       attrs.attrs.insert(hash("accept"), Box::new(rule.name()));
@@ -218,19 +355,27 @@ impl DFABuilder {
       }
       let leaf = builder.add_leaf("#".to_string(), vec!(), Some(attrs)).unwrap();
       let last = builder.last().unwrap();
-      let _ = builder.add_node(".".to_string(), last, Some(leaf), None);
-
-      match prev_root_id {
-        Some(prev_root_id) => {
-          let last = builder.last().unwrap();
-          let prev_root = builder.by_id(prev_root_id).unwrap();
-          let _ = builder.add_node("|".to_string(), prev_root, Some(last), None);
-        },
-        _ => {}
+      let rule_root = builder.add_node(".".to_string(), last, Some(leaf), None).unwrap();
+
+      for mode in rule.modes() {
+        let index = match roots.iter().position(|(root_mode, _)| root_mode == mode) {
+          Some(index) => index,
+          _ => {
+            roots.push((*mode, None));
+            roots.len() - 1
+          }
+        };
+        let root = match roots[index].1 {
+          Some(prev_root_id) => {
+            let prev_root = builder.by_id(prev_root_id).unwrap();
+            builder.add_node("|".to_string(), prev_root, Some(rule_root.clone()), None).unwrap()
+          },
+          _ => rule_root.clone()
+        };
+        roots[index].1 = Some(root.id());
       }
-      v_context.push(exec_context);
     }
-    Ok(v_context.pop().unwrap())
+    Ok((exec_context, roots))
   }
 
   fn nullable(&self, item: &Rc<dyn ASTItem>) -> bool {
@@ -369,125 +514,217 @@ impl DFABuilder {
     last_items.get_mut(&item_id).unwrap()
   }
 
-  fn follow<'a>(&'a self, item: &Rc<dyn ASTItem>, items: &BTreeMap<usize, Rc<dyn ASTItem>>,
-    first_items: &'a mut HashMap<usize, HashSet<usize>>,
-    last_items: &'a mut HashMap<usize, HashSet<usize>>,
-    follow_items: &'a mut HashMap<usize, HashSet<usize>>) -> &HashSet<usize> {
-    let item_id = item.id();
-
-    if !follow_items.contains_key(&item_id) {
-      let mut result: HashSet<usize> = HashSet::new();
-
-      for (_, item) in items.iter() {
-        match item.name() {
-          "." => {
-            let left = item.left().unwrap();
-            if self.last(&left, items, last_items).contains(&item_id) {
-              let right = item.right().unwrap();
-              for value in self.first(&right, items, first_items).iter() {
-                result.insert(*value);
-              }
-            }
-          },
-          "*" => {
-            if self.last(&item, items, last_items).contains(&item_id) {
-              let left = item.left().unwrap();
-              for value in self.first(&left, items, first_items).iter() {
-                result.insert(*value);
-              }
-            }
-          },
-          _ => {}
-        }
+  /// Returns the follow positions of every leaf, they are collected in one pass over the nodes,
+  /// because the large character classes have many leaves.
+  fn follow(&self, items: &BTreeMap<usize, Rc<dyn ASTItem>>,
+    first_items: &mut HashMap<usize, HashSet<usize>>,
+    last_items: &mut HashMap<usize, HashSet<usize>>) -> HashMap<usize, HashSet<usize>> {
+    let mut follow_items: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (_, item) in items.iter() {
+      let (last, first) = match item.name() {
+        "." => (item.left().unwrap(), item.right().unwrap()),
+        "*" => (item.clone(), item.left().unwrap()),
+        _ => continue
+      };
+      let first = self.first(&first, items, first_items).clone();
+      for id in self.last(&last, items, last_items).iter() {
+        follow_items.entry(*id).or_default().extend(first.iter());
       }
-
-      follow_items.insert(item_id, result);
     }
-
-    follow_items.get(&item_id).unwrap()
+    follow_items
   }
 
+  /// Returns the codes of the leaves, the leaf of the character class holds several codes.
   fn get_codes(items: &BTreeMap<usize, Rc<dyn ASTItem>>) -> Vec<u8> {
     let mut result: Vec<u8> = Vec::new();
     let mut added: HashSet<u8> = HashSet::new();
     for (_, item) in items {
-      if item.name() == "code" && item.value().len() > 0 {
-        let code = item.value()[0];
-        if added.contains(&code) {continue}
-        added.insert(code);
-        result.push(code);
+      if item.name() != "code" {continue}
+      for code in item.value() {
+        if added.contains(code) {continue}
+        added.insert(*code);
+        result.push(*code);
       }
     }
    result
   }
 }
 
-pub fn build(re_def: String) -> (Vec<Option<State>>, GotoStatesOpt) {
-  let exec_context = DFABuilder::build_ast(re_def);
-  let mut exec_context = exec_context.unwrap();
+///
+/// DFAStats
+///
+/// Number of states of the DFA before and after minimization.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct DFAStats {
+  states_before: usize,
+  states_after: usize
+}
+
+impl DFAStats {
+  pub fn states_before(&self) -> usize {
+    self.states_before
+  }
+
+  pub fn states_after(&self) -> usize {
+    self.states_after
+  }
+}
+
+/// Merges equivalent states by Moore's algorithm. The initial partition separates
+/// non-accepting states and accepting states by the accept token names and the actions,
+/// the start states of the modes other than the initial one are kept separate.
+/// Returns the number of states after minimization and the new state of every state,
+/// the start state stays 0.
+fn minimize(count: usize, starts: usize, states: &mut HashMap<usize, State>, goto_states: &mut GotoStates) -> (usize, Vec<usize>) {
+  let mut blocks: Vec<usize> = Vec::with_capacity(count);
+  let mut keys = HashMap::new();
+  for index in 0..count {
+    let start = if index > 0 && index < starts {Some(index)} else {None};
+    let key = (start, states.get(&index).map(|state| (state.accept, state.action.clone(), state.alternatives.clone())));
+    let len = keys.len();
+    blocks.push(*keys.entry(key).or_insert(len));
+  }
+  let mut blocks_len = keys.len();
+
+  loop {
+    let mut signatures: HashMap<(usize, Vec<(usize, usize)>), usize> = HashMap::new();
+    let mut new_blocks: Vec<usize> = Vec::with_capacity(count);
+    for index in 0..count {
+      let mut transitions: Vec<(usize, usize)> = match goto_states.states.get(&index) {
+        Some(states1) => states1.states.iter().map(|(code, goto)| (*code, blocks[*goto])).collect(),
+        _ => vec!()
+      };
+      transitions.sort_unstable();
+      let len = signatures.len();
+      new_blocks.push(*signatures.entry((blocks[index], transitions)).or_insert(len));
+    }
+    let new_len = signatures.len();
+    blocks = new_blocks;
+    if new_len == blocks_len {break}
+    blocks_len = new_len;
+  }
+
+  let mut min_states: HashMap<usize, State> = HashMap::new();
+  let mut min_goto_states = GotoStates::new();
+  for index in 0..count {
+    let block = blocks[index];
+    if let Some(state) = states.get(&index) {
+      min_states.entry(block).or_insert_with(|| state.clone());
+    }
+    if min_goto_states.state_exists(block) {continue}
+    if let Some(states1) = goto_states.states.get(&index) {
+      for (code, goto) in &states1.states {
+        min_goto_states.set_state(block, *code, blocks[*goto]);
+      }
+    }
+  }
+  *states = min_states;
+  *goto_states = min_goto_states;
+  (blocks_len, blocks)
+}
+
+pub fn build(re_def: String) -> Result<(Vec<Option<State>>, GotoStatesOpt), Error> {
+  let (states, goto_states, _) = build_with_stats(re_def)?;
+  Ok((states, goto_states))
+}
+
+/// Builds the minimized DFA and returns the number of states before and after minimization.
+pub fn build_with_stats(re_def: String) -> Result<(Vec<Option<State>>, GotoStatesOpt, DFAStats), Error> {
+  let (mut exec_context, roots) = DFABuilder::build_ast(re_def)?;
   let ast_builder = exec_context.builder();
   let items = ast_builder.items();
   let dfa_builder = DFABuilder::new();
   let mut first_items: HashMap<usize, HashSet<usize>> = HashMap::new();
   let mut last_items: HashMap<usize, HashSet<usize>> = HashMap::new();
-  let mut follow_items: HashMap<usize, HashSet<usize>> = HashMap::new();
 
+  // the start states of the modes are the first states
   let mut s: Vec<HashSet<usize>> = vec!();
-  s.insert(0, dfa_builder.first(&ast_builder.last().unwrap(), &items, &mut first_items).clone());
+  for (_, root) in &roots {
+    let first = match root.and_then(|root| ast_builder.by_id(root)) {
+      Some(root) => dfa_builder.first(&root, &items, &mut first_items).clone(),
+      _ => HashSet::new()
+    };
+    s.push(first);
+  }
+  // the start states of the modes are entered only at the beginning of a token
+  let key = |s_item: &HashSet<usize>| {
+    let mut key: Vec<usize> = s_item.iter().cloned().collect();
+    key.sort_unstable();
+    key
+  };
+  let mut indexes: HashMap<Vec<usize>, usize> = HashMap::new();
+  indexes.insert(key(&s[0]), 0);
+  let follow_items = dfa_builder.follow(&items, &mut first_items, &mut last_items);
   let codes = DFABuilder::get_codes(&items);
-  let mut marked: BTreeMap<usize, bool> = BTreeMap::new();
-  marked.insert(0, false);
   let mut states: HashMap<usize, State> = HashMap::new();
   let mut goto_states = GotoStates::new();
-  loop {
-    let index = marked.values().position(|&value| value == false);
-    if index == None {break}
-
-    let index = index.unwrap();
-    marked.insert(index, true);
+  let mut index = 0;
+  while index < s.len() {
+    // the codes of the character classes mostly have the same positions, so the goto state
+    // is found once for them
+    let mut code_positions: HashMap<u8, Vec<usize>> = HashMap::new();
+    for p in key(&s[index]) {
+      let item = items.get(&p).unwrap();
+      if item.name() != "code" {continue}
+      for code in item.value() {
+        code_positions.entry(*code).or_default().push(p);
+      }
+    }
+    let mut goto_indexes: HashMap<Vec<usize>, Option<usize>> = HashMap::new();
     for code in &codes {
-      let mut u: HashSet<usize> = HashSet::new();
-      for p in s[index].iter() {
-        let item = items.get(p).unwrap();
-        if item.name() == "code" && item.value().len() > 0 && item.value()[0] == *code {
-          for val in dfa_builder.follow(item, &items, &mut first_items, &mut last_items, &mut follow_items).iter() {
-            u.insert(*val);
+      let positions = match code_positions.remove(code) {
+        Some(positions) => positions,
+        _ => continue
+      };
+      let u_index = match goto_indexes.get(&positions) {
+        Some(u_index) => *u_index,
+        _ => {
+          let u: HashSet<usize> = positions.iter().flat_map(|p| follow_items.get(p).into_iter().flatten()).cloned().collect();
+          let u_index = match u.is_empty() {
+            true => None,
+            _ => {
+              let u_key = key(&u);
+              Some(match indexes.get(&u_key) {
+                Some(u_index) => *u_index,
+                _ => {
+                  s.push(u);
+                  indexes.insert(u_key, s.len() - 1);
+                  s.len() - 1
+                }
+              })
+            }
           };
+          goto_indexes.insert(positions, u_index);
+          u_index
         }
+      };
+      if let Some(u_index) = u_index {
+        goto_states.set_state(index, *code as usize, u_index);
       }
-    if u.len() == 0 {continue}
-    let mut u_index = s.iter().position(|s_item| {
-      u.len() == s_item.len() && u.iter().all(|&val| s_item.contains(&(val)))
-    });
-    if u_index == None {
-        s.push(u);
-        let u_index1 = s.len() - 1;
-        marked.insert(u_index1, false);
-        u_index = Some(u_index1);
-      }
-      goto_states.set_state(index, *code as usize, u_index.unwrap());
     }
+    index += 1;
   }
 
   for index in 0..s.len() {
-    let s_item = &s[index];
-    s_item.iter().any(|p| {
-      let item = items.get(p).unwrap();
-      if item.name() == "#" {
-        let mut state = State::new(item.attr(&hash("accept")).unwrap().as_usize().unwrap(), None);
-        state.action = match item.attr(&hash("action")) {
-          Some(ref attr) => match attr.as_function() {
-            Some(func) => {
-              Some(Rc::new(func.clone()))
-            },
-            _ => None
-          },
+    // the rule defined first is accepted, as in `Lex`, the rules defined later are the
+    // alternatives for the parser which does not expect the first one
+    let mut s_item: Vec<&usize> = s[index].iter().collect();
+    s_item.sort_unstable();
+    let mut accepts = s_item.into_iter().map(|p| items.get(p).unwrap()).filter(|item| item.name() == "#")
+      .map(|item| {
+        let accept = item.attr(&hash("accept")).unwrap().as_usize().unwrap();
+        let action = match item.attr(&hash("action")) {
+          Some(ref attr) => attr.as_string().map(|action| Rc::new(action.clone())),
           None => None
         };
-        states.insert(index, state);
-        true
-      } else {false}
-    });
+        (accept, action)
+      });
+    if let Some((accept, action)) = accepts.next() {
+      let mut state = State::new(accept, action);
+      state.alternatives = accepts.collect();
+      states.insert(index, state);
+    }
   }
 
   // remove dead-end transitions
@@ -515,6 +752,9 @@ pub fn build(re_def: String) -> (Vec<Option<State>>, GotoStatesOpt) {
     }
   }
 
+  let states_before = s.len();
+  let (states_after, blocks) = minimize(states_before, roots.len(), &mut states, &mut goto_states);
+
   //convert states map to vec
   let mut len = *(states.keys().max().unwrap_or(&0));
   if len > 0 {len += 1}
@@ -534,7 +774,9 @@ pub fn build(re_def: String) -> (Vec<Option<State>>, GotoStatesOpt) {
   }
 
   //convert goto states map to vec
-  let goto_states_opt = GotoStatesOpt::from(&goto_states);
+  let mut goto_states_opt = GotoStatesOpt::from(&goto_states);
+  goto_states_opt.modes = roots.iter().enumerate().skip(1)
+    .map(|(index, (mode, _))| (*mode, blocks[index])).collect();
 
-  (states_opt, goto_states_opt)
+  Ok((states_opt, goto_states_opt, DFAStats {states_before, states_after}))
 }
\ No newline at end of file
@@ -1,10 +1,9 @@
 use std::char;
 use std::rc::Rc;
-use std::fmt::Debug;
-use js_sys::Array;
-use wasm_bindgen::prelude::{JsValue, wasm_bindgen};
+use regex_syntax::hir::{Class, HirKind};
 
 use super::utils::*;
+use super::action::*;
 
 pub fn reg_exp() -> &'static str {
   "
@@ -21,82 +20,188 @@ pub fn reg_exp() -> &'static str {
    ']' \\]
    '{' \\{
    '}' \\}
-   char \\S {let tkn = this.to_codes(get(), 'utf8'); set_name(tkn[0]); set(tkn[1])}
-   escape \\\\{char} {let tkn = this.to_codes(get().slice(1), 'utf8'); set_name(tkn[0]); set(tkn[1])}
-   escape_hex \\\\x[0-9A-Fa-f][0-9A-Fa-f] {let tkn = this.to_codes(get().slice(2), 'hex'); set_name(tkn[0]); set(tkn[1])}
-   escape_unicode \\\\u[0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f] {let tkn = this.to_codes(get().slice(2), 'unicode'); set_name(tkn[0]); set(tkn[1])}
+   '.' \\.
+   icase \\(\\?i:
+   char \\S {char}
+   class \\\\[dDsSwW]
+   posix_class \\[:\\^?[a-z]+:\\]
+   escape \\\\{char} {escape}
+   escape_hex \\\\x[0-9A-Fa-f][0-9A-Fa-f] {escape_hex}
+   escape_unicode \\\\u[0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f] {escape_unicode}
+   escape_unicode8 \\\\U[0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f][0-9A-Fa-f] {escape_unicode}
   "
 }
 
 pub fn grammar() -> &'static str {
   "
   start: pattern;
-  pattern: disjunction {bind(id(0))};
-
-  disjunction: alternative '|' disjunction {bind(this.add_node('|', id(2), id(0)))};
-  disjunction: alternative {bind(id(0))};
-
-  alternative: alternative term {bind(this.add_node('.', id(1), id(0)))};
-  alternative: {bind(this.add_leaf('code', ''))};
-
-  term: atom '{' digits ',' digits '}' {bind(this.build_tree_to_duplicates2(id(5), get(3), get(1)))};
-  term: atom '{' digits ',' '}' {bind(this.build_tree_to_duplicates2(id(4), get(2), [48] /* 0 */))};
-  term: atom '{' digits '}' {bind(this.build_tree_to_duplicates1(id(3), get(1)))};
-  term: atom '?' {bind(this.build_tree_to_duplicates3(id(1), 0, 1))};
-  term: atom '+' {bind(this.build_tree_to_duplicates3(id(1), 1, 0))};
-  term: atom '*' {bind(this.build_tree_to_duplicates3(id(1), 0, 0))};
-  term: atom {bind(id(0))};
-  digits: 'codes' {bind(this.build_tree_to_codes(get(0)))};
-  digits: 'code' {set(0); bind(this.add_leaf('code', get(0)))};
-
-  atom: character_class {bind(id(0))};
-  atom: '(' pattern ')' {bind(id(1))};
-  atom: 'codes' {bind(this.build_tree_to_codes(get(0)))};
-  atom: 'code' {bind(this.add_leaf('code', get(0)))};
-
-  character_class: '[' '^' class_ranges_nonmatch ']' {bind(id(1))};
-  character_class: '[' class_ranges_match ']' {bind(id(1))};
-
-  class_ranges_nonmatch: nonempty_class_ranges_nonmatch {bind(id(0))};
-  class_ranges_nonmatch: {bind(this.add_leaf('code', ''))};
-  nonempty_class_ranges_nonmatch: class_atom_nonmatch '-' class_atom_nonmatch class_ranges_nonmatch {let node_type = get(0)[0] == 0 ? '.' : '|'; bind(this.add_node(node_type, this.build_tree_to_range(id(3), get(3), id(1), get(1), true), id(0)))};
-  nonempty_class_ranges_nonmatch: class_atom_nonmatch {bind(id(0))};
-  class_atom_nonmatch: 'code' {set(0); bind(this.add_leaf('code_not', get(0)))};
-
-  class_ranges_match: nonempty_class_ranges_match {set_val([1]); bind(id(0))};
-  class_ranges_match: {set_val([0]); bind(this.add_leaf('code', ''))};
-  nonempty_class_ranges_match: class_atom_match {bind(id(0))};
-  nonempty_class_ranges_match: class_atom_match '-' class_atom_match class_ranges_match {let node_type = get(0)[0] == 0 ? '.' : '|'; bind(this.add_node(node_type, this.build_tree_to_range(id(3), get(3), id(1), get(1), false), id(0)))};
-  class_atom_match: 'code' {set(0); bind(this.add_leaf('code', get(0)))};
+  pattern: disjunction {bind_0};
+
+  disjunction: alternative '|' disjunction {alternation};
+  disjunction: alternative {bind_0};
+
+  alternative: alternative term {concatenation};
+  alternative: {empty};
+
+  term: atom '{' digits ',' digits '}' {repeat_min_max};
+  term: atom '{' digits ',' '}' {repeat_min};
+  term: atom '{' digits '}' {repeat};
+  term: atom '?' {optional};
+  term: atom '+' {one_or_more};
+  term: atom '*' {zero_or_more};
+  term: atom {bind_0};
+  digits: 'codes' {codes};
+  digits: 'code' {digit};
+
+  atom: character_class {bind_0};
+  atom: '(' pattern ')' {bind_1};
+  atom: icase_group pattern ')' {icase_end};
+  icase_group: 'icase' {icase_start};
+  atom: '.' {any};
+  atom: 'class' {class};
+  atom: 'codes' {codes};
+  atom: 'code' {code};
+
+  character_class: '[' '^' class_items ']' {class_nonmatch};
+  character_class: '[' class_items ']' {class_match};
+
+  class_items: class_items class_item {class_union};
+  class_items: class_item {class_items};
+  class_item: 'code' '-' 'code' {class_range};
+  class_item: 'code' '-' 'codes' {class_range};
+  class_item: 'codes' '-' 'code' {class_range};
+  class_item: 'codes' '-' 'codes' {class_range};
+  class_item: 'code' {class_code};
+  class_item: 'codes' {class_code};
+  class_item: '.' {class_code};
+  class_item: '+' {class_code};
+  class_item: '*' {class_code};
+  class_item: '?' {class_code};
+  class_item: '|' {class_code};
+  class_item: '(' {class_code};
+  class_item: ')' {class_code};
+  class_item: '{' {class_code};
+  class_item: '}' {class_code};
+  class_item: ',' {class_code};
+  class_item: 'class' {class_escape};
+  class_item: 'posix_class' {class_posix};
   "
 }
 
-#[wasm_bindgen(module = "/src/dfa_grammar.js")]
-extern "C" {
-  pub fn cast_exec_context_to_js_value(id: usize, ec: ExecContext) -> JsValue;
-  pub fn return_exec_context(id: usize) -> ExecContext;
+#[derive(Clone)]
+pub struct ExecContext {
+  builder: ASTBuilder,
+  /// Depth of the case-insensitive groups `(?i:...)`.
+  icase: usize
 }
 
-#[derive(Debug)]
-struct TokenJS (String, Vec<u8>);
+impl ParserExecContext for ExecContext {
+  fn builder(&mut self) -> &mut ASTBuilder {
+    &mut self.builder
+  }
+}
 
-impl TokenJS {
-  ///Gets value as String
-  pub fn value_to_string(&self) -> String {
-    String::from_utf8(self.1.clone()).unwrap_or_default()
+impl LexActions for ExecContext {
+  fn lex_action(&mut self, _tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    let (name, value) = match action {
+      Some("char") => self.to_codes(ctx.get().to_vec(), "utf8")?,
+      // the hex and unicode escapes with the invalid digits are recognized as the simple escape
+      Some("escape") if [b'x', b'u', b'U'].contains(&ctx.get()[1]) =>
+        return Err(format!("Bad escape sequence {:?}", String::from_utf8_lossy(ctx.get()))),
+      Some("escape") => self.to_codes(ctx.get()[1..].to_vec(), "utf8")?,
+      Some("escape_hex") => self.to_codes(ctx.get()[2..].to_vec(), "hex")?,
+      Some("escape_unicode") => self.to_codes(ctx.get()[2..].to_vec(), "unicode")?,
+      _ => return Ok(())
+    };
+    ctx.set_name(name);
+    ctx.set(value);
+    Ok(())
   }
 }
 
+impl ParserActions for ExecContext {
+  fn parser_action(&mut self, _production: &GrammarProduction, action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
 
-#[wasm_bindgen]
-#[derive(Clone)]
-pub struct ExecContext {
-  builder: ASTBuilder
-}
+    fn id(ctx: &dyn ParserActionContext, index: usize) -> Result<usize, String> {
+      match ctx.id(index) {
+        Some(id) => Ok(id),
+        _ => Err(format!("Id of symbol {} is not bound", index))
+      }
+    }
 
-impl ParserExecContext for ExecContext {
-  fn builder(&mut self) -> &mut ASTBuilder {
-    &mut self.builder
+    fn get(ctx: &dyn ParserActionContext, index: usize) -> Vec<u8> {
+      match ctx.get(index) {
+        Some(value) => value.to_vec(),
+        _ => vec!()
+      }
+    }
+
+    let id = match action {
+      Some("bind_0") => id(ctx, 0)?,
+      Some("bind_1") => id(ctx, 1)?,
+      Some("alternation") => self.add_node("|".to_string(), id(ctx, 2)?, Some(id(ctx, 0)?))?,
+      Some("concatenation") => self.add_node(".".to_string(), id(ctx, 1)?, Some(id(ctx, 0)?))?,
+      Some("empty") => self.add_leaf("code".to_string(), vec!())?,
+      Some("repeat_min_max") => self.build_tree_to_duplicates2(id(ctx, 5)?, get(ctx, 3), get(ctx, 1))?,
+      Some("repeat_min") => self.build_tree_to_duplicates2(id(ctx, 4)?, get(ctx, 2), vec!(b'0'))?,
+      Some("repeat") => self.build_tree_to_duplicates1(id(ctx, 3)?, get(ctx, 1))?,
+      Some("optional") => self.build_tree_to_duplicates3(id(ctx, 1)?, 0, Some(1))?,
+      Some("one_or_more") => self.build_tree_to_duplicates3(id(ctx, 1)?, 1, Some(0))?,
+      Some("zero_or_more") => self.build_tree_to_duplicates3(id(ctx, 1)?, 0, Some(0))?,
+      Some("codes") => self.build_tree_to_chars(get(ctx, 0))?,
+      Some("code") => {
+        let codes = match self.icase {
+          0 => get(ctx, 0),
+          _ => class_codes(&class_fold(&class_range(&get(ctx, 0), &get(ctx, 0))?))
+        };
+        self.add_leaf("code".to_string(), codes)?
+      },
+      Some("icase_start") => {
+        self.icase += 1;
+        return Ok(())
+      },
+      Some("icase_end") => {
+        self.icase -= 1;
+        id(ctx, 1)?
+      },
+      Some("digit") => {
+        ctx.set(0);
+        self.add_leaf("code".to_string(), get(ctx, 0))?
+      },
+      Some("any") => self.build_tree_to_class(class_any())?,
+      Some("class") => self.build_tree_to_class(class_escape(&get(ctx, 0))?)?,
+      Some("class_match") => self.build_tree_to_class(get(ctx, 1))?,
+      Some("class_nonmatch") => self.build_tree_to_class(class_not(&get(ctx, 1)))?,
+      Some("class_items") => {
+        ctx.set(0);
+        return Ok(())
+      },
+      Some("class_union") => {
+        ctx.set_val(class_union(&get(ctx, 1), &get(ctx, 0)));
+        return Ok(())
+      },
+      Some("class_range") => {
+        ctx.set_val(self.fold(class_range(&get(ctx, 2), &get(ctx, 0))?));
+        return Ok(())
+      },
+      Some("class_code") => {
+        ctx.set_val(self.fold(class_range(&get(ctx, 0), &get(ctx, 0))?));
+        return Ok(())
+      },
+      Some("class_escape") => {
+        ctx.set_val(class_escape(&get(ctx, 0))?);
+        return Ok(())
+      },
+      Some("class_posix") => {
+        ctx.set_val(class_posix(&get(ctx, 0))?);
+        return Ok(())
+      },
+      _ => return Ok(())
+    };
+    ctx.bind(id);
+    Ok(())
   }
 }
 
@@ -108,82 +213,51 @@ impl ExecContext {
     }
   }
 
-}
-
-#[wasm_bindgen]
-impl ExecContext {
   pub fn new() -> Self {
     ExecContext {
-      builder: ASTBuilder::new()
+      builder: ASTBuilder::new(),
+      icase: 0
     }
   }
 
-  pub fn to_codes(&self, tkn_value: Vec<u8>, encoding: &str) -> Result<JsValue, JsValue> {
-    fn to_js_token(token: &TokenJS) -> Result<JsValue, JsValue> {
-      let arr = Array::new();
-      let name = JsValue::from(&token.0);
-      let value = Array::new();
-      for v in token.1.iter() {
-        value.push(&JsValue::from(*v));
-      }
-      arr.push(&name);
-      arr.push(&value);
-      Ok(JsValue::from(arr))
-    }
-
-    fn convert(token: TokenJS, encoding: &str, name: Option<String>) -> Result<JsValue, JsValue> {
-      let name = match name {
-        Some(name) => name,
-        _ => "codes".to_string()
-      };
-      let token = match encoding {
-        "utf8" => {
-          TokenJS(name, token.1.to_vec())
-        },
-        _ => {
-          panic!("Unknown encoding: '{}'", encoding);
-        }
-      };
-
-      to_js_token(&token)
-    }
-
-    let mut token = TokenJS("".to_string(), tkn_value);
+  /// Converts value of token to the name of token ('code' or 'codes') and codes.
+  pub fn to_codes(&self, tkn_value: Vec<u8>, encoding: &str) -> Result<(&'static str, Vec<u8>), String> {
     match encoding {
       "utf8" => {
-        if token.1.len() == 1 && token.1[0] < 128u8 {
-          token.0 = "code".to_string();
-          to_js_token(&token)
+        if tkn_value.len() == 1 && tkn_value[0] < 128u8 {
+          Ok(("code", tkn_value))
         } else {
-          convert(token, encoding, None)
+          Ok(("codes", tkn_value))
         }
       },
       "unicode" => {
-        if token.1.len() != 4 {return Err(JsValue::from("The length of 'unicode' value must be equal 4 bytes"))}
-        let str_val = token.value_to_string();
-        let code = u32::from_str_radix(&str_val, 16).expect(format!("Bad hex format number: {:?}", &str_val).as_str());
-        let ch = char::from_u32(code).expect(format!("Bad number for unicode: {}", code).as_str());
-        to_js_token(&TokenJS("codes".to_string(), ch.to_string().as_bytes().to_vec()))
+        if tkn_value.len() != 4 && tkn_value.len() != 8 {
+          return Err(String::from("The length of 'unicode' value must be equal 4 or 8 bytes"))
+        }
+        let str_val = String::from_utf8(tkn_value).unwrap_or_default();
+        let code = u32::from_str_radix(&str_val, 16).map_err(|_| format!("Bad hex format number: {:?}", &str_val))?;
+        let ch = char::from_u32(code).ok_or_else(|| format!("Bad number for unicode: {}", code))?;
+        self.to_codes(ch.to_string().into_bytes(), "utf8")
       },
       "hex" => {
-        if token.1.len() != 2 {return Err(JsValue::from("The length of 'hex' value must be equal 2 bytes"))}
-        let str_val = token.value_to_string();
-        let data = u8::from_str_radix(&str_val, 16).expect(format!("Bad hex format number: {:?}", &str_val).as_str());
-        to_js_token(&TokenJS("code".to_string(), data.to_be_bytes().to_vec()))
+        if tkn_value.len() != 2 {return Err(String::from("The length of 'hex' value must be equal 2 bytes"))}
+        let str_val = String::from_utf8(tkn_value).unwrap_or_default();
+        let data = u8::from_str_radix(&str_val, 16).map_err(|_| format!("Bad hex format number: {:?}", &str_val))?;
+        Ok(("code", data.to_be_bytes().to_vec()))
       },
-      _ => {Err(JsValue::from(format!("Error encoding format {:?}", encoding)))}
+      _ => {Err(format!("Error encoding format {:?}", encoding))}
     }
   }
 
-  fn to_digit(&self, value: Vec<u8>) -> Result<usize, JsValue> {
+  fn to_digit(&self, value: Vec<u8>) -> Result<usize, String> {
     let result = String::from_utf8(value);
-    if result.is_err() {return Err(JsValue::from(format!("Error convert to digit: {:?}", result).as_str()));}
+    if result.is_err() {return Err(format!("Error convert to digit: {:?}", result));}
     let result = result.unwrap().parse();
-    if result.is_err() {return Err(JsValue::from(format!("Error convert to digit: {:?}", result).as_str()));}
+    if result.is_err() {return Err(format!("Error convert to digit: {:?}", result));}
     Ok(result.unwrap())
   }
 
-  fn clone(&mut self, item: Rc<dyn ASTItem>) -> Result<Rc<dyn ASTItem>, JsValue> {
+  fn clone(&mut self, item: Rc<dyn ASTItem>) -> Result<Rc<dyn ASTItem>, String> {
     if item.type_item() == ASTItemType::Node {
       let left = self.clone(item.left().unwrap())?;
       let right = match item.right() {
@@ -199,13 +273,13 @@ impl ExecContext {
     }
   }
 
-  fn err(&self) -> Result<Rc<dyn ASTItem>, JsValue> {
-    Err(JsValue::from("'atom' not found in tree."))
+  fn err(&self) -> Result<Rc<dyn ASTItem>, String> {
+    Err(String::from("'atom' not found in tree."))
   }
 
-  fn v1(&mut self, atom_id: usize, min: usize) -> Result<Rc<dyn ASTItem>, JsValue> {
+  fn v1(&mut self, atom_id: usize, min: usize) -> Result<Rc<dyn ASTItem>, String> {
     let count = min;
-    if count == 0 {return Err(JsValue::from("NULL"));}
+    if count == 0 {return Err(String::from("NULL"));}
     let atom_node = self.builder.by_id(atom_id);
     if atom_node.is_none() {return self.err()}
     let atom_node = atom_node.unwrap();
@@ -217,7 +291,7 @@ impl ExecContext {
     Ok(node)
   }
 
-  fn v2(&mut self, atom_id: usize, min: usize) -> Result<Rc<dyn ASTItem>, JsValue> {
+  fn v2(&mut self, atom_id: usize, min: usize) -> Result<Rc<dyn ASTItem>, String> {
     let atom_node = self.builder.by_id(atom_id);
     if atom_node.is_none() {return self.err()}
     let atom_node = atom_node.unwrap();
@@ -230,9 +304,9 @@ impl ExecContext {
     Ok(node)
   }
 
-  fn v3(&mut self, atom_id: usize, min: usize, max: usize) -> Result<Rc<dyn ASTItem>, JsValue> {
+  fn v3(&mut self, atom_id: usize, min: usize, max: usize) -> Result<Rc<dyn ASTItem>, String> {
     if min > max {
-      return Err(JsValue::from(format!("min number:{} less than max number:{}", min, max)));
+      return Err(format!("min number:{} less than max number:{}", min, max));
     }
     let atom_node = self.builder.by_id(atom_id);
     if atom_node.is_none() {return self.err()}
@@ -253,17 +327,17 @@ impl ExecContext {
   }
 
   pub fn build_tree_to_duplicates1(&mut self, atom_id: usize,
-    min_codes: Vec<u8>) -> Result<usize, JsValue> {
+    min_codes: Vec<u8>) -> Result<usize, String> {
     self.build_tree_to_duplicates3(atom_id, self.to_digit(min_codes)?, None)
   }
 
   pub fn build_tree_to_duplicates2(&mut self, atom_id: usize,
-    min_codes: Vec<u8>, max_codes: Vec<u8>) -> Result<usize, JsValue> {
+    min_codes: Vec<u8>, max_codes: Vec<u8>) -> Result<usize, String> {
     self.build_tree_to_duplicates3(atom_id, self.to_digit(min_codes)?, Some(self.to_digit(max_codes)?))
   }
 
   pub fn build_tree_to_duplicates3(&mut self, atom_id: usize,
-    min: usize, max: Option<usize>) -> Result<usize, JsValue> {
+    min: usize, max: Option<usize>) -> Result<usize, String> {
     let result = match max {
       None => {
         if min > 0 {
@@ -278,8 +352,8 @@ impl ExecContext {
     Ok(result.id())
   }
 
-  pub fn build_tree_to_codes(&mut self, codes: Vec<u8>) -> Result<usize, JsValue> {
-    if codes.len() < 2 {return Err(JsValue::from("The length of 'codes' must not be less than 2"))}
+  pub fn build_tree_to_codes(&mut self, codes: Vec<u8>) -> Result<usize, String> {
+    if codes.len() < 2 {return Err(String::from("The length of 'codes' must not be less than 2"))}
     let mut index = 0;
     let leaf = self.builder.add_leaf("code".to_string(), vec!(codes[index]), None)?;
     index += 1;
@@ -295,60 +369,382 @@ impl ExecContext {
     Ok(self.builder.last().unwrap().id())
   }
 
-  pub fn build_tree_to_range(&mut self, start_id: usize, start_value: Vec<u8>, end_id: usize, end_value: Vec<u8>, not_items: Option<bool>) -> Result<usize, JsValue> {
-    if start_value.len() > 1 {
-      return Err(JsValue::from(format!("start value {:?} takes more than one byte", start_value)));
-    }
-    if end_value.len() > 1 {
-      return Err(JsValue::from(format!("end value {:?} takes more than one byte", end_value)));
+  /// Adds the other cases of the letters to the class inside of the case-insensitive group.
+  fn fold(&self, class: Vec<u8>) -> Vec<u8> {
+    match self.icase {
+      0 => class,
+      _ => class_fold(&class)
     }
-    let start_code = start_value[0];
-    let end_code = end_value[0];
-    if start_code > end_code {
-      return Err(JsValue::from(format!("The starting value: {:?} must not be greater than the final value: {:?}.", start_code, end_code)));
-    }
-    let mut code = start_code;
-    let name = match not_items.unwrap_or(false) {
-      true => "code_not".to_string(),
-      false => "code".to_string()
+  }
+
+  /// Builds the concatenation of the codes of the character, inside of the case-insensitive
+  /// group the alternation of the cases of the character.
+  pub fn build_tree_to_chars(&mut self, codes: Vec<u8>) -> Result<usize, String> {
+    let code = match code_point(&codes) {
+      Ok(code) if self.icase > 0 => code,
+      _ => return self.build_tree_to_codes(codes)
     };
-    let right_id = match end_code > start_code {
-      true => {
-        let leaf = self.builder.add_leaf(name.clone(), vec!(code), None)?;
-        code += 1;
-        Some(leaf.id())
-      },
-      false => None
+    let mut node = None;
+    for code in case_variants(code) {
+      let codes = utf8_encode(code);
+      let id = match codes.len() {
+        1 => self.add_leaf("code".to_string(), codes)?,
+        _ => self.build_tree_to_codes(codes)?
+      };
+      node = Some(match node {
+        Some(left) => self.add_node("|".to_string(), left, Some(id))?,
+        _ => id
+      });
+    }
+    Ok(node.unwrap())
+  }
+
+  /// Builds the leaf with the codes of the character class, the ranges of the multibyte
+  /// UTF-8 characters are added as the alternation of the sequences of byte ranges.
+  pub fn build_tree_to_class(&mut self, class: Vec<u8>) -> Result<usize, String> {
+    let codes = class_codes(&class);
+    let mut node = match codes.len() {
+      0 => None,
+      _ => Some(self.builder.add_leaf("code".to_string(), codes, None)?)
     };
-    match self.builder.last() {
-      Some(ref _last) => {
-        let _ = self.builder.add_node_id("|".to_string(), start_id, right_id, None)?;
-      },
-      _ => {}
+    let mut trie = Utf8Trie::default();
+    for (start, end) in class_ranges(&class) {
+      for sequence in utf8_sequences(start, end) {
+        trie.insert(&sequence);
+      }
     }
-    loop {
-      if code > end_code {break}
-      let right_id = match code < end_code {
-        true => self.builder.add_leaf_id(name.clone(), vec!(code), None)?,
-        false => end_id
-      };
-      let left_id = match self.builder.last() {
-        Some(ref last) => last.id(),
-        _ => start_id
-      };
-      let _ = self.builder.add_node_id("|".to_string(), left_id, Some(right_id), None)?;
-      if code == 255 {break}
-      code += 1;
+    trie.merge();
+    if let Some(trie_node) = self.build_tree_to_trie(&trie)? {
+      node = Some(match node {
+        Some(left) => self.builder.add_node("|".to_string(), left, Some(trie_node), None)?,
+        _ => trie_node
+      });
     }
-    Ok(self.builder.last().unwrap().id())
+    match node {
+      Some(node) => Ok(node.id()),
+      _ => Err(String::from("The character class is empty"))
+    }
+  }
+
+  fn build_tree_to_trie(&mut self, trie: &Utf8Trie) -> Result<Option<Rc<dyn ASTItem>>, String> {
+    let mut node: Option<Rc<dyn ASTItem>> = None;
+    for (codes, next) in trie.0.iter() {
+      let mut seq_node = self.builder.add_leaf("code".to_string(), codes.clone(), None)?;
+      if let Some(next) = self.build_tree_to_trie(next)? {
+        seq_node = self.builder.add_node(".".to_string(), seq_node, Some(next), None)?;
+      }
+      node = Some(match node {
+        Some(left) => self.builder.add_node("|".to_string(), left, Some(seq_node), None)?,
+        _ => seq_node
+      });
+    }
+    Ok(node)
   }
 
-  pub fn add_leaf(&mut self, name: String, value: Vec<u8>) -> Result<usize, JsValue> {
+  pub fn add_leaf(&mut self, name: String, value: Vec<u8>) -> Result<usize, String> {
     self.builder.add_leaf_id(name, value, None)
   }
 
-  pub fn add_node(&mut self, name: String, left_id: usize, right_id: Option<usize>) -> Result<usize, JsValue> {
+  pub fn add_node(&mut self, name: String, left_id: usize, right_id: Option<usize>) -> Result<usize, String> {
     self.builder.add_node_id(name, left_id, right_id, None)
   }
 
 }
+
+/// The value of the character class is the bitmap of 256 codes and the ranges of
+/// the multibyte UTF-8 characters, every range is two code points of 4 bytes.
+const CLASS_CODES_LEN: usize = 32;
+
+/// The maximum code point and the maximum code points encoded by 1, 2 and 3 bytes.
+const MAX_CODE_POINT: u32 = 0x10FFFF;
+const MAX_CODE_POINTS: [u32; 3] = [0x7F, 0x7FF, 0xFFFF];
+
+fn class_contains(class: &[u8], code: u8) -> bool {
+  class[(code >> 3) as usize] & (1 << (code & 7)) != 0
+}
+
+fn class_codes(class: &[u8]) -> Vec<u8> {
+  (0..=255u8).filter(|code| class_contains(class, *code)).collect()
+}
+
+fn class_ranges(class: &[u8]) -> Vec<(u32, u32)> {
+  class[CLASS_CODES_LEN..].chunks(8).map(|range| {
+    (u32::from_be_bytes([range[0], range[1], range[2], range[3]]),
+      u32::from_be_bytes([range[4], range[5], range[6], range[7]]))
+  }).collect()
+}
+
+/// Makes the value of the class, the ranges are sorted and merged.
+fn class_new(codes: &[u8], mut ranges: Vec<(u32, u32)>) -> Vec<u8> {
+  let mut class = codes[..CLASS_CODES_LEN].to_vec();
+  ranges.sort_unstable();
+  let mut merged: Vec<(u32, u32)> = vec!();
+  for (start, end) in ranges {
+    match merged.last_mut() {
+      Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+      _ => merged.push((start, end))
+    }
+  }
+  for (start, end) in merged {
+    class.extend_from_slice(&start.to_be_bytes());
+    class.extend_from_slice(&end.to_be_bytes());
+  }
+  class
+}
+
+/// Makes the class of the code points, the ASCII codes are placed to the bitmap.
+fn class_from_ranges(ranges: &[(u32, u32)]) -> Vec<u8> {
+  let mut codes = vec![0; CLASS_CODES_LEN];
+  let mut char_ranges = vec!();
+  for (start, end) in ranges {
+    for code in *start..=(*end).min(0x7F) {
+      codes[(code >> 3) as usize] |= 1 << (code & 7);
+    }
+    if *end > 0x7F {
+      char_ranges.push(((*start).max(0x80), *end));
+    }
+  }
+  class_new(&codes, char_ranges)
+}
+
+/// Makes the class of the bytes, the bytes above 0x7F are not the UTF-8 characters.
+fn class_from_bytes(start: u8, end: u8) -> Vec<u8> {
+  let mut codes = vec![0; CLASS_CODES_LEN];
+  for code in start..=end {
+    codes[(code >> 3) as usize] |= 1 << (code & 7);
+  }
+  class_new(&codes, vec!())
+}
+
+fn class_union(left: &[u8], right: &[u8]) -> Vec<u8> {
+  let codes: Vec<u8> = left.iter().zip(right.iter()).take(CLASS_CODES_LEN).map(|(left, right)| left | right).collect();
+  let mut ranges = class_ranges(left);
+  ranges.append(&mut class_ranges(right));
+  class_new(&codes, ranges)
+}
+
+/// Complement of the class: the other ASCII codes and the other UTF-8 characters.
+fn class_not(class: &[u8]) -> Vec<u8> {
+  let mut codes: Vec<u8> = class[..CLASS_CODES_LEN].iter().map(|code| !code).collect();
+  for code in codes[CLASS_CODES_LEN / 2..].iter_mut() {
+    *code = 0;
+  }
+  let mut ranges = vec!();
+  let mut start = 0x80;
+  for (range_start, range_end) in class_ranges(class) {
+    if range_start > start {
+      ranges.push((start, range_start - 1));
+    }
+    start = range_end + 1;
+  }
+  if start <= MAX_CODE_POINT {
+    ranges.push((start, MAX_CODE_POINT));
+  }
+  class_new(&codes, ranges)
+}
+
+/// The character and its lower and upper cases consisting of one character.
+fn case_variants(code: u32) -> Vec<u32> {
+  let mut variants = vec!(code);
+  if let Some(ch) = char::from_u32(code) {
+    for mut case in [ch.to_lowercase().collect::<Vec<char>>(), ch.to_uppercase().collect()] {
+      if case.len() == 1 && !variants.contains(&(case[0] as u32)) {
+        variants.push(case.pop().unwrap() as u32);
+      }
+    }
+  }
+  variants
+}
+
+/// Adds the other cases of the letters, the bytes above 0x7F are kept as is.
+fn class_fold(class: &[u8]) -> Vec<u8> {
+  let mut ranges: Vec<(u32, u32)> = vec!();
+  for code in class_codes(class) {
+    if code.is_ascii_alphabetic() {
+      ranges.push((code.to_ascii_lowercase() as u32, code.to_ascii_lowercase() as u32));
+      ranges.push((code.to_ascii_uppercase() as u32, code.to_ascii_uppercase() as u32));
+    }
+  }
+  for (start, end) in class_ranges(class) {
+    for code in start..=end {
+      for variant in case_variants(code) {
+        if variant != code {ranges.push((variant, variant))}
+      }
+    }
+  }
+  class_union(class, &class_from_ranges(&ranges))
+}
+
+/// Any character except the line feed.
+fn class_any() -> Vec<u8> {
+  class_not(&class_from_bytes(b'\n', b'\n'))
+}
+
+/// Range of the bytes or, if any of the values is the multibyte character, of the code points.
+fn class_range(start: &[u8], end: &[u8]) -> Result<Vec<u8>, String> {
+  if start.len() == 1 && end.len() == 1 {
+    if start[0] > end[0] {
+      return Err(format!("The starting value: {:?} must not be greater than the final value: {:?}.", start[0], end[0]));
+    }
+    return Ok(class_from_bytes(start[0], end[0]));
+  }
+  let start = code_point(start)?;
+  let end = code_point(end)?;
+  if start > end {
+    return Err(format!("The starting value: {:?} must not be greater than the final value: {:?}.", start, end));
+  }
+  Ok(class_from_ranges(&[(start, end)]))
+}
+
+fn code_point(value: &[u8]) -> Result<u32, String> {
+  let mut chars = std::str::from_utf8(value).unwrap_or_default().chars();
+  match (chars.next(), chars.next()) {
+    (Some(ch), None) => Ok(ch as u32),
+    _ => Err(format!("value {:?} is not a UTF-8 character", value))
+  }
+}
+
+fn utf8_encode(code: u32) -> Vec<u8> {
+  let mut data = [0; 4];
+  char::from_u32(code).map(|ch| ch.encode_utf8(&mut data).as_bytes().to_vec()).unwrap_or_default()
+}
+
+///
+/// Alternation of the sets of bytes followed by the rest of the UTF-8 sequences
+///
+/// The sequences with the same prefix share it and the sets with the same rest are merged, so
+/// the large Unicode classes are built of a few leaves.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Utf8Trie(Vec<(Vec<u8>, Utf8Trie)>);
+
+impl Utf8Trie {
+  fn insert(&mut self, sequence: &[(u8, u8)]) {
+    if let Some(((start, end), rest)) = sequence.split_first() {
+      let codes: Vec<u8> = (*start..=*end).collect();
+      match self.0.iter_mut().find(|(codes1, _)| *codes1 == codes) {
+        Some((_, next)) => next.insert(rest),
+        _ => {
+          let mut next = Utf8Trie::default();
+          next.insert(rest);
+          self.0.push((codes, next));
+        }
+      }
+    }
+  }
+
+  fn merge(&mut self) {
+    let mut merged: Vec<(Vec<u8>, Utf8Trie)> = vec!();
+    for (codes, mut next) in self.0.drain(..) {
+      next.merge();
+      match merged.iter_mut().find(|(_, next1)| *next1 == next) {
+        Some((codes1, _)) => {
+          codes1.extend(codes);
+          codes1.sort_unstable();
+        },
+        _ => merged.push((codes, next))
+      }
+    }
+    self.0 = merged;
+  }
+}
+
+/// Splits the range of the code points to the sequences of byte ranges of UTF-8,
+/// the surrogates are skipped.
+fn utf8_sequences(start: u32, end: u32) -> Vec<Vec<(u8, u8)>> {
+  let mut sequences = vec!();
+  let mut ranges = vec!((start, end));
+  'ranges: while let Some((start, mut end)) = ranges.pop() {
+    loop {
+      if start < 0xE000 && end > 0xD7FF {
+        if start < 0xD800 {ranges.push((start, 0xD7FF))}
+        if end > 0xDFFF {ranges.push((0xE000, end))}
+        continue 'ranges;
+      }
+      if let Some(max) = MAX_CODE_POINTS.iter().find(|max| start <= **max && **max < end) {
+        ranges.push((max + 1, end));
+        end = *max;
+        continue;
+      }
+      if end <= 0x7F {
+        sequences.push(vec!((start as u8, end as u8)));
+        continue 'ranges;
+      }
+      let mut split = false;
+      for i in 1..4 {
+        let mask = (1u32 << (6 * i)) - 1;
+        if start & !mask == end & !mask {continue}
+        if start & mask != 0 {
+          ranges.push(((start | mask) + 1, end));
+          end = start | mask;
+          split = true;
+          break;
+        }
+        if end & mask != mask {
+          ranges.push((end & !mask, end));
+          end = (end & !mask) - 1;
+          split = true;
+          break;
+        }
+      }
+      if split {continue}
+      let start = utf8_encode(start);
+      let end = utf8_encode(end);
+      sequences.push(start.into_iter().zip(end).collect());
+      continue 'ranges;
+    }
+  }
+  sequences
+}
+
+/// Classes `\d \D \s \S \w \W` of Unicode, the code points are taken from the `regex` crate
+/// tables, so the classes are the same in `Lex`.
+fn class_escape(value: &[u8]) -> Result<Vec<u8>, String> {
+  let code = match value.last() {
+    Some(code) if b"dDsSwW".contains(code) => *code,
+    _ => return Err(format!("Unknown class {:?}", String::from_utf8_lossy(value)))
+  };
+  let pattern = format!("\\{}", code.to_ascii_lowercase() as char);
+  let ranges: Vec<(u32, u32)> = match regex_syntax::parse(&pattern).map_err(|err| err.to_string())?.kind() {
+    HirKind::Class(Class::Unicode(class)) =>
+      class.ranges().iter().map(|range| (range.start() as u32, range.end() as u32)).collect(),
+    _ => return Err(format!("Unknown class {:?}", pattern))
+  };
+  let class = class_from_ranges(&ranges);
+  match code.is_ascii_uppercase() {
+    true => Ok(class_not(&class)),
+    _ => Ok(class)
+  }
+}
+
+/// POSIX class `[:name:]` or `[:^name:]`.
+fn class_posix(value: &[u8]) -> Result<Vec<u8>, String> {
+  let name = String::from_utf8_lossy(value);
+  let name = name.trim_start_matches("[:").trim_end_matches(":]");
+  match name.strip_prefix('^') {
+    Some(name) => Ok(class_not(&class_posix_name(name)?)),
+    _ => class_posix_name(name)
+  }
+}
+
+fn class_posix_name(name: &str) -> Result<Vec<u8>, String> {
+  let ranges: &[(u8, u8)] = match name {
+    "alnum" => &[(b'0', b'9'), (b'A', b'Z'), (b'a', b'z')],
+    "alpha" => &[(b'A', b'Z'), (b'a', b'z')],
+    "ascii" => &[(0x00, 0x7F)],
+    "blank" => &[(b'\t', b'\t'), (b' ', b' ')],
+    "cntrl" => &[(0x00, 0x1F), (0x7F, 0x7F)],
+    "digit" => &[(b'0', b'9')],
+    "graph" => &[(b'!', b'~')],
+    "lower" => &[(b'a', b'z')],
+    "print" => &[(b' ', b'~')],
+    "punct" => &[(b'!', b'/'), (b':', b'@'), (b'[', b'`'), (b'{', b'~')],
+    "space" => &[(b'\t', b'\r'), (b' ', b' ')],
+    "upper" => &[(b'A', b'Z')],
+    "word" => &[(b'0', b'9'), (b'A', b'Z'), (b'a', b'z'), (b'_', b'_')],
+    "xdigit" => &[(b'0', b'9'), (b'A', b'F'), (b'a', b'f')],
+    _ => return Err(format!("Unknown POSIX class {:?}", name))
+  };
+  let ranges: Vec<(u32, u32)> = ranges.iter().map(|(start, end)| (*start as u32, *end as u32)).collect();
+  Ok(class_from_ranges(&ranges))
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+#[cfg(feature = "wasm")]
+use js_sys::{Array, Object, Reflect, Uint8Array};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::JsValue;
+
+use super::utils::*;
+use super::lalr::GrammarConflict;
+
+///
+/// Error
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+  /// Syntax error in the text of grammar.
+  GrammarSyntax {line: usize, column: usize, message: String},
+  /// Syntax error in the regular expression of the lexer rule at the line, `column` is the
+  /// position of the rule name or expression in the line.
+  RegExpSyntax {line: usize, column: usize, message: String},
+  /// Conflicts of grammar.
+  Conflicts(Vec<GrammarConflict>),
+  /// No lexer rule matches the input starting at the byte offset.
+  LexNoMatch {offset: usize},
+  /// Symbol is not expected in the parser state, `offset` is the byte offset of the symbol
+  /// in the input and `expected` are the terminals which have an action in the state.
+  UnexpectedSymbol {state: usize, symbol: usize, value: Option<Vec<u8>>, offset: usize, expected: Vec<usize>},
+  /// Failure of the lexer or grammar action.
+  Action {message: String},
+  /// Data of the compiled tables are invalid.
+  InvalidBinary {message: String},
+  /// Framing of the input is invalid at the byte offset.
+  InvalidFrame {offset: usize, message: String},
+  /// Grammar or tables use the feature which the GLR parser or the generated module does not support.
+  Unsupported {message: String}
+}
+
+/// Returns the original name of symbol if known or the hash of name otherwise.
+pub fn symbol_name(name: usize) -> String {
+  match get_original_name(name) {
+    Some(original_name) => format!("'{}'", original_name),
+    _ => format!("#{}", name)
+  }
+}
+
+impl Error {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Error::GrammarSyntax {..} => "GrammarSyntax",
+      Error::RegExpSyntax {..} => "RegExpSyntax",
+      Error::Conflicts(_) => "Conflicts",
+      Error::LexNoMatch {..} => "LexNoMatch",
+      Error::UnexpectedSymbol {..} => "UnexpectedSymbol",
+      Error::Action {..} => "Action",
+      Error::InvalidBinary {..} => "InvalidBinary",
+      Error::InvalidFrame {..} => "InvalidFrame",
+      Error::Unsupported {..} => "Unsupported"
+    }
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::GrammarSyntax {line, column, message} =>
+        write!(f, "{} at line {}, column {}", message, line, column),
+      Error::RegExpSyntax {line, column, message} =>
+        write!(f, "Error in regular expression at line {}, column {}: {}", line, column, message),
+      Error::Conflicts(conflicts) => {
+        write!(f, "Grammar has {} conflict(s)", conflicts.len())?;
+        for conflict in conflicts {
+          write!(f, "\n{}", conflict)?;
+        }
+        Ok(())
+      },
+      Error::LexNoMatch {offset} =>
+        write!(f, "No lexer rule matches the input at byte {}", offset),
+      Error::UnexpectedSymbol {state, symbol, value, offset, expected} => {
+        let mut expected: Vec<String> = expected.iter().map(|name| symbol_name(*name)).collect();
+        let last = expected.pop();
+        let expected = match last {
+          Some(last) if !expected.is_empty() => format!("{} or {}", expected.join(", "), last),
+          Some(last) => last,
+          _ => "nothing".to_string()
+        };
+        write!(f, "Expected {} at byte {}, got {}", expected, offset, symbol_name(*symbol))?;
+        if let Some(value) = value {
+          write!(f, " {:?}", String::from_utf8_lossy(value))?;
+        }
+        write!(f, " (parser state {})", state)
+      },
+      Error::Action {message} =>
+        write!(f, "Error in action: {}", message),
+      Error::InvalidBinary {message} =>
+        write!(f, "Invalid compiled tables: {}", message),
+      Error::InvalidFrame {offset, message} =>
+        write!(f, "Invalid frame at byte {}: {}", offset, message),
+      Error::Unsupported {message} =>
+        write!(f, "Not supported: {}", message)
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "wasm")]
+impl From<Error> for JsValue {
+  fn from(err: Error) -> Self {
+    let result: JsValue = js_sys::Error::new(&err.to_string()).into();
+    let set = |key: &str, value: JsValue| {
+      let _ = Reflect::set(&result, &JsValue::from(key), &value);
+    };
+    let names = |names: &[usize]| -> JsValue {
+      names.iter().map(|name| JsValue::from(*name as u32)).collect::<Array>().into()
+    };
+
+    set("kind", JsValue::from(err.kind()));
+    match err {
+      Error::GrammarSyntax {line, column, message} => {
+        set("line", JsValue::from(line as u32));
+        set("column", JsValue::from(column as u32));
+        set("reason", JsValue::from(message));
+      },
+      Error::RegExpSyntax {line, column, message} => {
+        set("line", JsValue::from(line as u32));
+        set("column", JsValue::from(column as u32));
+        set("reason", JsValue::from(message));
+      },
+      Error::Conflicts(conflicts) => {
+        let items = Array::new();
+        for conflict in conflicts {
+          let item = Object::new();
+          let _ = Reflect::set(&item, &JsValue::from("kind"), &JsValue::from(format!("{:?}", conflict.kind())));
+          let _ = Reflect::set(&item, &JsValue::from("state"), &JsValue::from(conflict.state() as u32));
+          let _ = Reflect::set(&item, &JsValue::from("symbol"), &JsValue::from(conflict.symbol() as u32));
+          let _ = Reflect::set(&item, &JsValue::from("message"), &JsValue::from(conflict.to_string()));
+          items.push(&item);
+        }
+        set("conflicts", items.into());
+      },
+      Error::LexNoMatch {offset} => {
+        set("offset", JsValue::from(offset as u32));
+      },
+      Error::UnexpectedSymbol {state, symbol, value, offset, expected} => {
+        set("state", JsValue::from(state as u32));
+        set("symbol", JsValue::from(symbol as u32));
+        set("symbolName", JsValue::from(get_original_name(symbol)));
+        set("value", match value {
+          Some(value) => Uint8Array::from(value.as_slice()).into(),
+          _ => JsValue::NULL
+        });
+        set("offset", JsValue::from(offset as u32));
+        set("expected", names(&expected));
+        set("expectedNames", expected.iter()
+          .map(|name| JsValue::from(get_original_name(*name))).collect::<Array>().into());
+      },
+      Error::Action {message} | Error::InvalidBinary {message} | Error::Unsupported {message} => {
+        set("reason", JsValue::from(message));
+      },
+      Error::InvalidFrame {offset, message} => {
+        set("offset", JsValue::from(offset as u32));
+        set("reason", JsValue::from(message));
+      }
+    }
+    result
+  }
+}
@@ -1,23 +1,260 @@
 use std::rc::Rc;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use js_sys::{Object, Reflect, Function, Uint8Array};
 use wasm_bindgen::prelude::{JsValue, wasm_bindgen};
+use wasm_bindgen::JsCast;
 
-use super::lalr::{GrammarBuilder, StatesBuilder, LALRBuilder, LRBuilder};
+use super::utils::{GrammarProduction, hash};
+use super::error::Error;
+use super::action::*;
 use super::parser::{Parser, ParseResult, ParserType};
-use super::dfa::build;
+use super::lex::IntFormat;
 use super::stream_lex::StreamLex;
+use super::binary::Tables;
+use super::framing::{Framing, Frame, FrameDecoder, FrameQueue};
+
+type Functions = Rc<RefCell<HashMap<String, Function>>>;
+
+///
+/// Executes the text of actions as javascript functions with `context` as `this`.
+///
+struct JsActions<'a> {
+  context: &'a JsValue,
+  on_tkn_data: Option<&'a Function>,
+  on_parse_error: Option<&'a Function>,
+  lex_functions: &'a Functions,
+  parser_functions: &'a Functions
+}
+
+impl<'a> JsActions<'a> {
+  fn function(functions: &Functions, args: &str, action: &str) -> Function {
+    let mut functions = functions.borrow_mut();
+    if let Some(func) = functions.get(action) {
+      return func.clone();
+    }
+    let func = Function::new_with_args(args, action);
+    functions.insert(action.to_string(), func.clone());
+    func
+  }
+
+  fn error(err: JsValue) -> String {
+    match err.as_string() {
+      Some(err) => err,
+      _ => format!("{:?}", err)
+    }
+  }
+}
+
+impl<'a> LexActions for JsActions<'a> {
+  fn lex_action(&mut self, _tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    let action = match action {
+      Some(action) => action,
+      _ => return Ok(())
+    };
+    let func = Self::function(self.lex_functions, "get, set, set_name, set_name_from_hash, pass, read_int, read_blob, begin, push_mode, pop_mode", action);
+    let ctx = RefCell::new(ctx);
+
+    let get = || -> Vec<u8> {
+      ctx.borrow().get().to_vec()
+    };
+
+    let mut set = |value: Vec<u8>| {
+      ctx.borrow_mut().set(value);
+    };
+
+    let mut set_name = |name: String| {
+      ctx.borrow_mut().set_name(&name);
+    };
+
+    let mut set_name_from_hash = |hash_name: usize| {
+      ctx.borrow_mut().set_name_from_hash(hash_name);
+    };
+
+    let mut pass = || {
+      ctx.borrow_mut().pass();
+    };
+
+    let error = RefCell::new(None);
+    let read = |name: String, format: String, blob: bool| {
+      match IntFormat::from_name(&format) {
+        Some(format) if blob => ctx.borrow_mut().read_blob(&name, format),
+        Some(format) => ctx.borrow_mut().read_int(&name, format),
+        _ => *error.borrow_mut() = Some(format!("Invalid format of integer '{}'", format))
+      }
+    };
+
+    let mut read_int = |name: String, format: String| {
+      read(name, format, false);
+    };
+
+    let mut read_blob = |name: String, format: String| {
+      read(name, format, true);
+    };
+
+    let mut begin = |mode: String| {
+      ctx.borrow_mut().begin(&mode);
+    };
+
+    let mut push_mode = |mode: String| {
+      ctx.borrow_mut().push_mode(&mode);
+    };
+
+    let mut pop_mode = || {
+      ctx.borrow_mut().pop_mode();
+    };
+
+    do_lex_action(&func, self.context, &get, &mut set, &mut set_name, &mut set_name_from_hash, &mut pass,
+      &mut read_int, &mut read_blob, &mut begin, &mut push_mode, &mut pop_mode)
+      .map_err(Self::error)?;
+    match error.into_inner() {
+      Some(message) => Err(message),
+      _ => Ok(())
+    }
+  }
+
+  fn tkn_data(&mut self, tkn_name: usize, data: &[u8], end: bool) -> Result<(), String> {
+    if let Some(on_tkn_data) = self.on_tkn_data {
+      let tkn_name = JsValue::from(tkn_name as u32);
+      let data = unsafe {Uint8Array::view(data)};
+      let end = JsValue::from(end);
+      let _ = on_tkn_data.call3(self.context, &tkn_name, &data, &end).map_err(Self::error)?;
+    }
+    Ok(())
+  }
+}
+
+impl<'a> ParserActions for JsActions<'a> {
+  fn parser_action(&mut self, production: &GrammarProduction, action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    let action = match action {
+      Some(action) => action,
+      _ => return Ok(())
+    };
+    // labels of the production are declared as constants with indices of the symbols
+    let labels = production.attr(&hash("labels")).and_then(|attr| attr.as_labels());
+    let action = match labels {
+      Some(labels) => {
+        let labels: Vec<String> = labels.labels().iter().map(|(name, index)| format!("{} = {}", name, index)).collect();
+        Cow::Owned(format!("const {}; {}", labels.join(", "), action))
+      },
+      _ => Cow::Borrowed(action)
+    };
+    let func = Self::function(self.parser_functions,
+      "bind, id, lookup, get, set, set_val, set_name, set_name_from_hash, push_after, begin, push_mode, pop_mode",
+      &action);
+    let ctx = RefCell::new(ctx);
+
+    let mut bind = |id: usize| {
+      ctx.borrow_mut().bind(id);
+    };
+
+    let id = |index: usize| -> Option<usize> {
+      ctx.borrow().id(index)
+    };
+
+    let lookup = || -> Option<Vec<u8>> {
+      ctx.borrow().lookup().map(|value| value.to_vec())
+    };
+
+    let get = |index: usize| -> Option<Vec<u8>> {
+      ctx.borrow().get(index).map(|value| value.to_vec())
+    };
+
+    let mut set = |index: usize| {
+      ctx.borrow_mut().set(index);
+    };
+
+    let mut set_val = |value: Vec<u8>| {
+      ctx.borrow_mut().set_val(value);
+    };
+
+    let mut set_name = |name: String| {
+      ctx.borrow_mut().set_name(&name);
+    };
+
+    let mut set_name_from_hash = |hash_name: usize| {
+      ctx.borrow_mut().set_name_from_hash(hash_name);
+    };
+
+    let mut push_after = |name: String, insert_name: Option<String>,
+      insert_value: Option<Vec<u8>>, size: Option<usize>, stop_codes: Option<Vec<u8>>| {
+      ctx.borrow_mut().push_after(&name, insert_name.as_deref(), insert_value, size, stop_codes);
+    };
+
+    let mut begin = |mode: String| {
+      ctx.borrow_mut().begin(&mode);
+    };
+
+    let mut push_mode = |mode: String| {
+      ctx.borrow_mut().push_mode(&mode);
+    };
+
+    let mut pop_mode = || {
+      ctx.borrow_mut().pop_mode();
+    };
+
+    do_parser_action(&func, self.context, &mut bind, &id, &lookup, &get, &mut set, &mut set_val,
+      &mut set_name, &mut set_name_from_hash, &mut push_after, &mut begin, &mut push_mode, &mut pop_mode)
+      .map_err(Self::error)
+  }
+
+  fn parse_error(&mut self, err: &Error) -> Result<(), String> {
+    if let Some(on_parse_error) = self.on_parse_error {
+      let _ = on_parse_error.call1(self.context, &JsValue::from(err.clone())).map_err(Self::error)?;
+    }
+    Ok(())
+  }
+}
 
 #[wasm_bindgen]
 pub struct Executor {
   parser: Parser,
-  exec_context: Option<Object>
+  tables: Tables,
+  exec_context: Option<Object>,
+  lex_functions: Functions,
+  parser_functions: Functions,
+  decoder: Option<Box<dyn FrameDecoder>>,
+  /// Frames left after parsing is interrupted by `on_after_parse`.
+  frames: FrameQueue
 }
 
 impl Clone for Executor {
   fn clone(&self) -> Self {
     Executor {
       parser: self.parser.clone(),
-      exec_context: None
+      tables: self.tables.clone(),
+      exec_context: None,
+      lex_functions: self.lex_functions.clone(),
+      parser_functions: self.parser_functions.clone(),
+      decoder: self.decoder.as_ref().map(|decoder| {
+        let mut decoder = decoder.box_clone();
+        decoder.reset();
+        decoder
+      }),
+      frames: FrameQueue::new()
+    }
+  }
+}
+
+impl Executor {
+  fn from_tables(tables: Tables) -> Executor {
+    let mut lex = StreamLex::new();
+    lex.set_states(Some(tables.lex_states().clone()), Some(tables.lex_goto_states().clone()));
+
+    let mut parser = Parser::new(Box::new(lex));
+    parser.set_states(tables.grammar().clone(), tables.goto_states().clone(), tables.action_states().clone());
+
+    Executor {
+      parser,
+      tables,
+      exec_context: None,
+      lex_functions: Rc::new(RefCell::new(HashMap::new())),
+      parser_functions: Rc::new(RefCell::new(HashMap::new())),
+      decoder: None,
+      frames: FrameQueue::new()
     }
   }
 }
@@ -28,85 +265,145 @@ impl Executor {
     self.clone()
   }
 
-  pub fn build(reg_exp: String, grammar: String, parser_type: ParserType) -> Self {
-    let (lex_states, lex_goto_states) = build(reg_exp);
-    let lex_states = Some(Rc::new(lex_states));
-    let lex_goto_states = Some(Rc::new(lex_goto_states));
-    let on_tkn_data = None;
-    let mut lex = StreamLex::new(on_tkn_data);
-    lex.set_states(lex_states, lex_goto_states);
-
-    let grammar = GrammarBuilder::from_text(grammar).unwrap();
-    let goto_states;
-    let action_states;
-    match parser_type {
-      ParserType::LALR1 => {
-        goto_states = LALRBuilder::build_goto_states(&grammar);
-        action_states = LALRBuilder::build_action_states(&grammar, &goto_states).unwrap();
-      },
-      ParserType::LR1 => {
-        goto_states = LRBuilder::build_goto_states(&grammar);
-        action_states = LRBuilder::build_action_states(&grammar, &goto_states).unwrap();
-      }
-    }
-    let parser_grammar = Rc::new(grammar);
-    let parser_goto_states = Rc::new(goto_states);
-    let parser_action_states = Rc::new(action_states);
+  pub fn build(reg_exp: String, grammar: String, parser_type: ParserType) -> Result<Executor, JsValue> {
+    let tables = Tables::build(reg_exp, grammar, parser_type)?;
+    Ok(Executor::from_tables(tables))
+  }
 
-    let mut parser = Parser::new(Box::new(lex));
-    parser.set_states(parser_grammar, parser_goto_states, parser_action_states);
+  /// Serializes compiled tables of the lexer and the parser.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    self.tables.to_bytes()
+  }
 
-    Executor {
-      parser,
-      exec_context: None
-    }
+  /// Creates executor from tables serialized by `to_bytes`.
+  pub fn from_bytes(data: &[u8]) -> Result<Executor, JsValue> {
+    let tables = Tables::from_bytes(data)?;
+    Ok(Executor::from_tables(tables))
+  }
+
+  /// Number of states of the lexer DFA before minimization, undefined for executor created by `from_bytes`.
+  pub fn lex_states_before(&self) -> Option<usize> {
+    self.tables.lex_stats().map(|stats| stats.states_before())
+  }
+
+  /// Number of states of the lexer DFA after minimization.
+  pub fn lex_states_after(&self) -> usize {
+    self.tables.lex_goto_states().len().max(self.tables.lex_states().len())
+  }
+
+  /// Sets the decoder of frames, the payload of every frame is parsed as the message.
+  pub fn set_framing(&mut self, framing: Framing) {
+    self.decoder = Some(framing.decoder());
+  }
+
+  /// Sets whether the lexer prefers the tokens which the parser accepts in the current state.
+  pub fn set_context_lexing(&mut self, value: bool) {
+    self.parser.set_context_lexing(value);
   }
 
   pub fn parse_init(&mut self) {
     self.parser.init();
+    if let Some(ref mut decoder) = self.decoder {
+      decoder.reset();
+    }
+    self.frames.clear();
   }
 
   pub fn has_data(&self) -> bool {
-    self.parser.has_data()
+    self.parser.has_data() || self.frames.has_data()
   }
 
+  /// Returns the data left in the parser and the data of the frames left after parsing is interrupted.
   pub fn data(&self) -> Vec<u8> {
-    self.parser.data()
+    let mut data = self.parser.data();
+    data.extend(self.frames.data());
+    data
   }
 
   pub fn parse_data(&mut self, data: &Uint8Array, proto: &Object,
     socket_key: &JsValue, socket: &JsValue,
     on_before_parse: Option<Function>, on_after_parse: Option<Function>,
     on_tkn_data: Option<Function>) -> Result<(), JsValue> {
-    self.parser.set_on_tkn_data(on_tkn_data);
-    self.parser.set_data(data.to_vec());
-    loop {
-      let exec_context = match self.exec_context.take() {
-        Some(exec_context) => exec_context,
-        None => {
-          let exec_context = Object::create(proto);
-          let _ = Reflect::set(&exec_context, socket_key, socket);
-          if let Some(ref on_before_parse) = on_before_parse {
-            let _ = on_before_parse.call0(&exec_context);
-          };
-          exec_context
-        }
-      };
+    let on_parse_error = Reflect::get(proto, &JsValue::from("onParseError")).ok()
+      .and_then(|value| value.dyn_into::<Function>().ok());
+    // returns true if parsing is broken by `on_after_parse`
+    let parse = |executor: &mut Executor| -> Result<bool, JsValue> {
+      loop {
+        let exec_context = match executor.exec_context.take() {
+          Some(exec_context) => exec_context,
+          None => {
+            let exec_context = Object::create(proto);
+            let _ = Reflect::set(&exec_context, socket_key, socket);
+            if let Some(ref on_before_parse) = on_before_parse {
+              let _ = on_before_parse.call0(&exec_context);
+            };
+            exec_context
+          }
+        };
+
+        let mut actions = JsActions {
+          context: &exec_context,
+          on_tkn_data: on_tkn_data.as_ref(),
+          on_parse_error: on_parse_error.as_ref(),
+          lex_functions: &executor.lex_functions,
+          parser_functions: &executor.parser_functions
+        };
+        let result = executor.parser.parse(&mut actions)?;
+        if result == ParseResult::ParseWait {
+          executor.exec_context = Some(exec_context);
+          break;
+        };
 
-      let result = self.parser.parse(&exec_context)?;
-      if result == ParseResult::ParseWait {
-        self.exec_context = Some(exec_context);
-        break;
-      };
+        if let Some(ref on_after_parse) = on_after_parse {
+          let break_parse = on_after_parse.call0(&exec_context)?;
+          if break_parse == JsValue::TRUE {return Ok(true)};
+        }
 
-      if let Some(ref on_after_parse) = on_after_parse {
-        let break_parse = on_after_parse.call0(&exec_context)?;
-        if break_parse == JsValue::TRUE {break};
+        if !executor.parser.has_data() {break}
       }
+      Ok(false)
+    };
 
-      if !self.parser.has_data() {break}
-    }
-    Ok(())
+    let data = data.to_vec();
+    let frames = match self.decoder {
+      Some(ref mut decoder) => decoder.decode(&data)?,
+      _ => vec!(Frame::new(data, false))
+    };
+    // the frames left after the previous interrupted call are parsed first
+    let mut queue = std::mem::take(&mut self.frames);
+    queue.push(frames);
+    let result = queue.parse(|frame| {
+      if frame.end() {
+        self.parser.set_end_of_data();
+      } else {
+        self.parser.set_data(frame.data().clone());
+      }
+      parse(self)
+    });
+    self.frames = queue;
+    result.map(|_| ())
   }
 
 }
+
+#[wasm_bindgen(module = "/src/lex.js")]
+extern "C" {
+  #[wasm_bindgen(catch)]
+  fn do_lex_action(action: &Function, context: &JsValue,
+    get: &dyn Fn() -> Vec<u8>, set: &mut dyn FnMut(Vec<u8>), set_name: &mut dyn FnMut(String), set_name_from_hash: &mut dyn FnMut(usize), pass: &mut dyn FnMut(),
+    read_int: &mut dyn FnMut(String, String), read_blob: &mut dyn FnMut(String, String),
+    begin: &mut dyn FnMut(String), push_mode: &mut dyn FnMut(String), pop_mode: &mut dyn FnMut()
+  ) -> Result<(), JsValue>;
+}
+
+#[wasm_bindgen(module = "/src/parser.js")]
+extern "C" {
+  #[wasm_bindgen(catch)]
+  fn do_parser_action(action: &Function, context: &JsValue, bind: &mut dyn FnMut(usize), id: &dyn Fn(usize) -> Option<usize>,
+    lookup: &dyn Fn() -> Option<Vec<u8>>, get: &dyn Fn(usize) -> Option<Vec<u8>>,
+    set: &mut dyn FnMut(usize), set_val: &mut dyn FnMut(Vec<u8>),
+    set_name: &mut dyn FnMut(String), set_name_from_hash: &mut dyn FnMut(usize),
+    push_after: &mut dyn FnMut(String, Option<String>, Option<Vec<u8>>, Option<usize>, Option<Vec<u8>>),
+    begin: &mut dyn FnMut(String), push_mode: &mut dyn FnMut(String), pop_mode: &mut dyn FnMut()
+  ) -> Result<(), JsValue>;
+}
@@ -0,0 +1,345 @@
+use std::collections::VecDeque;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::error::Error;
+use super::lex::IntFormat;
+
+/// Built-in decoders of frames.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Framing {
+  Chunked,
+  U16Length,
+  U32Length,
+  Newline
+}
+
+impl Framing {
+  pub fn decoder(&self) -> Box<dyn FrameDecoder> {
+    match self {
+      Framing::Chunked => Box::new(ChunkedDecoder::new()),
+      Framing::U16Length => Box::new(LengthDecoder::new(IntFormat::U16BE)),
+      Framing::U32Length => Box::new(LengthDecoder::new(IntFormat::U32BE)),
+      Framing::Newline => Box::new(NewlineDecoder::new())
+    }
+  }
+}
+
+///
+/// Frame
+///
+/// Part of the payload of the frame decoded from the input, `end` is set for the last part.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+  data: Vec<u8>,
+  end: bool
+}
+
+impl Frame {
+  pub fn new(data: Vec<u8>, end: bool) -> Self {
+    Self {
+      data,
+      end
+    }
+  }
+
+  pub fn data(&self) -> &Vec<u8> {
+    &self.data
+  }
+
+  pub fn end(&self) -> bool {
+    self.end
+  }
+}
+
+/// Appends the data to the last part of the frame or starts the new part.
+fn push_frame(frames: &mut Vec<Frame>, data: &[u8], end: bool) {
+  match frames.last_mut() {
+    Some(frame) if !frame.end => {
+      frame.data.extend_from_slice(data);
+      frame.end = end;
+    },
+    _ => if !data.is_empty() || end {
+      frames.push(Frame::new(data.to_vec(), end));
+    }
+  }
+}
+
+///
+/// Decoded frames which are not parsed yet
+///
+/// The end of the frame is queued as the separate part without data, so the parsing interrupted
+/// after the data of the frame is resumed from its end.
+///
+#[derive(Debug, Clone, Default)]
+pub struct FrameQueue {
+  frames: VecDeque<Frame>
+}
+
+impl FrameQueue {
+  pub fn new() -> Self {
+    Self {
+      frames: VecDeque::new()
+    }
+  }
+
+  pub fn push(&mut self, frames: Vec<Frame>) {
+    for frame in frames {
+      let end = frame.end;
+      self.frames.push_back(Frame::new(frame.data, false));
+      if end {
+        self.frames.push_back(Frame::new(vec!(), true));
+      }
+    }
+  }
+
+  /// Calls `parse` for every queued part until it returns true, the rest of parts are kept
+  /// for the next call. Returns whether parsing is interrupted.
+  pub fn parse<E, F: FnMut(&Frame) -> Result<bool, E>>(&mut self, mut parse: F) -> Result<bool, E> {
+    while let Some(frame) = self.frames.pop_front() {
+      if parse(&frame)? {
+        return Ok(true);
+      }
+    }
+    Ok(false)
+  }
+
+  /// Whether some of queued parts has data.
+  pub fn has_data(&self) -> bool {
+    self.frames.iter().any(|frame| !frame.data.is_empty())
+  }
+
+  /// Returns the data of the queued parts.
+  pub fn data(&self) -> Vec<u8> {
+    self.frames.iter().flat_map(|frame| frame.data.iter().copied()).collect()
+  }
+
+  pub fn clear(&mut self) {
+    self.frames.clear();
+  }
+}
+
+///
+/// Decoder of frames between the input and the lexer, the payload of every frame is parsed
+/// as the message.
+///
+pub trait FrameDecoder {
+  /// Decodes the next part of the input, returns the parts of payload of the frames.
+  fn decode(&mut self, data: &[u8]) -> Result<Vec<Frame>, Error>;
+
+  /// Resets the state of the decoder.
+  fn reset(&mut self);
+
+  fn box_clone(&self) -> Box<dyn FrameDecoder>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum ChunkedState {
+  #[default]
+  Size,
+  Data(usize),
+  DataEnd,
+  Trailer
+}
+
+///
+/// Decoder of HTTP chunked transfer coding, the body is the frame.
+///
+#[derive(Debug, Clone, Default)]
+pub struct ChunkedDecoder {
+  state: ChunkedState,
+  line: Vec<u8>,
+  offset: usize
+}
+
+impl ChunkedDecoder {
+  pub fn new() -> Self {
+    Self {
+      state: ChunkedState::Size,
+      line: vec!(),
+      offset: 0
+    }
+  }
+
+  fn error(&self, message: &str) -> Error {
+    Error::InvalidFrame {offset: self.offset, message: message.to_string()}
+  }
+
+  /// Parses the size of the chunk, the chunk extensions are skipped.
+  fn chunk_size(&self) -> Result<usize, Error> {
+    let line = String::from_utf8_lossy(&self.line);
+    let size = line.split(';').next().unwrap_or_default().trim();
+    usize::from_str_radix(size, 16).map_err(|_| self.error(&format!("Invalid size of chunk '{}'", size)))
+  }
+}
+
+impl FrameDecoder for ChunkedDecoder {
+  fn decode(&mut self, data: &[u8]) -> Result<Vec<Frame>, Error> {
+    let mut frames = vec!();
+    let mut position = 0;
+    while position < data.len() {
+      match self.state {
+        ChunkedState::Data(size) => {
+          let len = size.min(data.len() - position);
+          push_frame(&mut frames, &data[position..position + len], false);
+          position += len;
+          self.offset += len;
+          self.state = match size - len {
+            0 => ChunkedState::DataEnd,
+            size => ChunkedState::Data(size)
+          };
+        },
+        state => {
+          let code = data[position];
+          position += 1;
+          self.offset += 1;
+          if code != b'\n' {
+            self.line.push(code);
+            continue;
+          }
+          if self.line.last() == Some(&b'\r') {
+            self.line.pop();
+          }
+          self.state = match state {
+            ChunkedState::Size => match self.chunk_size()? {
+              0 => ChunkedState::Trailer,
+              size => ChunkedState::Data(size)
+            },
+            ChunkedState::DataEnd if !self.line.is_empty() => return Err(self.error("Expected CRLF after data of chunk")),
+            ChunkedState::DataEnd => ChunkedState::Size,
+            _ if !self.line.is_empty() => ChunkedState::Trailer,
+            _ => {
+              push_frame(&mut frames, &[], true);
+              ChunkedState::Size
+            }
+          };
+          self.line.clear();
+        }
+      }
+    }
+    Ok(frames)
+  }
+
+  fn reset(&mut self) {
+    self.state = ChunkedState::Size;
+    self.line.clear();
+    self.offset = 0;
+  }
+
+  fn box_clone(&self) -> Box<dyn FrameDecoder> {
+    Box::new(self.clone())
+  }
+}
+
+///
+/// Decoder of frames prefixed by the length of the payload.
+///
+#[derive(Debug, Clone)]
+pub struct LengthDecoder {
+  format: IntFormat,
+  length: Vec<u8>,
+  size: Option<u64>,
+  offset: usize
+}
+
+impl LengthDecoder {
+  pub fn new(format: IntFormat) -> Self {
+    Self {
+      format,
+      length: vec!(),
+      size: None,
+      offset: 0
+    }
+  }
+}
+
+impl FrameDecoder for LengthDecoder {
+  fn decode(&mut self, data: &[u8]) -> Result<Vec<Frame>, Error> {
+    let mut frames = vec!();
+    let mut position = 0;
+    while position < data.len() {
+      match self.size {
+        Some(size) => {
+          let len = (size as usize).min(data.len() - position);
+          let size = size - len as u64;
+          push_frame(&mut frames, &data[position..position + len], size == 0);
+          position += len;
+          self.offset += len;
+          self.size = if size == 0 {None} else {Some(size)};
+        },
+        None => {
+          self.length.push(data[position]);
+          position += 1;
+          self.offset += 1;
+          let size = self.format.decode(&self.length)
+            .map_err(|message| Error::InvalidFrame {offset: self.offset, message})?;
+          if let Some(size) = size {
+            self.length.clear();
+            match size {
+              0 => push_frame(&mut frames, &[], true),
+              size => self.size = Some(size)
+            }
+          }
+        }
+      }
+    }
+    Ok(frames)
+  }
+
+  fn reset(&mut self) {
+    self.length.clear();
+    self.size = None;
+    self.offset = 0;
+  }
+
+  fn box_clone(&self) -> Box<dyn FrameDecoder> {
+    Box::new(self.clone())
+  }
+}
+
+///
+/// Decoder of frames ended by the line feed, the carriage return before it is removed.
+///
+#[derive(Debug, Clone, Default)]
+pub struct NewlineDecoder {
+  /// Carriage return at the end of the previous data.
+  cr: bool
+}
+
+impl NewlineDecoder {
+  pub fn new() -> Self {
+    Self {
+      cr: false
+    }
+  }
+}
+
+impl FrameDecoder for NewlineDecoder {
+  fn decode(&mut self, data: &[u8]) -> Result<Vec<Frame>, Error> {
+    let mut frames = vec!();
+    let mut lines = data.split(|code| *code == b'\n').peekable();
+    while let Some(line) = lines.next() {
+      let end = lines.peek().is_some();
+      if self.cr && !(end && line.is_empty()) {
+        push_frame(&mut frames, b"\r", false);
+      }
+      let (line, cr) = match line.last() {
+        Some(b'\r') => (&line[..line.len() - 1], true),
+        _ => (line, false)
+      };
+      self.cr = cr && !end;
+      push_frame(&mut frames, line, end);
+    }
+    Ok(frames)
+  }
+
+  fn reset(&mut self) {
+    self.cr = false;
+  }
+
+  fn box_clone(&self) -> Box<dyn FrameDecoder> {
+    Box::new(self.clone())
+  }
+}
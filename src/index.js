@@ -1,7 +1,7 @@
 const assert = require('assert');
 const Net = require('net');
 const Tls = require('tls');
-const {Executor, hash, ParserType} = require('../pkg/server');
+const {Executor, hash, ParserType, Framing} = require('../pkg/server');
 
 const INIT = 0;
 const LISTENING_START = 1;
@@ -409,10 +409,69 @@ class Client extends Base {
  * @typedef {Object} BuilderOptions
  * @property {String} regexp - regular expression text
  * @property {String} grammar - grammar text
- * @property {ParserType} [parserType] - parser type (LALR1 or LR1, default LALR1)
+ * @property {ParserType} [parserType] - parser type (LALR1, LR1 or MinimalLR1, default LALR1)
+ * @property {Uint8Array} [compiled] - tables compiled by compile function, regexp, grammar and parserType are not used if set
+ * @property {Framing} [framing] - decoder of frames (Chunked, U16Length, U32Length or Newline), the payload of every frame is parsed as the message
+ * @property {Boolean} [contextLexing] - lexer prefers the tokens accepted by the parser in the current state (default false)
  * @property {Object} [proto] - prototype for message context
  */
 
+function buildExecutor(opt) {
+  const executor = createExecutor(opt);
+  switch (opt.framing) {
+    case Framing.Chunked:
+    case Framing.U16Length:
+    case Framing.U32Length:
+    case Framing.Newline:
+      executor.set_framing(opt.framing);
+      break;
+    case undefined:
+    case null:
+      break;
+    default:
+      executor.free();
+      throw new Error(`"framing" option ${opt.framing} is invalid`);
+  }
+  if (opt.contextLexing) executor.set_context_lexing(true);
+  return executor;
+}
+
+function createExecutor(opt) {
+  if (opt.compiled) return Executor.from_bytes(opt.compiled);
+  const regexp = opt.regexp;
+  assert(regexp, '"regexp" option must be set');
+  const grammar = opt.grammar;
+  assert(grammar, '"grammar" option must be set');
+  let parserType;
+  switch (opt.parserType) {
+    case ParserType.LALR1:
+    case ParserType.LR1:
+    case ParserType.MinimalLR1:
+    case ParserType.GLR:
+      parserType = opt.parserType;
+      break;
+    case undefined:
+    case null:
+        parserType = ParserType.LALR1;
+      break;
+    default:
+      throw new Error(`"parserType" option ${opt.parserType} is invalid`);
+    }
+  return Executor.build(regexp, grammar, parserType);
+}
+
+/**
+ * Compiles regular expressions and grammar to tables which can be passed in "compiled" option of build.
+ * @param {BuilderOptions} options
+ * @return {Uint8Array}
+ */
+function compile(options) {
+  const executor = buildExecutor(options);
+  const data = executor.to_bytes();
+  executor.free();
+  return data;
+}
+
 /**
  * Creates and return an instance of Server or Client.
  * @param {BuilderOptions | Array<BuilderOptions>} options
@@ -423,28 +482,10 @@ function build(options, type = 'server') {
   if (!Array.isArray(options)) options = [options];
   const execOptions = [];
   options.forEach((opt) => {
-    const regexp = opt.regexp;
-    assert(regexp, '"regexp" option must be set');
-    const grammar = opt.grammar;
-    assert(grammar, '"grammar" option must be set');
-    const proto = opt.proto;
-    let parserType;
-    switch (opt.parserType) {
-      case ParserType.LALR1:
-      case ParserType.LR1:
-        parserType = opt.parserType;
-        break;
-      case undefined:
-      case null:
-          parserType = ParserType.LALR1;
-        break;
-      default:
-        throw new Error(`"parserType" option ${opt.parserType} is invalid`);
-      }
-    const executor = Executor.build(regexp, grammar, parserType);
+    const executor = buildExecutor(opt);
     execOptions.push({
       executor,
-      proto
+      proto: opt.proto
     });
   });
 
@@ -462,6 +503,8 @@ function build(options, type = 'server') {
 
 module.exports = {
   build,
+  compile,
   hash,
-  ParserType
+  ParserType,
+  Framing
 }
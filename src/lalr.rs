@@ -1,34 +1,37 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
-use js_sys::Function;
-use wasm_bindgen::prelude::JsValue;
+use std::fmt;
 
 use super::utils::*;
+use super::action::*;
 use super::lex::*;
+use super::error::*;
+use super::binary::{BinaryWriter, BinaryReader};
+use super::rust_action::RustAction;
 
 fn reg_exp() -> &'static str {
   "space                \\s+
     digit               [0-9]       DEF
     letter              _|[A-Za-z]  DEF
+    error               error
+    left_assoc          %left
+    right_assoc         %right
+    nonassoc            %nonassoc
+    prec                %prec
+    label               {letter}({letter}|{digit})*=
     nonterm_name        {letter}({letter}|{digit})*
-    term_name           \'\\S+\'    {set(get().slice(1,-1))}
+    term_name           \'\\S+\'    {unquote}
     colon               :
     vert_line           \\|
+    lparen              \\(
+    rparen              \\)
+    question            \\?
+    star                \\*
+    plus                \\+
+    separator           %
     rust_action_code    \\[[\\s|\\S]+?\\]
     action_code         \\{[\\s|\\S]+?\\}
     semicolon           ;
-    error               error
-  "
-}
-
-fn rust_action_regular_definition_text() -> &'static str {
-  "space                \\s+        {pass()}
-    digit               [0-9]       DEF
-    set                 set
-    index               {digit}({digit}|{digit})*
-    '('                 \\(
-    ','                 ,
-    ')'                 \\)
   "
 }
 
@@ -169,6 +172,49 @@ impl GotoStatesOpt {
       None => None
     }
   }
+
+  pub fn len(&self) -> usize {
+    self.states.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.states.is_empty()
+  }
+
+  /// Returns pairs of symbol name and goto state of the state.
+  pub fn transitions(&self, state: usize) -> Vec<(usize, usize)> {
+    match self.states.get(state) {
+      Some(Some(st)) => st.states.iter().enumerate()
+        .filter_map(|(name, goto)| goto.map(|goto| (name, goto))).collect(),
+      _ => vec!()
+    }
+  }
+
+  pub fn write(&self, writer: &mut BinaryWriter) {
+    writer.write_usize(self.states.len());
+    for state in 0..self.states.len() {
+      let transitions = self.transitions(state);
+      writer.write_usize(transitions.len());
+      for (name, goto) in transitions {
+        writer.write_name(name);
+        writer.write_usize(goto);
+      }
+    }
+  }
+
+  pub fn read(reader: &mut BinaryReader) -> Result<Self, Error> {
+    let mut states = GotoStates::new();
+    let len = reader.read_usize()?;
+    for state in 0..len {
+      let count = reader.read_usize()?;
+      for _ in 0..count {
+        let name = reader.read_name()?;
+        let goto = reader.read_usize()?;
+        states.set_state(state, name, goto);
+      }
+    }
+    Ok(Self::from(&states))
+  }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -279,6 +325,14 @@ impl ActionStates1Opt {
       _ => None
     }
   }
+
+  pub fn names(&self) -> Vec<usize> {
+    let mut names = vec!();
+    for name in 0..self.states.len() {
+      if self.states[name].is_some() {names.push(name)}
+    }
+    names
+  }
 }
 
 
@@ -327,11 +381,20 @@ impl ActionStates {
     state1.set_state(name, action_state);
   }
 
+  pub fn remove_state(&mut self, state: usize, name: usize) {
+    if let Some(state1) = self.states.get_mut(&state) {
+      state1.states.remove(&name);
+    }
+  }
+
 }
 
 #[derive(Debug)]
 pub struct ActionStatesOpt {
-  states: Vec<Option<ActionStates1Opt>>
+  states: Vec<Option<ActionStates1Opt>>,
+  resolutions: Vec<ConflictResolution>,
+  alternatives: HashMap<(usize, usize), Vec<ActionState2>>,
+  glr: bool
 }
 
 impl ActionStatesOpt {
@@ -353,13 +416,19 @@ impl ActionStatesOpt {
       };
     }
     Self {
-      states: states_opt
+      states: states_opt,
+      resolutions: vec!(),
+      alternatives: HashMap::new(),
+      glr: false
     }
   }
 
   pub fn new() -> Self {
     Self {
-      states: vec!()
+      states: vec!(),
+      resolutions: vec!(),
+      alternatives: HashMap::new(),
+      glr: false
     }
   }
 
@@ -374,6 +443,139 @@ impl ActionStatesOpt {
     }
   }
 
+  pub fn len(&self) -> usize {
+    self.states.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.states.is_empty()
+  }
+
+  /// Returns pairs of symbol name and action of the state.
+  pub fn actions(&self, state: usize) -> Vec<(usize, &ActionState2)> {
+    match self.states.get(state) {
+      Some(Some(st)) => st.states.iter().enumerate()
+        .filter_map(|(name, action)| action.as_ref().map(|action| (name, action))).collect(),
+      _ => vec!()
+    }
+  }
+
+  /// Whether the conflicting actions are kept for the GLR parser.
+  pub fn is_glr(&self) -> bool {
+    self.glr
+  }
+
+  /// Whether the conflicting actions are left in the tables.
+  pub fn has_alternatives(&self) -> bool {
+    !self.alternatives.is_empty()
+  }
+
+  /// Returns every action of the state on the symbol, more than one if they are in conflict.
+  pub fn glr_actions(&self, state: usize, name: usize) -> Vec<&ActionState2> {
+    match self.alternatives.get(&(state, name)) {
+      Some(actions) => actions.iter().collect(),
+      _ => self.state(state, name).into_iter().collect()
+    }
+  }
+
+  fn write_action(writer: &mut BinaryWriter, grammar: &Grammar, action: &ActionState2) {
+    writer.write_usize(match action.state {
+      ActionState::Shift => 0,
+      ActionState::Reduce => 1,
+      ActionState::Accept => 2
+    });
+    writer.write_option(action.goto);
+    writer.write_option(match action.production {
+      Some(ref production) => grammar.production_index(production),
+      _ => None
+    });
+  }
+
+  fn read_action(reader: &mut BinaryReader, grammar: &Grammar) -> Result<ActionState2, Error> {
+    let action_state = match reader.read_usize()? {
+      0 => ActionState::Shift,
+      1 => ActionState::Reduce,
+      2 => ActionState::Accept,
+      value => return Err(Error::InvalidBinary {message: format!("Invalid action {}", value)})
+    };
+    let goto = reader.read_option()?;
+    let production = match reader.read_option()? {
+      Some(index) if index < grammar.len() => Some(grammar.production_clone_rc(index)),
+      Some(index) => return Err(Error::InvalidBinary {message: format!("Invalid index of production {}", index)}),
+      _ => None
+    };
+    Ok(ActionState2::new(action_state, goto, production))
+  }
+
+  /// Writes actions of every state, productions are written as indexes in the grammar.
+  pub fn write(&self, writer: &mut BinaryWriter, grammar: &Grammar) {
+    writer.write_usize(self.states.len());
+    for state in 0..self.states.len() {
+      let actions = self.actions(state);
+      writer.write_usize(actions.len());
+      for (name, action) in actions {
+        writer.write_name(name);
+        Self::write_action(writer, grammar, action);
+      }
+    }
+    writer.write_bool(self.glr);
+    writer.write_usize(self.alternatives.len());
+    // the keys are sorted so that the same tables are written to the same bytes
+    let mut keys: Vec<&(usize, usize)> = self.alternatives.keys().collect();
+    keys.sort_unstable();
+    for key in keys {
+      let (state, name) = key;
+      let actions = &self.alternatives[key];
+      writer.write_usize(*state);
+      writer.write_name(*name);
+      writer.write_usize(actions.len());
+      for action in actions {
+        Self::write_action(writer, grammar, action);
+      }
+    }
+  }
+
+  pub fn read(reader: &mut BinaryReader, grammar: &Grammar) -> Result<Self, Error> {
+    let mut states = ActionStates::new();
+    let len = reader.read_usize()?;
+    for state in 0..len {
+      let count = reader.read_usize()?;
+      for _ in 0..count {
+        let name = reader.read_name()?;
+        states.set_state(state, name, Self::read_action(reader, grammar)?);
+      }
+    }
+    let mut action_states = Self::from(&states);
+    action_states.glr = reader.read_bool()?;
+    let len = reader.read_usize()?;
+    for _ in 0..len {
+      let state = reader.read_usize()?;
+      let name = reader.read_name()?;
+      let count = reader.read_usize()?;
+      let mut actions = vec!();
+      for _ in 0..count {
+        actions.push(Self::read_action(reader, grammar)?);
+      }
+      action_states.alternatives.insert((state, name), actions);
+    }
+    Ok(action_states)
+  }
+
+  /// Conflicts resolved by precedence and associativity of terminals.
+  pub fn resolutions(&self) -> &Vec<ConflictResolution> {
+    &self.resolutions
+  }
+
+  /// Returns names of the terminals which have an action in the state.
+  pub fn expected(&self, state: usize) -> Vec<usize> {
+    let e_term_name = GrammarSymbol::e_term().name();
+    let error_term_name = GrammarSymbol::error_term().name();
+    match self.states.get(state) {
+      Some(Some(st)) => st.names().into_iter()
+        .filter(|name| *name != e_term_name && *name != error_term_name).collect(),
+      _ => vec!()
+    }
+  }
 }
 
 enum BuildState {
@@ -381,14 +583,115 @@ enum BuildState {
   ProdName,
   WaitRight,
   Right,
-  EndAction
+  EndAction,
+  Precedence(Associativity),
+  Prec,
+  Separator(GrammarSymbol),
+  Label(String)
+}
+
+///
+/// Generated productions
+///
+/// Productions of the nonterminals generated for the groups and the repetitions of EBNF.
+/// Every generated production concatenates values of its symbols, so the group or the
+/// repetition is a single symbol for the action indices of the production where it is used.
+///
+struct GeneratedProductions {
+  prod_name: String,
+  counter: usize,
+  productions: Vec<GrammarProduction>
+}
+
+impl GeneratedProductions {
+  fn new() -> Self {
+    Self {
+      prod_name: String::new(),
+      counter: 0,
+      productions: vec!()
+    }
+  }
+
+  /// Returns a new name derived from the name of the production being built, e.g. `headers.list1`.
+  fn name(&mut self, kind: &str) -> usize {
+    self.counter += 1;
+    hash(&format!("{}.{}{}", self.prod_name, kind, self.counter))
+  }
+
+  fn push(&mut self, name: usize, mut symbols: Vec<GrammarSymbol>) {
+    if symbols.is_empty() {
+      symbols.push(GrammarSymbol::e_term());
+    }
+    let action = RustAction::set((0..symbols.len()).rev().collect());
+    let mut production = GrammarProduction::new(name, None);
+    for symbol in symbols {
+      production.push_symbol(symbol);
+    }
+    production.add_attr(hash("rust_action"), Box::new(action));
+    self.productions.push(production);
+  }
+
+  /// Returns the nonterminal of the repetition of the symbol by the operator `?`, `*` or `+`.
+  fn repetition(&mut self, symbol: GrammarSymbol, operator: usize) -> GrammarSymbol {
+    let (kind, alternatives) = if operator == hash("question") {
+      ("opt", vec!(vec!(symbol), vec!()))
+    } else if operator == hash("star") {
+      ("list", vec!(vec!(), vec!(symbol)))
+    } else {
+      ("list", vec!(vec!(symbol.clone()), vec!(symbol)))
+    };
+    let name = self.name(kind);
+    let non_term = GrammarSymbol::non_term(name, None);
+    for (i, mut symbols) in alternatives.into_iter().enumerate() {
+      if kind == "list" && i == 1 {
+        symbols.insert(0, non_term.clone());
+      }
+      self.push(name, symbols);
+    }
+    non_term
+  }
+
+  /// Returns the nonterminal of one or more symbols separated by the separator.
+  fn separated(&mut self, symbol: GrammarSymbol, separator: GrammarSymbol) -> GrammarSymbol {
+    let name = self.name("list");
+    let non_term = GrammarSymbol::non_term(name, None);
+    self.push(name, vec!(symbol.clone()));
+    self.push(name, vec!(non_term.clone(), separator, symbol));
+    non_term
+  }
+
+  /// Returns the nonterminal of the group with the alternatives.
+  fn group(&mut self, alternatives: Vec<Vec<GrammarSymbol>>) -> GrammarSymbol {
+    let name = self.name("group");
+    for symbols in alternatives {
+      self.push(name, symbols);
+    }
+    GrammarSymbol::non_term(name, None)
+  }
 }
 
 pub struct GrammarBuilder {
 }
 
+struct GrammarLexActions;
+
+impl LexActions for GrammarLexActions {
+  fn lex_action(&mut self, _tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    match action {
+      Some("unquote") => {
+        let value = ctx.get();
+        let value = value[1..value.len()-1].to_vec();
+        ctx.set(value);
+      },
+      _ => {}
+    }
+    Ok(())
+  }
+}
+
 impl GrammarBuilder {
-  pub fn from_text(grammar: String) -> Result<Grammar, String> {
+  pub fn from_text(grammar: String) -> Result<Grammar, Error> {
     let grammar = GrammarBuilder::build_grammar(grammar)?;
     Ok(grammar)
   }
@@ -404,197 +707,462 @@ impl GrammarBuilder {
     }
   }
 
-  fn build_rust_action(rust_action_text: String) -> Result<RustAction, String> {
+  fn build_rust_action(rust_action_text: String, labels: Option<&Labels>) -> Result<RustAction, String> {
+    let len = rust_action_text.len();
+    RustAction::parse(&rust_action_text[1..len-1], labels)
+      .map_err(|err| format!("Error parse rust action: {}", err))
+  }
+
+  fn build_grammar(grammar: String) -> Result<Grammar, Error> {
 
-    fn get_token(lex: &mut Lex, ctx: &JsValue, err_message: &str) -> Result<Token, String> {
-      let tkn = lex.get_token(ctx).expect("Error in get_token");
-      if tkn == None {return Err(err_message.to_string())};
-      Ok(tkn.unwrap())
+    fn syntax_error(lex: &Lex, position: usize, message: &str) -> Error {
+      let (line, column) = lex.line_column(position);
+      Error::GrammarSyntax {line, column, message: message.to_string()}
     }
 
-    fn get_index(lex: &mut Lex, ctx: &JsValue, err_message: &str) -> Result<usize, String> {
-      let tkn = get_token(lex, ctx, err_message)?;
-      if tkn.name() != hash("index") {return Err(err_message.to_string())};
-      let str_val = tkn.value_to_string();
-      let index = usize::from_str_radix(&str_val, 10);
-      if index.is_err() {return Err(err_message.to_string())};
-      Ok(index.unwrap())
+    let lex_actions = &mut GrammarLexActions;
+    let mut lex = Lex::new(grammar);
+    lex.set_regular_definition_text(reg_exp().to_string())?;
+    let mut grammar = Grammar::new();
+    let mut production_block: Vec<usize> = vec![];
+    let mut production = GrammarProduction::new(hash(""), None);
+    let mut prod_name: usize = 0;
+    let mut precedence_level: usize = 0;
+    let mut state = BuildState::WaitName;
+    let mut generated = GeneratedProductions::new();
+    // alternatives and symbols of the current alternative of the open groups
+    let mut groups: Vec<(Vec<Vec<GrammarSymbol>>, Vec<GrammarSymbol>)> = vec!();
+
+    macro_rules! push_symbol {
+      ($symbol: expr) => {
+        {
+          let symbol = $symbol;
+          match groups.last_mut() {
+            Some((_, symbols)) => symbols.push(symbol),
+            _ => production.push_symbol(symbol)
+          }
+        }
+      }
     }
 
-    fn get_action(lex: &mut Lex, ctx: &JsValue, err_message: &str,
-      index: usize, index2: Option<usize>, index3: Option<usize>,
-      index4: Option<usize>, index5: Option<usize>) -> Result<RustAction, String> {
-        let tkn = lex.get_token(ctx).expect("Error in get_token");
-        if tkn.is_some() {return Err(err_message.to_string())};
-        Ok(RustAction::new(RustActionCommand::Set, index, index2, index3, index4, index5))
+    macro_rules! pop_symbol {
+      () => {
+        match groups.last_mut() {
+          Some((_, symbols)) => symbols.pop(),
+          _ if production.len() > 0 => {
+            let symbol = production.symbol(production.len() - 1).clone();
+            production.remove_symbol(production.len() - 1);
+            Some(symbol)
+          },
+          _ => None
+        }
+      }
     }
 
-    let len = rust_action_text.len();
-    let rust_action_text = (&rust_action_text[1..len-1]).to_string();
-    let null_context = &JsValue::NULL;
-    let err_message = "Error parse rust action";
-    let mut lex = Lex::new(rust_action_text);
-    lex.set_regular_definition_text(rust_action_regular_definition_text().to_string());
-
-    let tkn = get_token(&mut lex, null_context, err_message)?;
-    if tkn.name() != hash("set") {return Err(err_message.to_string())};
-    let tkn = get_token(&mut lex, null_context, err_message)?;
-    if tkn.name() != hash("(") {return Err(err_message.to_string())};
-    let index = get_index(&mut lex, null_context, err_message)?;
-    let tkn = get_token(&mut lex, null_context, err_message)?;
-    if tkn.name() == hash(")") {
-      return get_action(&mut lex, null_context, err_message, index, None, None, None, None);
-    };
+    // labels of the current production with positions of the symbols from the leftmost one
+    let mut labels: Vec<(String, usize)> = vec!();
 
-    if tkn.name() != hash(",") {return Err(err_message.to_string())};
-    let index2 = Some(get_index(&mut lex, null_context, err_message)?);
-    let tkn = get_token(&mut lex, null_context, err_message)?;
-    if tkn.name() == hash(")") {
-      return get_action(&mut lex, null_context, err_message, index, index2, None, None, None);
-    };
+    macro_rules! set_labels {
+      () => {
+        if labels.len() > 0 {
+          let len = production.len();
+          let labels = labels.drain(..).map(|(name, position)| (name, len - 1 - position)).collect();
+          production.add_attr(hash("labels"), Box::new(Labels::new(labels)));
+        }
+      }
+    }
 
-    if tkn.name() != hash(",") {return Err(err_message.to_string())};
-    let index3 = Some(get_index(&mut lex, null_context, err_message)?);
-    let tkn = get_token(&mut lex, null_context, err_message)?;
-    if tkn.name() == hash(")") {
-      return get_action(&mut lex, null_context, err_message, index, index2, index3, None, None);
-    };
+    macro_rules! rust_action {
+      ($tkn: expr) => {
+        {
+          set_labels!();
+          // indices of the labels differ in the productions of the block, so the action is built for each one
+          let build = |production: &GrammarProduction| {
+            let labels = production.attr(&hash("labels")).and_then(|attr| attr.as_labels());
+            GrammarBuilder::build_rust_action($tkn.value_to_string(), labels)
+              .map_err(|err| syntax_error(&lex, lex.token_position(), &err))
+          };
+          let action = build(&production)?;
+          production.add_attr(hash("rust_action"), Box::new(action));
+          for i in &production_block {
+            let action = build(grammar.production(*i))?;
+            grammar.production_mut(*i).add_attr(hash("rust_action"), Box::new(action));
+          }
+        }
+      }
+    }
 
-    if tkn.name() != hash(",") {return Err(err_message.to_string())};
-    let index4 = Some(get_index(&mut lex, null_context, err_message)?);
-    let tkn = get_token(&mut lex, null_context, err_message)?;
-    if tkn.name() == hash(")") {
-      return get_action(&mut lex, null_context, err_message, index, index2, index3, index4, None);
-    };
+    // names of the arguments of the javascript function of the action can't be labels
+    let reserved = ["bind", "id", "lookup", "get", "set", "set_val", "set_name", "set_name_from_hash", "push_after"];
 
-    if tkn.name() != hash(",") {return Err(err_message.to_string())};
-    let index5 = Some(get_index(&mut lex, null_context, err_message)?);
-    let tkn = get_token(&mut lex, null_context, err_message)?;
-    if tkn.name() == hash(")") {
-      return get_action(&mut lex, null_context, err_message, index, index2, index3, index4, index5);
-    };
+    macro_rules! label {
+      ($tkn: expr) => {
+        {
+          let value = $tkn.value_to_string();
+          let label = value[..value.len()-1].to_string();
+          if reserved.contains(&label.as_str()) {
+            return Err(syntax_error(&lex, lex.token_position(), &format!("Reserved name {} of label!", label)));
+          }
+          if labels.iter().any(|(name, _)| name == &label) {
+            return Err(syntax_error(&lex, lex.token_position(), &format!("Duplicate label {}!", label)));
+          }
+          label
+        }
+      }
+    }
 
-    Err(err_message.to_string())
-  }
+    macro_rules! symbol {
+      ($tkn: expr) => {
+        if $tkn.name() == hash("nonterm_name") {
+          Some(GrammarSymbol::non_term(hash(&$tkn.value_to_string()), None))
+        } else if $tkn.name() == hash("term_name") {
+          Some(GrammarSymbol::term(hash(&$tkn.value_to_string()), None))
+        } else if $tkn.name() == hash("error") {
+          Some(GrammarSymbol::error_term())
+        } else {
+          None
+        }
+      }
+    }
 
-  fn build_grammar(grammar: String) -> Result<Grammar, String> {
-    let null_context = &JsValue::NULL;
-    let mut lex = Lex::new(grammar);
-    lex.set_regular_definition_text(reg_exp().to_string());
-    let mut grammar = Grammar::new();
-    let mut production_block: Vec<usize> = vec![];
-    let mut production = GrammarProduction::new(hash(""), None);
-    let mut prod_name: usize = 0;
-    let mut state = BuildState::WaitName;
     loop {
-      let tkn = lex.get_token(null_context).expect("Error in get_token");
+      let tkn = match lex.get_token(lex_actions) {
+        Ok(tkn) => tkn,
+        Err(Error::LexNoMatch {offset}) => return Err(syntax_error(&lex, offset, "Unknown symbol!")),
+        Err(err) => return Err(err)
+      };
       if tkn == None {break};
       let tkn = tkn.unwrap();
       if tkn.name() == hash("space") {continue};
 
       match &state {
         BuildState::WaitName => {
+          let assoc = if tkn.name() == hash("left_assoc") {Some(Associativity::Left)}
+            else if tkn.name() == hash("right_assoc") {Some(Associativity::Right)}
+            else if tkn.name() == hash("nonassoc") {Some(Associativity::NonAssoc)}
+            else {None};
+          if let Some(assoc) = assoc {
+            precedence_level += 1;
+            state = BuildState::Precedence(assoc);
+            continue;
+          }
           if tkn.name() != hash("nonterm_name") {
-            return Err("Expected name of production!".to_string());
+            return Err(syntax_error(&lex, lex.token_position(), "Expected name of production!"));
           };
           prod_name = hash(&tkn.value_to_string());
+          generated.prod_name = tkn.value_to_string();
           state = BuildState::ProdName;
         },
         BuildState::ProdName => {
           if tkn.name() != hash("colon") {
-            return Err("Expected ':'!".to_string());
+            return Err(syntax_error(&lex, lex.token_position(), "Expected ':'!"));
           };
           production = GrammarProduction::new(prod_name, None);
           production_block.clear();
           state = BuildState::WaitRight;
         },
         BuildState::WaitRight => {
-          if tkn.name() == hash("nonterm_name") {
-            production.push_symbol(GrammarSymbol::non_term(hash(&tkn.value_to_string()), None));
+          if let Some(symbol) = symbol!(tkn) {
+            production.push_symbol(symbol);
             state = BuildState::Right;
-          } else if tkn.name() == hash("term_name") {
-            production.push_symbol(GrammarSymbol::term(hash(&tkn.value_to_string()), None));
+          } else if tkn.name() == hash("lparen") {
+            groups.push((vec!(), vec!()));
             state = BuildState::Right;
+          } else if tkn.name() == hash("label") {
+            state = BuildState::Label(label!(tkn));
+          } else if tkn.name() == hash("prec") {
+            state = BuildState::Prec;
           } else if tkn.name() == hash("semicolon") {
             production.push_symbol(GrammarSymbol::e_term());
             grammar.push_production(Rc::new(production.clone()));
             production_block.push(grammar.len() - 1);
             state = BuildState::WaitName;
           } else if tkn.name() == hash("rust_action_code") {
-            let action = GrammarBuilder::build_rust_action(tkn.value_to_string())?;
-            production.add_attr(hash("rust_action"), Box::new(action.clone()));
-            for i in &production_block {
-              grammar.production_mut(*i).add_attr(hash("rust_action"), Box::new(action.clone()));
-            }
+            rust_action!(tkn);
             state = BuildState::EndAction;
           } else if tkn.name() == hash("action_code") {
-            let func = Function::new_with_args("bind, id, lookup, get, set, set_val, set_name, set_name_from_hash, push_after", &tkn.value_to_string());
-            production.add_attr(hash("action"), Box::new(func.clone()));
+            set_labels!();
+            let action_code = tkn.value_to_string();
+            let action_code = action_code[1..action_code.len()-1].to_string();
+            production.add_attr(hash("action"), Box::new(action_code.clone()));
             for i in &production_block {
-              grammar.production_mut(*i).add_attr(hash("action"), Box::new(func.clone()));
+              grammar.production_mut(*i).add_attr(hash("action"), Box::new(action_code.clone()));
             }
             state = BuildState::EndAction;
           } else {
-            return Err("Production symbol or action is expected!".to_string());
+            return Err(syntax_error(&lex, lex.token_position(), "Production symbol or action is expected!"));
           }
         },
         BuildState::Right => {
-          if tkn.name() == hash("nonterm_name") {
-            production.push_symbol(GrammarSymbol::non_term(hash(&tkn.value_to_string()), None));
-          } else if tkn.name() == hash("term_name") {
-            production.push_symbol(GrammarSymbol::term(hash(&tkn.value_to_string()), None));
+          if !groups.is_empty() && [hash("prec"), hash("rust_action_code"), hash("action_code"), hash("semicolon")]
+            .contains(&tkn.name()) {
+            return Err(syntax_error(&lex, lex.token_position(), "Expected ')'!"));
+          }
+          if let Some(symbol) = symbol!(tkn) {
+            push_symbol!(symbol);
+          } else if tkn.name() == hash("lparen") {
+            groups.push((vec!(), vec!()));
+          } else if tkn.name() == hash("label") {
+            if !groups.is_empty() {
+              return Err(syntax_error(&lex, lex.token_position(), "Label is not allowed in group!"));
+            }
+            state = BuildState::Label(label!(tkn));
+          } else if tkn.name() == hash("rparen") {
+            let (mut alternatives, symbols) = match groups.pop() {
+              Some(group) => group,
+              _ => return Err(syntax_error(&lex, lex.token_position(), "Unexpected ')'!"))
+            };
+            alternatives.push(symbols);
+            push_symbol!(generated.group(alternatives));
+          } else if [hash("question"), hash("star"), hash("plus")].contains(&tkn.name()) {
+            let symbol = match pop_symbol!() {
+              Some(symbol) => symbol,
+              _ => return Err(syntax_error(&lex, lex.token_position(),
+                &format!("Expected symbol before {}!", tkn.value_to_string())))
+            };
+            push_symbol!(generated.repetition(symbol, tkn.name()));
+          } else if tkn.name() == hash("separator") {
+            match pop_symbol!() {
+              Some(symbol) => state = BuildState::Separator(symbol),
+              _ => return Err(syntax_error(&lex, lex.token_position(), "Expected symbol before %!"))
+            };
+          } else if tkn.name() == hash("prec") {
+            state = BuildState::Prec;
+          } else if tkn.name() == hash("vert_line") && !groups.is_empty() {
+            let (alternatives, symbols) = groups.last_mut().unwrap();
+            alternatives.push(std::mem::take(symbols));
           } else if tkn.name() == hash("vert_line") {
+            set_labels!();
             GrammarBuilder::if_empty_error_production_add_e_term(&mut production);
             grammar.push_production(Rc::new(production));
             production_block.push(grammar.len() - 1);
             production = GrammarProduction::new(prod_name.clone(), None);
           } else if tkn.name() == hash("rust_action_code") {
-            let action = GrammarBuilder::build_rust_action(tkn.value_to_string())?;
-            production.add_attr(hash("rust_action"), Box::new(action.clone()));
-            for i in &production_block {
-              grammar.production_mut(*i).add_attr(hash("rust_action"), Box::new(action.clone()));
-            }
+            rust_action!(tkn);
             state = BuildState::EndAction;
           } else if tkn.name() == hash("action_code") {
-            let func = Function::new_with_args("bind, id, lookup, get, set, set_val, set_name, set_name_from_hash, push_after", &tkn.value_to_string());
-            production.add_attr(hash("action"), Box::new(func.clone()));
+            set_labels!();
+            let action_code = tkn.value_to_string();
+            let action_code = action_code[1..action_code.len()-1].to_string();
+            production.add_attr(hash("action"), Box::new(action_code.clone()));
             for i in &production_block {
-              grammar.production_mut(*i).add_attr(hash("action"), Box::new(func.clone()));
+              grammar.production_mut(*i).add_attr(hash("action"), Box::new(action_code.clone()));
             }
             state = BuildState::EndAction;
           } else if tkn.name() == hash("semicolon") {
+            set_labels!();
             GrammarBuilder::if_empty_error_production_add_e_term(&mut production);
             grammar.push_production(Rc::new(production.clone()));
             production_block.push(grammar.len() - 1);
             state = BuildState::WaitName;
           } else {
-            return Err(format!("Unexpected token {}!", tkn.value_to_string()));
+            return Err(syntax_error(&lex, lex.token_position(),
+              &format!("Unexpected token {}!", tkn.value_to_string())));
+          }
+        },
+        BuildState::Precedence(assoc) => {
+          if tkn.name() == hash("term_name") {
+            grammar.set_precedence(hash(&tkn.value_to_string()), Precedence::new(precedence_level, assoc.clone()));
+          } else if tkn.name() == hash("semicolon") {
+            state = BuildState::WaitName;
+          } else {
+            return Err(syntax_error(&lex, lex.token_position(), "Expected terminal name or ';'!"));
+          }
+        },
+        BuildState::Prec => {
+          if tkn.name() != hash("term_name") {
+            return Err(syntax_error(&lex, lex.token_position(), "Expected terminal name after %prec!"));
+          }
+          production.add_attr(hash("prec"), Box::new(hash(&tkn.value_to_string())));
+          state = BuildState::Right;
+        },
+        BuildState::Label(label) => {
+          labels.push((label.clone(), production.len()));
+          if let Some(symbol) = symbol!(tkn) {
+            production.push_symbol(symbol);
+          } else if tkn.name() == hash("lparen") {
+            groups.push((vec!(), vec!()));
+          } else {
+            return Err(syntax_error(&lex, lex.token_position(), "Expected symbol after label!"));
           }
+          state = BuildState::Right;
+        },
+        BuildState::Separator(symbol) => {
+          let separator = match symbol!(tkn) {
+            Some(separator) => separator,
+            _ => return Err(syntax_error(&lex, lex.token_position(), "Expected separator symbol after %!"))
+          };
+          push_symbol!(generated.separated(symbol.clone(), separator));
+          state = BuildState::Right;
         },
         BuildState::EndAction => {
           if tkn.name() == hash("semicolon") {
+            set_labels!();
             GrammarBuilder::if_empty_error_production_add_e_term(&mut production);
             grammar.push_production(Rc::new(production.clone()));
             production_block.push(grammar.len() - 1);
             state = BuildState::WaitName;
           } else {
-            return Err(format!("Unexpected token {}!", tkn.value_to_string()));
+            return Err(syntax_error(&lex, lex.token_position(),
+              &format!("Unexpected token {}!", tkn.value_to_string())));
           }
         }
       }
     }
+    for production in generated.productions {
+      grammar.push_production(Rc::new(production));
+    }
     Ok(grammar)
   }
 
 }
 
-#[derive(Debug, PartialEq)]
-enum Conflict {
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
   ShiftShift,
   ShiftReduce,
   ReduceReduce
 }
 
+///
+/// Conflict of two actions in the parser state on the lookahead symbol
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrammarConflict {
+  kind: Conflict,
+  state: usize,
+  symbol: usize,
+  production1: Option<Rc<GrammarProduction>>,
+  production2: Rc<GrammarProduction>
+}
+
+impl GrammarConflict {
+  pub fn new(kind: Conflict, state: usize, symbol: usize, production1: Option<Rc<GrammarProduction>>,
+    production2: Rc<GrammarProduction>) -> Self {
+    Self {
+      kind,
+      state,
+      symbol,
+      production1,
+      production2
+    }
+  }
+
+  pub fn kind(&self) -> &Conflict {
+    &self.kind
+  }
+
+  pub fn state(&self) -> usize {
+    self.state
+  }
+
+  pub fn symbol(&self) -> usize {
+    self.symbol
+  }
+
+  pub fn production1(&self) -> &Option<Rc<GrammarProduction>> {
+    &self.production1
+  }
+
+  pub fn production2(&self) -> &Rc<GrammarProduction> {
+    &self.production2
+  }
+}
+
+impl fmt::Display for GrammarConflict {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.production1 {
+      Some(ref production1) =>
+        write!(f, "{:?} conflict in state {} on symbol {}, production1: {}, production2: {}",
+          self.kind, self.state, symbol_name(self.symbol), production1, self.production2),
+      _ =>
+        write!(f, "{:?} conflict in state {} on symbol {}, production: {}",
+          self.kind, self.state, symbol_name(self.symbol), self.production2)
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+  Shift,
+  Reduce(Rc<GrammarProduction>),
+  Error
+}
+
+///
+/// Conflict resolved by precedence and associativity
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictResolution {
+  conflict: GrammarConflict,
+  resolution: Resolution
+}
+
+impl ConflictResolution {
+  pub fn new(conflict: GrammarConflict, resolution: Resolution) -> Self {
+    Self {
+      conflict,
+      resolution
+    }
+  }
+
+  pub fn conflict(&self) -> &GrammarConflict {
+    &self.conflict
+  }
+
+  pub fn resolution(&self) -> &Resolution {
+    &self.resolution
+  }
+}
+
+impl fmt::Display for ConflictResolution {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.resolution {
+      Resolution::Shift => write!(f, "{} resolved as shift", self.conflict),
+      Resolution::Reduce(ref production) => write!(f, "{} resolved as reduce by {}", self.conflict, production),
+      Resolution::Error => write!(f, "{} resolved as error (nonassoc)", self.conflict)
+    }
+  }
+}
+
+/// Resolves shift/reduce conflict on the symbol by precedence of the symbol and the production.
+fn resolve_shift_reduce(grammar: &Grammar, symbol: usize, production: &Rc<GrammarProduction>) -> Option<Resolution> {
+  let symbol_prec = grammar.precedence(symbol)?;
+  let production_prec = grammar.production_precedence(production)?;
+  let resolution = if production_prec.level() > symbol_prec.level() {
+    Resolution::Reduce(production.clone())
+  } else if production_prec.level() < symbol_prec.level() {
+    Resolution::Shift
+  } else {
+    match symbol_prec.assoc() {
+      Associativity::Left => Resolution::Reduce(production.clone()),
+      Associativity::Right => Resolution::Shift,
+      Associativity::NonAssoc => Resolution::Error
+    }
+  };
+  Some(resolution)
+}
+
+/// Resolves reduce/reduce conflict in favor of the production with the higher precedence,
+/// or the one defined first in the grammar if precedences are equal.
+fn resolve_reduce_reduce(grammar: &Grammar, production1: &Rc<GrammarProduction>,
+  production2: &Rc<GrammarProduction>) -> Option<Resolution> {
+  let prec1 = grammar.production_precedence(production1)?;
+  let prec2 = grammar.production_precedence(production2)?;
+  let production = if prec1.level() != prec2.level() {
+    match prec1.level() > prec2.level() {
+      true => production1,
+      false => production2
+    }
+  } else {
+    match grammar.production_index(production1) <= grammar.production_index(production2) {
+      true => production1,
+      false => production2
+    }
+  };
+  Some(Resolution::Reduce(production.clone()))
+}
+
 fn build_goto_states<T: LRItems>(grammar: &Grammar) -> GotoStatesOpt {
   let mut states = GotoStates::new();
   let canonical = T::canonical(grammar);
@@ -617,114 +1185,456 @@ fn build_goto_states<T: LRItems>(grammar: &Grammar) -> GotoStatesOpt {
   GotoStatesOpt::from(&states)
 }
 
-pub trait StatesBuilder {
-  fn build_goto_states(grammar: &Grammar) -> GotoStatesOpt;
+///
+/// Conflict with the LR(1) items of its state and the shortest example of input reaching it
+///
+#[derive(Debug, Clone)]
+pub struct ConflictReport {
+  conflict: GrammarConflict,
+  items: Vec<LRItem>,
+  example: Vec<usize>
+}
 
-  fn build_collection_items<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Vec<LRItems1>;
+impl ConflictReport {
+  pub fn new(conflict: GrammarConflict, items: Vec<LRItem>, example: Vec<usize>) -> Self {
+    Self {
+      conflict,
+      items,
+      example
+    }
+  }
 
-  fn build_action_states<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<ActionStatesOpt, String> {
-    let lalr = Self::build_collection_items(grammar, goto_states);
-    let mut action_states = ActionStates::new();
-    let mut conflicts: Vec<(Conflict, Option<Rc<GrammarProduction>>, Rc<GrammarProduction>, usize)> = Vec::new();
+  pub fn conflict(&self) -> &GrammarConflict {
+    &self.conflict
+  }
 
-    let mut push_if_not_exists = |item: (Conflict, Option<Rc<GrammarProduction>>, Rc<GrammarProduction>, usize)| {
-      if !conflicts.iter().any(|i| i.0 == item.0 && i.1 == item.1 && i.2 == item.2 && i.3 == item.3) {
-        conflicts.push(item);
-      }
-    };
+  pub fn kind(&self) -> &Conflict {
+    self.conflict.kind()
+  }
 
-    for i in 0..lalr.len() {
-      let items = &lalr[i];
-      for i1 in 0..items.len() {
-        let item = items.item(i1);
-        if item.position < item.production.len() {
-          let symbol = item.production.symbol(item.position);
-          if symbol.is_term() {
-            let j = goto_states.state(i, symbol.name());
-            if j.is_some() {
-              let j = *j.unwrap();
-              let state = action_states.state(&i, &symbol.name());
-              if state.is_some() {
-                let state = state.unwrap();
-                if state.state() != &ActionState::Shift {
-                  push_if_not_exists((Conflict::ShiftReduce,
-                    state.production().clone(), item.production.clone(), symbol.name()));
-                } else if state.goto() != &Some(j) {
-                  push_if_not_exists((Conflict::ShiftShift,
-                    state.production().clone(), item.production.clone(), symbol.name()));
-                }
-              }
+  pub fn state(&self) -> usize {
+    self.conflict.state()
+  }
+
+  pub fn lookahead(&self) -> usize {
+    self.conflict.symbol()
+  }
 
-              action_states.set_state(i, symbol.name(),
-                ActionState2::new(ActionState::Shift, Some(j), None));
+  pub fn production1(&self) -> &Option<Rc<GrammarProduction>> {
+    self.conflict.production1()
+  }
+
+  pub fn production2(&self) -> &Rc<GrammarProduction> {
+    self.conflict.production2()
+  }
+
+  /// LR(1) items of the state.
+  pub fn items(&self) -> &Vec<LRItem> {
+    &self.items
+  }
+
+  /// Names of symbols of the shortest input which brings the parser to the state, the
+  /// lookahead follows it. Nonterminals which derive no terminal string are left as is.
+  pub fn example(&self) -> &Vec<usize> {
+    &self.example
+  }
+}
+
+impl fmt::Display for ConflictReport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "{}", self.conflict)?;
+    writeln!(f, "  items:")?;
+    for item in &self.items {
+      writeln!(f, "    {}", item)?;
+    }
+    write!(f, "  example:")?;
+    for name in &self.example {
+      write!(f, " {}", symbol_name(*name))?;
+    }
+    write!(f, " . {}", symbol_name(self.lookahead()))
+  }
+}
+
+/// Shortest strings of terminals derived from the nonterminals.
+fn shortest_derivations(grammar: &Grammar) -> HashMap<usize, Vec<usize>> {
+  let e_term_name = GrammarSymbol::e_term().name();
+  let mut derivations: HashMap<usize, Vec<usize>> = HashMap::new();
+  loop {
+    let mut changed = false;
+    for index in 0..grammar.len() {
+      let production = grammar.production(index);
+      let mut derivation = vec!();
+      let mut complete = true;
+      for i in 0..production.len() {
+        let symbol = production.symbol(i);
+        if symbol.is_non_term() {
+          match derivations.get(&symbol.name()) {
+            Some(symbols) => derivation.extend(symbols),
+            _ => {
+              complete = false;
+              break;
             }
           }
+        } else if symbol.name() != e_term_name {
+          derivation.push(symbol.name());
         }
+      }
+      if complete && derivations.get(&production.name()).is_none_or(|symbols| derivation.len() < symbols.len()) {
+        derivations.insert(production.name(), derivation);
+        changed = true;
+      }
+    }
+    if !changed {break}
+  }
+  derivations
+}
+
+/// Shortest examples of input which bring the parser from the initial state to each state.
+fn state_examples(grammar: &Grammar, goto_states: &GotoStatesOpt) -> HashMap<usize, Vec<usize>> {
+  let derivations = shortest_derivations(grammar);
+  let mut paths: HashMap<usize, Vec<usize>> = HashMap::new();
+  let mut queue = VecDeque::new();
+  paths.insert(0, vec!());
+  queue.push_back(0);
+  while let Some(state) = queue.pop_front() {
+    for (name, goto) in goto_states.transitions(state) {
+      if paths.contains_key(&goto) {continue}
+      let mut path = paths[&state].clone();
+      path.push(name);
+      paths.insert(goto, path);
+      queue.push_back(goto);
+    }
+  }
 
-        if item.position > 0 && item.position == item.production.len() &&
-          item.production.name() != grammar.production(0).name() {
-            let term_name = item.term_name.clone().unwrap();
-            let state = action_states.state(&i, &term_name);
+  paths.into_iter().map(|(state, path)| {
+    let mut example = vec!();
+    for name in path {
+      match derivations.get(&name) {
+        Some(symbols) => example.extend(symbols),
+        _ => example.push(name)
+      }
+    }
+    (state, example)
+  }).collect()
+}
+
+/// Builds actions of the parser states, returns them with the unresolved conflicts. Conflicts
+/// which differ only in state are reported once unless `all_conflicts` is set.
+fn build_actions(grammar: &Grammar, goto_states: &GotoStatesOpt, lalr: &[LRItems1],
+  all_conflicts: bool) -> (ActionStatesOpt, Vec<GrammarConflict>) {
+  let mut action_states = ActionStates::new();
+  let mut conflicts: Vec<GrammarConflict> = Vec::new();
+
+  let mut resolutions: Vec<ConflictResolution> = Vec::new();
+  let mut errors: Vec<(usize, usize)> = Vec::new();
+
+  let mut push_if_not_exists = |item: GrammarConflict| {
+    if all_conflicts || !conflicts.iter().any(|i| i.kind == item.kind && i.production1 == item.production1 &&
+      i.production2 == item.production2 && i.symbol == item.symbol) {
+      conflicts.push(item);
+    }
+  };
+
+  // actions which are in unresolved conflicts, kept for the GLR parser
+  let mut alternatives: HashMap<(usize, usize), Vec<ActionState2>> = HashMap::new();
+  let mut push_alternatives = |state: usize, name: usize, action1: &ActionState2, action2: ActionState2| {
+    let actions = alternatives.entry((state, name)).or_default();
+    for action in [action1.clone(), action2] {
+      if !actions.iter().any(|action1| action1.state == action.state && action1.goto == action.goto &&
+        action1.production == action.production) {
+        actions.push(action);
+      }
+    }
+  };
+
+  let mut push_resolution = |item: ConflictResolution| {
+    if !resolutions.iter().any(|i| i.conflict.kind == item.conflict.kind &&
+      i.conflict.production1 == item.conflict.production1 &&
+      i.conflict.production2 == item.conflict.production2 && i.conflict.symbol == item.conflict.symbol) {
+      resolutions.push(item);
+    }
+  };
+
+  for i in 0..lalr.len() {
+    let items = &lalr[i];
+    for i1 in 0..items.len() {
+      let item = items.item(i1);
+      if item.position < item.production.len() {
+        let symbol = item.production.symbol(item.position);
+        if symbol.is_term() {
+          let j = goto_states.state(i, symbol.name());
+          if j.is_some() {
+            let j = *j.unwrap();
+            let mut resolution = None;
+            let state = action_states.state(&i, &symbol.name());
             if state.is_some() {
               let state = state.unwrap();
-              if state.state() == &ActionState::Shift {
-                push_if_not_exists((Conflict::ShiftReduce,
-                  state.production().clone(), item.production.clone(), term_name));
-              } else if state.production() != &Some(item.production.clone()) {
-                push_if_not_exists((Conflict::ReduceReduce,
-                  state.production().clone(), item.production.clone(), term_name));
+              if state.state() == &ActionState::Reduce {
+                let production = state.production().clone().unwrap();
+                resolution = resolve_shift_reduce(grammar, symbol.name(), &production);
+                let conflict = GrammarConflict::new(Conflict::ShiftReduce, i, symbol.name(),
+                  Some(item.production.clone()), production);
+                match resolution {
+                  Some(ref resolution) => push_resolution(ConflictResolution::new(conflict, resolution.clone())),
+                  _ => {
+                    push_if_not_exists(conflict);
+                    push_alternatives(i, symbol.name(), state,
+                      ActionState2::new(ActionState::Shift, Some(j), Some(item.production.clone())));
+                  }
+                }
+              } else if state.state() != &ActionState::Shift {
+                push_if_not_exists(GrammarConflict::new(Conflict::ShiftReduce, i, symbol.name(),
+                  Some(item.production.clone()), state.production().clone().unwrap()));
+                push_alternatives(i, symbol.name(), state,
+                  ActionState2::new(ActionState::Shift, Some(j), Some(item.production.clone())));
+              } else if state.goto() != &Some(j) {
+                push_if_not_exists(GrammarConflict::new(Conflict::ShiftShift, i, symbol.name(),
+                  state.production().clone(), item.production.clone()));
               }
             }
 
-            action_states.set_state(i, term_name,
-              ActionState2::new(ActionState::Reduce, None, Some(item.production.clone())));
+            match resolution {
+              Some(Resolution::Reduce(_)) => {},
+              _ => {
+                if resolution == Some(Resolution::Error) {errors.push((i, symbol.name()))}
+                action_states.set_state(i, symbol.name(),
+                  ActionState2::new(ActionState::Shift, Some(j), Some(item.production.clone())));
+              }
+            }
+          }
         }
+      }
 
-        let s_term_name = GrammarSymbol::s_term().name();
-        if item.position > 0 && item.position == item.production.len() &&
-          item.production.as_ref() == grammar.production(0) &&
-          item.term_name.clone().unwrap() == s_term_name {
-            let state = action_states.state(&i, &s_term_name);
-            if state.is_some() {
-              let state = state.unwrap();
-              if state.state() == &ActionState::Shift {
-                push_if_not_exists((Conflict::ShiftReduce,
-                  state.production().clone(), item.production.clone(), s_term_name));
-              } else if state.production() != &Some(item.production.clone()) {
-                push_if_not_exists((Conflict::ReduceReduce,
-                  state.production().clone(), item.production.clone(), s_term_name));
+      if item.position > 0 && item.position == item.production.len() &&
+        item.production.name() != grammar.production(0).name() {
+          let term_name = item.term_name.clone().unwrap();
+          let mut resolution = None;
+          let state = action_states.state(&i, &term_name);
+          if state.is_some() {
+            let state = state.unwrap();
+            let conflict = if state.state() == &ActionState::Shift {
+              resolution = resolve_shift_reduce(grammar, term_name, &item.production);
+              Some(GrammarConflict::new(Conflict::ShiftReduce, i, term_name,
+                state.production().clone(), item.production.clone()))
+            } else if state.production() != &Some(item.production.clone()) {
+              if state.state() == &ActionState::Reduce {
+                resolution = resolve_reduce_reduce(grammar, state.production().as_ref().unwrap(), &item.production);
+              }
+              Some(GrammarConflict::new(Conflict::ReduceReduce, i, term_name,
+                state.production().clone(), item.production.clone()))
+            } else {
+              None
+            };
+            if let Some(conflict) = conflict {
+              match resolution {
+                Some(ref resolution) => push_resolution(ConflictResolution::new(conflict, resolution.clone())),
+                _ => {
+                  push_if_not_exists(conflict);
+                  push_alternatives(i, term_name, state,
+                    ActionState2::new(ActionState::Reduce, None, Some(item.production.clone())));
+                }
               }
             }
+          }
+
+          match resolution {
+            Some(Resolution::Shift) => {},
+            Some(Resolution::Reduce(ref production)) if production != &item.production => {},
+            Some(Resolution::Error) => errors.push((i, term_name)),
+            _ => {
+              action_states.set_state(i, term_name,
+                ActionState2::new(ActionState::Reduce, None, Some(item.production.clone())));
+            }
+          }
+      }
 
-            action_states.set_state(i, s_term_name,
-              ActionState2::new(ActionState::Accept, None, Some(item.production.clone())));
+      let s_term_name = GrammarSymbol::s_term().name();
+      if item.position > 0 && item.position == item.production.len() &&
+        item.production.as_ref() == grammar.production(0) &&
+        item.term_name.clone().unwrap() == s_term_name {
+          let state = action_states.state(&i, &s_term_name);
+          if state.is_some() {
+            let state = state.unwrap();
+            let accept = ActionState2::new(ActionState::Accept, None, Some(item.production.clone()));
+            if state.state() == &ActionState::Shift {
+              push_if_not_exists(GrammarConflict::new(Conflict::ShiftReduce, i, s_term_name,
+                state.production().clone(), item.production.clone()));
+              push_alternatives(i, s_term_name, state, accept);
+            } else if state.production() != &Some(item.production.clone()) {
+              push_if_not_exists(GrammarConflict::new(Conflict::ReduceReduce, i, s_term_name,
+                state.production().clone(), item.production.clone()));
+              push_alternatives(i, s_term_name, state, accept);
+            }
+          }
+
+          action_states.set_state(i, s_term_name,
+            ActionState2::new(ActionState::Accept, None, Some(item.production.clone())));
+      }
+    }
+  }
+
+  for (state, name) in errors {
+    action_states.remove_state(state, name);
+  }
+
+  let mut action_states = ActionStatesOpt::from(&action_states);
+  action_states.resolutions = resolutions;
+  action_states.alternatives = alternatives;
+  (action_states, conflicts)
+}
+
+/// LR(0) item as the index of production and the position in it.
+type Item0 = (usize, usize);
+
+/// LR(1) item as the index of production, the position in it and the lookahead.
+type Item1 = (usize, usize, usize);
+
+///
+/// Productions of the grammar indexed by nonterminals for building closures
+///
+struct LR0Automaton<'a> {
+  grammar: &'a Grammar,
+  productions: HashMap<usize, Vec<usize>>,
+  firsts: HashMap<Item0, (Vec<usize>, bool)>
+}
+
+impl<'a> LR0Automaton<'a> {
+  fn new(grammar: &'a Grammar) -> Self {
+    let mut productions: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..grammar.len() {
+      productions.entry(grammar.production(index).name()).or_default().push(index);
+    }
+
+    // first terminals of the symbols following every position, the same as `Grammar::first`
+    // of the symbols with a lookahead appended, and whether the lookahead is added to them
+    let e_term_name = GrammarSymbol::e_term().name();
+    let mut symbol_firsts: HashMap<usize, (Vec<usize>, bool)> = HashMap::new();
+    let mut firsts: HashMap<Item0, (Vec<usize>, bool)> = HashMap::new();
+    for index in 0..grammar.len() {
+      let production = grammar.production(index);
+      for position in 0..production.len() {
+        let mut names: Vec<usize> = vec!();
+        let mut nullable = true;
+        for i in (position + 1)..production.len() {
+          let symbol = production.symbol(i);
+          let (first, has_e_term) = symbol_firsts.entry(symbol.name()).or_insert_with(|| {
+            let first: Vec<usize> = grammar.first(&vec!(symbol.clone())).iter().map(|symbol| symbol.name()).collect();
+            let has_e_term = first.contains(&e_term_name);
+            (first, has_e_term)
+          });
+          for name in first.iter() {
+            if !names.contains(name) {names.push(*name)}
+          }
+          if !*has_e_term {
+            nullable = false;
+            break;
+          }
+        }
+        firsts.insert((index, position), (names, nullable));
+      }
+    }
+
+    Self {
+      grammar,
+      productions,
+      firsts
+    }
+  }
+
+  fn next_symbol(&self, production: usize, position: usize) -> Option<&GrammarSymbol> {
+    let production = self.grammar.production(production);
+    match position < production.len() {
+      true => Some(production.symbol(position)),
+      false => None
+    }
+  }
+
+  fn closure(&self, kernel: &[Item0]) -> Vec<Item0> {
+    let mut result = kernel.to_vec();
+    let mut added: HashSet<usize> = HashSet::new();
+    let mut i = 0;
+    while i < result.len() {
+      let (production, position) = result[i];
+      if let Some(symbol) = self.next_symbol(production, position) {
+        for index in self.productions.get(&symbol.name()).map(|v| &v[..]).unwrap_or(&[]) {
+          if added.insert(*index) {result.push((*index, 0))}
+        }
+      }
+      i += 1;
+    }
+    result
+  }
+
+  fn closure1(&self, kernel: &[Item1]) -> Vec<Item1> {
+    let mut result = kernel.to_vec();
+    let mut added: HashSet<Item1> = kernel.iter().cloned().collect();
+    let mut i = 0;
+    while i < result.len() {
+      let (production, position, term_name) = result[i];
+      if let Some(symbol) = self.next_symbol(production, position) {
+        let (first, nullable) = &self.firsts[&(production, position)];
+        for index in self.productions.get(&symbol.name()).map(|v| &v[..]).unwrap_or(&[]) {
+          for name in first.iter().chain(Some(&term_name).filter(|_| *nullable)) {
+            let item = (*index, 0, *name);
+            if added.insert(item) {result.push(item)}
+          }
         }
       }
+      i += 1;
     }
+    result
+  }
+}
+
+pub trait StatesBuilder {
+  fn build_goto_states(grammar: &Grammar) -> GotoStatesOpt;
 
+  fn build_collection_items<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<Vec<LRItems1>, Error>;
+
+  /// Builds states of the grammar and reports every conflict which is not resolved by precedence.
+  fn analyze(grammar: &Grammar) -> Result<Vec<ConflictReport>, Error> {
+    let goto_states = Self::build_goto_states(grammar);
+    let lalr = Self::build_collection_items(grammar, &goto_states)?;
+    let (_, conflicts) = build_actions(grammar, &goto_states, &lalr, true);
+    let examples = state_examples(grammar, &goto_states);
+    Ok(conflicts.into_iter().map(|conflict| {
+      let items = match lalr.get(conflict.state()) {
+        Some(items) => (0..items.len()).map(|i| items.item(i).clone()).collect(),
+        _ => vec!()
+      };
+      let example = examples.get(&conflict.state()).cloned().unwrap_or_default();
+      ConflictReport::new(conflict, items, example)
+    }).collect())
+  }
+
+  fn build_action_states<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<ActionStatesOpt, Error> {
+    let lalr = Self::build_collection_items(grammar, goto_states)?;
+    let (action_states, conflicts) = build_actions(grammar, goto_states, &lalr, false);
     if conflicts.len() > 0 {
       if cfg!(debug_assertions) {
-        for i in 0..conflicts.len() {
-          let conflict = &conflicts[i];
-          let msg = match conflict.1 {
-            Some(ref prod) =>
-              format!("Grammar conflict {:?}, production1: {}, production2: {}, symbol: {:?}",
-                conflict.0, prod, conflict.2, get_original_name(conflict.3)),
-            _ =>
-              format!("Grammar conflict {:?}, production1: {}, production2: {}, symbol: {:?}",
-                conflict.0, "", conflict.2, get_original_name(conflict.3)),
-          };
-          log(&msg);
+        for conflict in &conflicts {
+          log(&format!("Grammar conflict {}", conflict));
         }
         log(format!("conflicts count: {}", conflicts.len()).as_str());
       }
-      return Err("Grammar is not LALR!".to_string())
+      return Err(Error::Conflicts(conflicts))
     };
 
-    Ok(ActionStatesOpt::from(&action_states))
+    if cfg!(debug_assertions) {
+      for resolution in action_states.resolutions() {
+        log(&format!("Grammar conflict {}", resolution));
+      }
+    }
+    Ok(action_states)
   }
 }
 
+///
+/// LALR(1) builder
+///
+/// States are the LR(0) automaton built with hashed kernels, lookaheads are spontaneously generated
+/// and then propagated between kernel items (the dragon book, 4.7.5).
+///
 pub struct LALRBuilder {
 }
 
@@ -733,6 +1643,395 @@ impl LALRBuilder {
     Self {
     }
   }
+}
+
+impl StatesBuilder for LALRBuilder {
+  fn build_goto_states(grammar: &Grammar) -> GotoStatesOpt {
+    let automaton = LR0Automaton::new(grammar);
+    let mut order: HashMap<usize, usize> = HashMap::new();
+    for (index, symbol) in grammar.symbols().iter().enumerate() {
+      order.entry(symbol.name()).or_insert(index);
+    }
+
+    let mut states = GotoStates::new();
+    let mut kernels: Vec<Vec<Item0>> = vec!(vec!((0, 0)));
+    let mut indexes: HashMap<Vec<Item0>, usize> = HashMap::new();
+    indexes.insert(vec!((0, 0)), 0);
+    let mut i = 0;
+    while i < kernels.len() {
+      let mut gotos: Vec<(usize, Vec<Item0>)> = vec!();
+      for (production, position) in automaton.closure(&kernels[i]) {
+        let name = match automaton.next_symbol(production, position) {
+          Some(symbol) => symbol.name(),
+          _ => continue
+        };
+        match gotos.iter_mut().find(|(name1, _)| *name1 == name) {
+          Some((_, kernel)) => kernel.push((production, position + 1)),
+          _ => gotos.push((name, vec!((production, position + 1))))
+        }
+      }
+      gotos.sort_by_key(|(name, _)| order.get(name).cloned().unwrap_or(usize::MAX));
+      for (name, kernel) in gotos {
+        let mut key = kernel.clone();
+        key.sort_unstable();
+        let j = match indexes.get(&key) {
+          Some(j) => *j,
+          _ => {
+            indexes.insert(key, kernels.len());
+            kernels.push(kernel);
+            kernels.len() - 1
+          }
+        };
+        states.set_state(i, name, j);
+      }
+      i += 1;
+    }
+
+    GotoStatesOpt::from(&states)
+  }
+
+  fn build_collection_items<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<Vec<LRItems1>, Error> {
+    let automaton = LR0Automaton::new(grammar);
+    let marker = usize::MAX;
+
+    // kernels of states in the order of items in the closure of the previous state
+    let mut kernels: Vec<Vec<Item0>> = vec!(vec!((0, 0)));
+    let mut i = 0;
+    while i < kernels.len() {
+      let closure = automaton.closure(&kernels[i]);
+      for (name, j) in goto_states.transitions(i) {
+        if j >= kernels.len() {kernels.resize(j + 1, vec!())}
+        if !kernels[j].is_empty() {continue}
+        kernels[j] = closure.iter().filter(|(production, position)| {
+          automaton.next_symbol(*production, *position).map(|symbol| symbol.name()) == Some(name)
+        }).map(|(production, position)| (*production, position + 1)).collect();
+      }
+      i += 1;
+    }
+    let kernel_indexes: Vec<HashMap<Item0, usize>> = kernels.iter().map(|kernel| {
+      kernel.iter().enumerate().map(|(index, item)| (*item, index)).collect()
+    }).collect();
+
+    // spontaneously generated lookaheads and propagation links of kernel items
+    let mut lookaheads: Vec<Vec<Vec<usize>>> = kernels.iter().map(|kernel| vec!(vec!(); kernel.len())).collect();
+    let mut links: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+    let mut closures: HashMap<Item0, Vec<Item1>> = HashMap::new();
+    lookaheads[0][0].push(GrammarSymbol::s_term().name());
+    for (i, kernel) in kernels.iter().enumerate() {
+      for (k, &item) in kernel.iter().enumerate() {
+        let closure = closures.entry(item).or_insert_with(|| automaton.closure1(&[(item.0, item.1, marker)]));
+        for (production, position, term_name) in closure.iter() {
+          let name = match automaton.next_symbol(*production, *position) {
+            Some(symbol) => symbol.name(),
+            _ => continue
+          };
+          let j = match goto_states.state(i, name) {
+            Some(j) => *j,
+            _ => continue
+          };
+          let t = match kernel_indexes[j].get(&(*production, position + 1)) {
+            Some(t) => *t,
+            _ => return Err(Error::InvalidBinary {
+              message: format!("Goto state {} of state {} has no kernel item of production {}", j, i, production)
+            })
+          };
+          if *term_name == marker {
+            let targets = links.entry((i, k)).or_default();
+            if !targets.contains(&(j, t)) {targets.push((j, t))}
+          } else if !lookaheads[j][t].contains(term_name) {
+            lookaheads[j][t].push(*term_name);
+          }
+        }
+      }
+    }
+
+    let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
+    for (i, kernel_lookaheads) in lookaheads.iter().enumerate() {
+      for (k, lookahead) in kernel_lookaheads.iter().enumerate() {
+        if !lookahead.is_empty() {queue.push_back((i, k))}
+      }
+    }
+    while let Some((i, k)) = queue.pop_front() {
+      let targets = match links.get(&(i, k)) {
+        Some(targets) => targets,
+        _ => continue
+      };
+      for (j, t) in targets {
+        let mut changed = false;
+        for index in 0..lookaheads[i][k].len() {
+          let term_name = lookaheads[i][k][index];
+          if !lookaheads[*j][*t].contains(&term_name) {
+            lookaheads[*j][*t].push(term_name);
+            changed = true;
+          }
+        }
+        if changed {queue.push_back((*j, *t))}
+      }
+    }
+
+    let mut result: Vec<LRItems1> = Vec::with_capacity(kernels.len());
+    for i in 0..kernels.len() {
+      let mut kernel: Vec<Item1> = vec!();
+      for k in 0..kernels[i].len() {
+        let (production, position) = kernels[i][k];
+        for term_name in &lookaheads[i][k] {
+          kernel.push((production, position, *term_name));
+        }
+      }
+      let mut items = LRItems1::new();
+      for (production, position, term_name) in automaton.closure1(&kernel) {
+        items.push_item(LRItem::new(position, grammar.production_clone_rc(production), Some(term_name)));
+      }
+      result.push(items);
+    }
+    Ok(result)
+  }
+}
+
+///
+/// Minimal LR(1) builder
+///
+/// States are built by Pager's practical general method: a goto state is merged with an existing
+/// state of the same LR(0) core only if their lookaheads are weakly compatible, so merging never
+/// introduces conflicts which are absent in the canonical LR(1) collection. Lookaheads of the
+/// resulting states are computed the same way as by `LALRBuilder`.
+///
+#[derive(Default)]
+pub struct MinimalLRBuilder {
+}
+
+impl MinimalLRBuilder {
+  pub fn new() -> Self {
+    Self {
+    }
+  }
+
+  /// Checks Pager's weak compatibility of lookaheads of two states with the same kernel items.
+  fn weakly_compatible(lookaheads1: &[Vec<usize>], lookaheads2: &[Vec<usize>]) -> bool {
+    let intersects = |a: &Vec<usize>, b: &Vec<usize>| a.iter().any(|name| b.contains(name));
+    for i in 0..lookaheads1.len() {
+      for j in (i + 1)..lookaheads1.len() {
+        if !intersects(&lookaheads1[i], &lookaheads2[j]) && !intersects(&lookaheads1[j], &lookaheads2[i]) {continue}
+        if intersects(&lookaheads1[i], &lookaheads1[j]) || intersects(&lookaheads2[i], &lookaheads2[j]) {continue}
+        return false;
+      }
+    }
+    true
+  }
+
+  /// Adds lookaheads to the kernel items of the state, returns whether they are changed.
+  fn merge(lookaheads: &mut [Vec<usize>], added: &[Vec<usize>]) -> bool {
+    let mut changed = false;
+    for (names, added) in lookaheads.iter_mut().zip(added) {
+      for name in added {
+        if !names.contains(name) {
+          names.push(*name);
+          changed = true;
+        }
+      }
+    }
+    changed
+  }
+}
+
+impl StatesBuilder for MinimalLRBuilder {
+  fn build_goto_states(grammar: &Grammar) -> GotoStatesOpt {
+    let automaton = LR0Automaton::new(grammar);
+    let mut order: HashMap<usize, usize> = HashMap::new();
+    for (index, symbol) in grammar.symbols().iter().enumerate() {
+      order.entry(symbol.name()).or_insert(index);
+    }
+
+    let mut states = GotoStates::new();
+    let mut kernels: Vec<Vec<Item0>> = vec!(vec!((0, 0)));
+    let mut lookaheads: Vec<Vec<Vec<usize>>> = vec!(vec!(vec!(GrammarSymbol::s_term().name())));
+    let mut cores: HashMap<Vec<Item0>, Vec<usize>> = HashMap::new();
+    cores.insert(vec!((0, 0)), vec!(0));
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    queue.push_back(0);
+    while let Some(i) = queue.pop_front() {
+      let mut kernel: Vec<Item1> = vec!();
+      for (k, (production, position)) in kernels[i].iter().enumerate() {
+        for term_name in &lookaheads[i][k] {
+          kernel.push((*production, *position, *term_name));
+        }
+      }
+
+      // kernel items of goto states are taken from the LR(0) closure, because the closure with
+      // lookaheads has no items of the nonterminals which derive no terminals
+      let mut gotos: Vec<(usize, Vec<Item0>, Vec<Vec<usize>>)> = vec!();
+      for (production, position) in automaton.closure(&kernels[i]) {
+        let name = match automaton.next_symbol(production, position) {
+          Some(symbol) => symbol.name(),
+          _ => continue
+        };
+        match gotos.iter_mut().find(|(name1, _, _)| *name1 == name) {
+          Some((_, items, names)) => {
+            items.push((production, position + 1));
+            names.push(vec!());
+          },
+          _ => gotos.push((name, vec!((production, position + 1)), vec!(vec!())))
+        }
+      }
+      // lookaheads of the kernel items of goto states
+      for (production, position, term_name) in automaton.closure1(&kernel) {
+        let name = match automaton.next_symbol(production, position) {
+          Some(symbol) => symbol.name(),
+          _ => continue
+        };
+        if let Some((_, items, names)) = gotos.iter_mut().find(|(name1, _, _)| *name1 == name) {
+          if let Some(k) = items.iter().position(|item| *item == (production, position + 1)) {
+            if !names[k].contains(&term_name) {names[k].push(term_name)}
+          }
+        }
+      }
+      gotos.sort_by_key(|(name, _, _)| order.get(name).cloned().unwrap_or(usize::MAX));
+
+      for (name, items, names) in gotos {
+        // lookaheads in the order of kernel items of the existing states with the same core
+        let mut key = items.clone();
+        key.sort_unstable();
+        let align = |kernel: &Vec<Item0>| -> Vec<Vec<usize>> {
+          kernel.iter().map(|item| names[items.iter().position(|item1| item1 == item).unwrap()].clone()).collect()
+        };
+        let j = match states.state(&i, &name) {
+          Some(j) => Some(*j),
+          _ => cores.get(&key).and_then(|indexes| indexes.iter().find(|j| {
+            Self::weakly_compatible(&lookaheads[**j], &align(&kernels[**j]))
+          }).cloned())
+        };
+        match j {
+          Some(j) => {
+            let added = align(&kernels[j]);
+            if Self::merge(&mut lookaheads[j], &added) && !queue.contains(&j) {
+              queue.push_back(j);
+            }
+            states.set_state(i, name, j);
+          },
+          _ => {
+            let j = kernels.len();
+            kernels.push(items);
+            lookaheads.push(names);
+            cores.entry(key).or_default().push(j);
+            queue.push_back(j);
+            states.set_state(i, name, j);
+          }
+        }
+      }
+    }
+
+    GotoStatesOpt::from(&states)
+  }
+
+  fn build_collection_items<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<Vec<LRItems1>, Error> {
+    LALRBuilder::build_collection_items(grammar, goto_states)
+  }
+}
+
+/// Returns the nonterminal which derives itself (A =>+ A), the trees of such grammars are
+/// infinite for some inputs.
+fn cyclic_nonterminal(grammar: &Grammar) -> Option<usize> {
+  let mut nullable: HashSet<usize> = HashSet::new();
+  nullable.insert(GrammarSymbol::e_term().name());
+  loop {
+    let mut changed = false;
+    for index in 0..grammar.len() {
+      let production = grammar.production(index);
+      if nullable.contains(&production.name()) {continue}
+      if (0..production.len()).all(|i| nullable.contains(&production.symbol(i).name())) {
+        nullable.insert(production.name());
+        changed = true;
+      }
+    }
+    if !changed {break}
+  }
+
+  // nonterminals derived alone by the productions whose other symbols are nullable
+  let mut units: HashMap<usize, Vec<usize>> = HashMap::new();
+  for index in 0..grammar.len() {
+    let production = grammar.production(index);
+    for i in 0..production.len() {
+      let symbol = production.symbol(i);
+      if symbol.is_term() {continue}
+      if (0..production.len()).all(|k| k == i || nullable.contains(&production.symbol(k).name())) {
+        units.entry(production.name()).or_default().push(symbol.name());
+      }
+    }
+  }
+
+  for index in 0..grammar.len() {
+    let name = grammar.production(index).name();
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut stack: Vec<usize> = units.get(&name).cloned().unwrap_or_default();
+    while let Some(next) = stack.pop() {
+      if next == name {return Some(name)}
+      if visited.insert(next) {
+        stack.extend(units.get(&next).into_iter().flatten());
+      }
+    }
+  }
+  None
+}
+
+///
+/// GLR builder
+///
+/// States are built the same way as by `LALRBuilder`, the actions which are in conflicts not
+/// resolved by precedence are kept in the table instead of failing the build.
+///
+#[derive(Default)]
+pub struct GLRBuilder {
+}
+
+impl GLRBuilder {
+  pub fn new() -> Self {
+    Self {
+    }
+  }
+}
+
+impl StatesBuilder for GLRBuilder {
+  fn build_goto_states(grammar: &Grammar) -> GotoStatesOpt {
+    LALRBuilder::build_goto_states(grammar)
+  }
+
+  fn build_collection_items<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<Vec<LRItems1>, Error> {
+    LALRBuilder::build_collection_items(grammar, goto_states)
+  }
+
+  fn build_action_states<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<ActionStatesOpt, Error> {
+    if let Some(name) = cyclic_nonterminal(grammar) {
+      return Err(Error::Unsupported {
+        message: format!("GLR parser does not support cyclic grammars, {} derives itself", symbol_name(name))
+      });
+    }
+    let lalr = Self::build_collection_items(grammar, goto_states)?;
+    let (mut action_states, conflicts) = build_actions(grammar, goto_states, &lalr, false);
+    if cfg!(debug_assertions) {
+      for conflict in &conflicts {
+        log(&format!("Grammar conflict {} kept for GLR", conflict));
+      }
+    }
+    action_states.glr = true;
+    Ok(action_states)
+  }
+}
+
+///
+/// LALR(1) builder computing lookaheads by repeated passes over the LR(0) canonical collection
+///
+/// It is much slower than `LALRBuilder` and is kept as the reference implementation for tests
+/// and benchmarks.
+///
+#[derive(Default)]
+pub struct NaiveLALRBuilder {
+}
+
+impl NaiveLALRBuilder {
+  pub fn new() -> Self {
+    Self {
+    }
+  }
 
   fn make_lr_items1(item: LRItem) -> LRItems1 {
     let mut items = LRItems1::new();
@@ -741,12 +2040,12 @@ impl LALRBuilder {
   }
 }
 
-impl StatesBuilder for LALRBuilder {
+impl StatesBuilder for NaiveLALRBuilder {
   fn build_goto_states(grammar: &Grammar) -> GotoStatesOpt {
     build_goto_states::<LRItems0>(grammar)
   }
 
-  fn build_collection_items<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Vec<LRItems1> {
+  fn build_collection_items<'a>(grammar: &'a Grammar, goto_states: &'a GotoStatesOpt) -> Result<Vec<LRItems1>, Error> {
     let mut kernels : Vec<LRItems0> = vec![];
     let canonical = LRItems0::canonical(grammar);
     for index in 0..canonical.len() {
@@ -825,7 +2124,7 @@ impl StatesBuilder for LALRBuilder {
       let items = &lalr_kernels[i];
       result.push(items.closure(grammar));
     }
-    result
+    Ok(result)
   }
 }
 
@@ -844,7 +2143,7 @@ impl StatesBuilder for LRBuilder {
     build_goto_states::<LRItems1>(grammar)
   }
 
-  fn build_collection_items<'a>(grammar: &'a Grammar, _goto_states: &'a GotoStatesOpt) -> Vec<LRItems1> {
-    LRItems1::canonical(grammar)
+  fn build_collection_items<'a>(grammar: &'a Grammar, _goto_states: &'a GotoStatesOpt) -> Result<Vec<LRItems1>, Error> {
+    Ok(LRItems1::canonical(grammar))
   }
 }
\ No newline at end of file
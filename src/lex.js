@@ -1,3 +1,3 @@
-exports.do_lex_action = (func, context, get, set, set_name, set_name_from_hash, pass) => {
-  func.call(context, get, set, set_name, set_name_from_hash, pass);
+exports.do_lex_action = (func, context, get, set, set_name, set_name_from_hash, pass, read_int, read_blob, begin, push_mode, pop_mode) => {
+  func.call(context, get, set, set_name, set_name_from_hash, pass, read_int, read_blob, begin, push_mode, pop_mode);
 }
\ No newline at end of file
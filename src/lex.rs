@@ -1,26 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::rc::Rc;
 use std::fmt::Debug;
-use js_sys::Function;
 use regex::Regex;
-use wasm_bindgen::prelude::{JsValue, wasm_bindgen};
 
 use super::utils::*;
+use super::action::*;
+use super::error::*;
+
+/// Name of the mode of the lexer in which the rules without modes are recognized.
+pub const INITIAL_MODE: &str = "INITIAL";
 
 struct LexRuleIntl {
   expression: String,
-  action: Option<Function>,
+  action: Option<String>,
   define: bool,
-  position: usize
+  position: usize,
+  line: usize,
+  column: usize,
+  modes: Option<Vec<String>>
 }
 
 impl LexRuleIntl {
-  fn new(expression: &str, action: Option<Function>, define: bool, position: usize) -> Self {
+  fn new(expression: &str, action: Option<String>, define: bool, position: usize, line: usize, column: usize) -> Self {
     LexRuleIntl {
       expression: String::from(expression),
       action,
       define,
-      position
+      position,
+      line,
+      column,
+      modes: None
     }
   }
 }
@@ -29,19 +39,26 @@ impl LexRuleIntl {
 pub struct LexRule {
   name: usize,
   expression: Regex,
-  action: Option<Function>,
+  action: Option<String>,
   define: bool,
-  position: usize
+  position: usize,
+  line: usize,
+  column: usize,
+  modes: Vec<usize>
 }
 
 impl LexRule {
-  pub fn new(name: usize, expression: Regex, action: Option<Function>, define: bool, position: usize) -> Self {
+  pub fn new(name: usize, expression: Regex, action: Option<String>, define: bool, position: usize, line: usize,
+    column: usize) -> Self {
     LexRule {
       name,
       expression,
       action,
       define,
-      position
+      position,
+      line,
+      column,
+      modes: vec!(hash(INITIAL_MODE))
     }
   }
 
@@ -53,13 +70,186 @@ impl LexRule {
     &self.expression
   }
 
-  pub fn action(&self) -> &Option<Function> {
+  pub fn action(&self) -> &Option<String> {
     &self.action
   }
 
   pub fn define(&self) -> bool {
     self.define
   }
+
+  /// Number of the line (starting from 1) in the text of regular definitions.
+  pub fn line(&self) -> usize {
+    self.line
+  }
+
+  /// Position (starting from 1) of the expression in the line.
+  pub fn column(&self) -> usize {
+    self.column
+  }
+
+  /// Modes of the lexer in which the rule is recognized.
+  pub fn modes(&self) -> &Vec<usize> {
+    &self.modes
+  }
+}
+
+/// Format of the integer read by the lexer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntFormat {
+  U8,
+  U16BE,
+  U16LE,
+  U32BE,
+  U32LE,
+  U64BE,
+  U64LE,
+  /// Variable byte integer of MQTT: 7 bits in every byte from the least significant, the high
+  /// bit is set if the next byte follows, at most 4 bytes.
+  VarInt
+}
+
+impl IntFormat {
+  /// Gets the format by name: `u8`, `u16be`, `u16le`, `u32be`, `u32le`, `u64be`, `u64le` or `varint`.
+  pub fn from_name(name: &str) -> Option<Self> {
+    let format = match name {
+      "u8" => IntFormat::U8,
+      "u16be" => IntFormat::U16BE,
+      "u16le" => IntFormat::U16LE,
+      "u32be" => IntFormat::U32BE,
+      "u32le" => IntFormat::U32LE,
+      "u64be" => IntFormat::U64BE,
+      "u64le" => IntFormat::U64LE,
+      "varint" => IntFormat::VarInt,
+      _ => return None
+    };
+    Some(format)
+  }
+
+  /// Decodes the integer, returns `None` if more bytes are needed.
+  pub fn decode(&self, codes: &[u8]) -> Result<Option<u64>, String> {
+    let (width, big_endian) = match self {
+      IntFormat::U8 => (1, true),
+      IntFormat::U16BE => (2, true),
+      IntFormat::U16LE => (2, false),
+      IntFormat::U32BE => (4, true),
+      IntFormat::U32LE => (4, false),
+      IntFormat::U64BE => (8, true),
+      IntFormat::U64LE => (8, false),
+      IntFormat::VarInt => {
+        let mut value = 0u64;
+        for (i, code) in codes.iter().enumerate() {
+          value |= ((code & 0x7f) as u64) << (7 * i);
+          if code & 0x80 == 0 {return Ok(Some(value))}
+          if i == 3 {return Err("Invalid variable byte integer".to_string())}
+        }
+        return Ok(None);
+      }
+    };
+    if codes.len() < width {
+      return Ok(None);
+    }
+    let fold = |value: u64, code: &u8| (value << 8) | *code as u64;
+    let value = match big_endian {
+      true => codes[..width].iter().fold(0, fold),
+      _ => codes[..width].iter().rev().fold(0, fold)
+    };
+    Ok(Some(value))
+  }
+}
+
+/// Token `name` read after the current token as the integer of the format, or as the bytes
+/// prefixed by their length if the flag is set.
+pub type ReadInt = (usize, IntFormat, bool);
+
+/// Change of the mode of the lexer requested by the action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModeAction {
+  /// Switches to the mode.
+  Begin(usize),
+  /// Saves the current mode on the stack and switches to the mode.
+  Push(usize),
+  /// Switches to the mode saved on the stack.
+  Pop
+}
+
+///
+/// LexModes
+///
+/// Current mode of the lexer and the stack of the modes saved by `push_mode`.
+///
+#[derive(Debug, Clone)]
+pub struct LexModes {
+  mode: usize,
+  stack: Vec<usize>
+}
+
+impl LexModes {
+  pub fn new() -> Self {
+    Self {
+      mode: hash(INITIAL_MODE),
+      stack: vec!()
+    }
+  }
+
+  pub fn mode(&self) -> usize {
+    self.mode
+  }
+
+  pub fn reset(&mut self) {
+    self.mode = hash(INITIAL_MODE);
+    self.stack.clear();
+  }
+
+  pub fn apply(&mut self, action: &ModeAction) -> Result<(), Error> {
+    match action {
+      ModeAction::Begin(mode) => self.mode = *mode,
+      ModeAction::Push(mode) => {
+        self.stack.push(self.mode);
+        self.mode = *mode;
+      },
+      ModeAction::Pop => match self.stack.pop() {
+        Some(mode) => self.mode = mode,
+        _ => return Err(Error::Action {message: "Stack of lexer modes is empty".to_string()})
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Default for LexModes {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+///
+/// ValidTerminals
+///
+/// Terminals which the parser accepts in the current state. The tokens which are not
+/// terminals of the grammar (skipped or renamed by actions) are always valid.
+///
+#[derive(Debug)]
+pub struct ValidTerminals {
+  valid: HashSet<usize>,
+  terminals: Rc<HashSet<usize>>
+}
+
+impl ValidTerminals {
+  pub fn new(valid: HashSet<usize>, terminals: Rc<HashSet<usize>>) -> Self {
+    Self {
+      valid,
+      terminals
+    }
+  }
+
+  pub fn valid(&self) -> &HashSet<usize> {
+    &self.valid
+  }
+
+  pub fn contains(&self, tkn_name: usize) -> bool {
+    self.valid.contains(&tkn_name) || !self.terminals.contains(&tkn_name)
+  }
 }
 
 pub trait LexBase {
@@ -81,8 +271,27 @@ pub trait LexBase {
     unimplemented!();
   }
 
+  /// Reads the next token up to the delimiter, the delimiter is skipped.
   #[allow(unused_variables)]
-  fn set_read_to_code(&mut self, tkn_name: usize, stop_code: u8) {
+  fn set_read_to_codes(&mut self, tkn_name: usize, stop_codes: Vec<u8>) {
+    unimplemented!();
+  }
+
+  /// Reads the next token as the integer, its value is the decimal text of the integer.
+  #[allow(unused_variables)]
+  fn set_read_int(&mut self, tkn_name: usize, format: IntFormat) {
+    unimplemented!();
+  }
+
+  /// Reads the length of the next token as the integer, the value of the token is the bytes of the length.
+  #[allow(unused_variables)]
+  fn set_read_blob(&mut self, tkn_name: usize, format: IntFormat) {
+    unimplemented!();
+  }
+
+  /// Marks the end of the data of the message: the last token is finished and the end of
+  /// the message follows it.
+  fn set_end_of_data(&mut self) {
     unimplemented!();
   }
 
@@ -101,12 +310,113 @@ pub trait LexBase {
     unimplemented!();
   }
 
+  /// Byte offset in the input of the last recognized token.
+  fn token_offset(&self) -> usize {
+    unimplemented!();
+  }
+
+  /// Changes the mode of the lexer, the rules of the mode are used from the next token.
   #[allow(unused_variables)]
-  fn set_on_tkn_data(&mut self, on_tkn_data: Option<Function>) {
+  fn change_mode(&mut self, action: &ModeAction) -> Result<(), Error> {
     unimplemented!();
   }
 
-  fn get_token(&mut self, exec_context: &JsValue) -> Result<Option<Token>, JsValue>;
+  /// Sets the terminals which the parser accepts next, the longest of the valid tokens is
+  /// preferred to the other ones. `None` recognizes the tokens by the rules only.
+  #[allow(unused_variables)]
+  fn set_valid_terminals(&mut self, valid_terminals: Option<Rc<ValidTerminals>>) {
+  }
+
+  fn get_token(&mut self, actions: &mut dyn LexActions) -> Result<Option<Token>, Error>;
+}
+
+struct LexContext<'a> {
+  value: &'a [u8],
+  value_changed: Option<Vec<u8>>,
+  name_changed: Option<usize>,
+  name_changed1: Option<usize>,
+  is_pass: bool,
+  read_int: Option<ReadInt>,
+  modes: Vec<ModeAction>
+}
+
+impl<'a> LexActionContext for LexContext<'a> {
+  fn get(&self) -> &[u8] {
+    self.value
+  }
+
+  fn set(&mut self, value: Vec<u8>) {
+    self.value_changed = Some(value);
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name_changed = Some(hash(name));
+  }
+
+  fn set_name_from_hash(&mut self, name: usize) {
+    self.name_changed1 = Some(name);
+  }
+
+  fn pass(&mut self) {
+    self.is_pass = true;
+  }
+
+  fn read_int(&mut self, tkn_name: &str, format: IntFormat) {
+    self.read_int = Some((hash(tkn_name), format, false));
+  }
+
+  fn read_blob(&mut self, tkn_name: &str, format: IntFormat) {
+    self.read_int = Some((hash(tkn_name), format, true));
+  }
+
+  fn begin(&mut self, mode: &str) {
+    self.modes.push(ModeAction::Begin(hash(mode)));
+  }
+
+  fn push_mode(&mut self, mode: &str) {
+    self.modes.push(ModeAction::Push(hash(mode)));
+  }
+
+  fn pop_mode(&mut self) {
+    self.modes.push(ModeAction::Pop);
+  }
+}
+
+/// The new token, the pass flag, the token read after it as the integer and the changes of the mode.
+pub type LexActionResult = (Token, bool, Option<ReadInt>, Vec<ModeAction>);
+
+/// Executes action of the recognized token, returns the new token, the pass flag, the
+/// token read after it as the integer and the changes of the mode of the lexer.
+pub fn exec_lex_action(actions: &mut dyn LexActions, tkn_name: usize, tkn_value: Vec<u8>,
+  action: Option<&str>) -> Result<LexActionResult, Error> {
+  let mut ctx = LexContext {
+    value: &tkn_value,
+    value_changed: None,
+    name_changed: None,
+    name_changed1: None,
+    is_pass: false,
+    read_int: None,
+    modes: vec!()
+  };
+  actions.lex_action(tkn_name, action, &mut ctx).map_err(|message| Error::Action {message})?;
+
+  let tkn_name = match ctx.name_changed1 {
+    Some(tkn_name_changed1) => tkn_name_changed1,
+    _ => match ctx.name_changed {
+      Some(tkn_name_changed) => tkn_name_changed,
+      _ => tkn_name
+    }
+  };
+  let is_pass = ctx.is_pass;
+  let read_int = ctx.read_int;
+  let modes = ctx.modes;
+
+  let tkn_value = match ctx.value_changed {
+    Some(tkn_value_changed) => tkn_value_changed,
+    _ => tkn_value
+  };
+
+  Ok((Token::new(tkn_name, tkn_value), is_pass, read_int, modes))
 }
 
 impl Debug for dyn LexBase {
@@ -118,34 +428,64 @@ impl Debug for dyn LexBase {
 pub struct Lex {
   text: String,
   cur_position: usize,
+  tkn_position: usize,
   rules: Vec<LexRule>,
   reg_exp: String,
-  error: bool
+  error: bool,
+  modes: LexModes,
+  valid_terminals: Option<Rc<ValidTerminals>>
 }
 
 impl LexBase for Lex {
   fn set_text(&mut self, value: String) {
     self.text = value;
     self.cur_position = 0;
+    self.tkn_position = 0;
+    self.modes.reset();
+  }
+
+  fn token_offset(&self) -> usize {
+    self.tkn_position
+  }
+
+  fn change_mode(&mut self, action: &ModeAction) -> Result<(), Error> {
+    if let ModeAction::Begin(mode) | ModeAction::Push(mode) = action {
+      if !self.rules.iter().any(|rule| rule.modes.contains(mode)) {
+        return Err(Error::Action {message: format!("Unknown lexer mode '{}'", get_original_name(*mode).unwrap_or_default())});
+      }
+    }
+    self.modes.apply(action)
   }
 
-  fn get_token(&mut self, exec_context: &JsValue) -> Result<Option<Token>, JsValue> {
+  fn set_valid_terminals(&mut self, valid_terminals: Option<Rc<ValidTerminals>>) {
+    self.valid_terminals = valid_terminals;
+  }
+
+  fn get_token(&mut self, actions: &mut dyn LexActions) -> Result<Option<Token>, Error> {
     self.error = false;
     let mut is_pass = false;
-    let mut tkn: Option<Token> = None;
+    let mut tkn: Option<Token>;
     loop {
+      tkn = None;
       if self.cur_position < self.text.len() {
         let cur_text = &self.text[self.cur_position..];
         let mut tkn_name: Option<usize> = None;
         let mut tkn_value: Option<Vec<u8>> = None;
         let mut pos: Option<usize> = None;
+        let mut valid_pos: Option<(usize, usize)> = None;
         for i in 0..self.rules.len() {
           let rule = &self.rules[i];
+          if !rule.modes.contains(&self.modes.mode()) {continue}
           let result = rule.expression.find(cur_text);
           if result == None {continue};
           let result = result.unwrap();
           if result.start() > 0 {continue};
           let res_len = result.end() - result.start();
+          if let Some(ref valid_terminals) = self.valid_terminals {
+            if valid_terminals.contains(rule.name) && !matches!(valid_pos, Some((_, len)) if len >= res_len) {
+              valid_pos = Some((i, res_len));
+            }
+          }
           match tkn_value {
             Some(ref val) if res_len > val.len() => {
               tkn_name = Some(rule.name.clone());
@@ -160,57 +500,32 @@ impl LexBase for Lex {
             _ => {}
           }
         }
+        // the longest of the tokens expected by the parser is preferred
+        if let Some((i, len)) = valid_pos {
+          tkn_name = Some(self.rules[i].name);
+          tkn_value = Some(cur_text.as_bytes()[..len].to_vec());
+          pos = Some(i);
+        }
         if tkn_name.is_some() {
           let tkn_name = tkn_name.unwrap();
           let tkn_value = tkn_value.unwrap();
-          let mut tkn_name_changed: Option<usize> = None;
-          let mut tkn_name_changed1: Option<usize> = None;
-          let mut tkn_value_changed: Option<Vec<u8>> = None;
+          self.tkn_position = self.cur_position;
           self.cur_position += tkn_value.len();
-          let action = self.rules[pos.unwrap()].action();
-          if let Some(ref func) = action {
-  
-            let get = || -> Vec<u8> {
-              tkn_value.clone()
-            };
-  
-            let mut set = |value: Vec<u8>| {
-              tkn_value_changed = Some(value);
-            };
-  
-            let mut set_name = |name: String| {
-              tkn_name_changed = Some(hash(&name));
-            };
-
-            let mut set_name_from_hash = |hash_name: usize| {
-              tkn_name_changed1 = Some(hash_name);
-            };
-
-            let mut pass = || {
-              is_pass = true;
-            };
-  
-            let _ = do_lex_action(&func, exec_context, &get, &mut set, &mut set_name, &mut set_name_from_hash, &mut pass);
-  
-            let tkn_name = match tkn_name_changed1 {
-              Some(tkn_name_changed1) => tkn_name_changed1,
-              _ => match tkn_name_changed {
-                Some(tkn_name_changed) => tkn_name_changed,
-                _ => tkn_name
-              }
-            };
-  
-            let tkn_value = match tkn_value_changed {
-              Some(tkn_value_changed) => tkn_value_changed,
-              _ => tkn_value
-            };
-  
-            tkn = Some(Token::new(tkn_name, tkn_value));
-          } else {
-            tkn = Some(Token::new(tkn_name, tkn_value));
+          let action = self.rules[pos.unwrap()].action().as_deref();
+          let (token, pass, read_int, modes) = exec_lex_action(actions, tkn_name, tkn_value, action)?;
+          if read_int.is_some() {
+            return Err(Error::Action {message: "Reading of integers is supported only by StreamLex".to_string()});
           }
+          for mode in &modes {
+            self.change_mode(mode)?;
+          }
+          tkn = Some(token);
+          is_pass = pass;
         }
         self.error = tkn == None;
+        if self.error {
+          return Err(Error::LexNoMatch {offset: self.cur_position});
+        }
       }
   
       if !is_pass {break}
@@ -226,9 +541,12 @@ impl Lex {
     Lex {
       text,
       cur_position: 0,
+      tkn_position: 0,
       rules: Vec::new(),
       reg_exp: String::new(),
-      error: false
+      error: false,
+      modes: LexModes::new(),
+      valid_terminals: None
     }
   }
 
@@ -240,21 +558,53 @@ impl Lex {
     &self.rules
   }
 
-  pub fn set_regular_definition_text(&mut self, value: String) {
+  /// Position in the text of the last recognized token.
+  pub fn token_position(&self) -> usize {
+    self.tkn_position
+  }
+
+  /// Converts position in the text to the line and column (starting from 1).
+  pub fn line_column(&self, position: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for ch in self.text[..position.min(self.text.len())].chars() {
+      if ch == '\n' {
+        line += 1;
+        column = 1;
+      } else {
+        column += 1;
+      }
+    }
+    (line, column)
+  }
+
+  pub fn set_regular_definition_text(&mut self, value: String) -> Result<(), Error> {
     self.cur_position = 0;
+    self.tkn_position = 0;
     self.reg_exp = String::new();
     self.rules.clear();
 
-    if value == "" {return;}
+    if value.is_empty() {return Ok(());}
 
     let mut rules: HashMap<usize, LexRuleIntl> = HashMap::new();
 
     let rg_name = Regex::new(r"((_|[A-Za-z])(_|[A-Za-z]|[0-9])*)|('\S+')").unwrap();
     let rg_expression = Regex::new(r"\S+").unwrap();
-    let rg_def = Regex::new(r"DEF").unwrap();
+    let rg_modes = Regex::new(r"^\s*<([^>\s]+)>").unwrap();
+    let rg_flag = Regex::new(r"^\s*(DEF|ICASE)(\s|$)").unwrap();
     let rg_action = Regex::new(r"\{(\S|\s)*\}").unwrap();
 
-    for item in value.split("\n") {
+    for (line, item) in value.split("\n").enumerate() {
+      let line = line + 1;
+      let text = item;
+      // position (starting from 1) in the line of the byte index in the rest of the line
+      let column = |item: &str, index: usize| text[..text.len() - item.len() + index].chars().count() + 1;
+      // modes of the rule in angle brackets before the name
+      let (modes, item) = match rg_modes.captures(item) {
+        Some(modes) => (Some(modes[1].split(',').map(|mode| mode.to_string()).collect::<Vec<String>>()),
+          &item[modes.get(0).unwrap().end()..]),
+        _ => (None, item)
+      };
       let result = rg_name.find(item);
       if result == None {continue};
       let result = result.unwrap();
@@ -262,32 +612,50 @@ impl Lex {
         "\'" => &item[result.start()+1..result.end()-1],
         _ => &item[result.start()..result.end()]
       };
+      let original_name = name;
       let name = hash(&(name.to_string()));
-      if rules.contains_key(&name) {panic!("Rule '{}' already defined", name)};
+      if rules.contains_key(&name) {
+        return Err(Error::RegExpSyntax {line, column: column(item, result.start()),
+          message: format!("Rule '{}' already defined", original_name)});
+      };
       let mut index = result.end() + 1;
-      let result = rg_expression.find(&item[index..]);
-      let result = result.expect(format!("Expression for '{}' is not defined", name).as_str());
+      let result = match item.get(index..) {
+        Some(expression) => rg_expression.find(expression),
+        _ => None
+      };
+      let result = match result {
+        Some(result) => result,
+        _ => return Err(Error::RegExpSyntax {line, column: column(item, item.len()),
+          message: format!("Expression for '{}' is not defined", original_name)})
+      };
       index += result.start();
+      let expression_column = column(item, index);
       let len = result.end() - result.start() + 1;
-      let expression = &item[index..index + len - 1];
-      index += len;
+      let mut expression = item[index..index + len - 1].to_string();
+      index += len - 1;
       let mut define = false;
-      if index < item.len() - 1 {
-        let result = rg_def.find(&item[index..]);
-        if result.is_some() {
-          define = true;
-          index += result.unwrap().end() + 1;
+      while let Some(flag) = item.get(index..).and_then(|flags| rg_flag.captures(flags)) {
+        match &flag[1] {
+          "DEF" => define = true,
+          // the case-insensitive rule is the inline group, so it remains such in the definitions
+          _ => expression = format!("(?i:{})", expression)
         }
+        index += flag.get(1).unwrap().end();
       }
+      index += 1;
       let mut result = None;
       if index < item.len() - 1 {
         result = rg_action.find(&item[index..]);
       }
-      let rule = LexRuleIntl::new(expression,
+      let mut rule = LexRuleIntl::new(&expression,
         match result {
-          Some(action) => Some(Function::new_with_args("get, set, set_name, set_name_from_hash, pass", action.as_str())),
+          Some(action) => {
+            let action = action.as_str();
+            Some(action[1..action.len()-1].to_string())
+          },
           None => None
-        }, define, rules.len());
+        }, define, rules.len(), line, expression_column);
+      rule.modes = modes;
       rules.insert(name, rule);
     }
 
@@ -317,36 +685,55 @@ impl Lex {
       if expressions.len() == 0 {break};
       for (name, expression) in &expressions {
         let old_rule = rules.get(name).unwrap();
-        let new_rule = LexRuleIntl::new(expression, old_rule.action.clone(), old_rule.define, old_rule.position);
+        let mut new_rule = LexRuleIntl::new(expression, old_rule.action.clone(), old_rule.define, old_rule.position,
+          old_rule.line, old_rule.column);
+        new_rule.modes = old_rule.modes.clone();
         rules.insert(*name, new_rule);
       }
     }
+    // the rules of the mode '*' are recognized in all modes in the order of definition
+    let mut all_modes = vec!(hash(INITIAL_MODE));
+    let mut ordered_rules: Vec<&LexRuleIntl> = rules.values().collect();
+    ordered_rules.sort_by_key(|rule| rule.position);
+    for rule in ordered_rules {
+      for mode in rule.modes.iter().flatten() {
+        let mode = hash(mode);
+        if mode != hash("*") && !all_modes.contains(&mode) {
+          all_modes.push(mode);
+        }
+      }
+    }
     // creates regular expressions
     for (name, rule) in rules {
       if rule.define {continue};
+      let modes = match rule.modes {
+        Some(ref modes) if modes.iter().any(|mode| mode == "*") => all_modes.clone(),
+        Some(ref modes) => modes.iter().map(|mode| hash(mode)).collect(),
+        _ => vec!(hash(INITIAL_MODE))
+      };
+      let expression = match Regex::new(&rule.expression) {
+        Ok(expression) => expression,
+        Err(err) => return Err(Error::RegExpSyntax {line: rule.line, column: rule.column, message: err.to_string()})
+      };
       self.rules.push(LexRule {
         name,
-        expression: Regex::new(&rule.expression).unwrap(),
+        expression,
         action: rule.action.clone(),
         define: rule.define,
-        position: rule.position
+        position: rule.position,
+        line: rule.line,
+        column: rule.column,
+        modes
       });
     }
     //sort rules
     self.rules.sort_by_key(|rule| rule.position);
 
     self.reg_exp = value;
+    Ok(())
   }
 
   pub fn error(&self) -> bool {
     self.error
   }
 }
-
-#[wasm_bindgen(module = "/src/lex.js")]
-extern "C" {
-  #[wasm_bindgen(catch)]
-  pub fn do_lex_action(action: &Function, context: &JsValue,
-    get: &dyn Fn() -> Vec<u8>, set: &mut dyn FnMut(Vec<u8>), set_name: &mut dyn FnMut(String), set_name_from_hash: &mut dyn FnMut(usize), pass: &mut dyn FnMut()
-  ) -> Result<(), JsValue>;
-}
\ No newline at end of file
@@ -1,8 +1,15 @@
 pub mod utils;
+pub mod error;
+pub mod action;
+pub mod rust_action;
 pub mod lex;
 pub mod lalr;
 pub mod parser;
 pub mod dfa_grammar;
 pub mod dfa;
 pub mod stream_lex;
+pub mod framing;
+pub mod binary;
+pub mod codegen;
+#[cfg(feature = "wasm")]
 pub mod executor;
\ No newline at end of file
@@ -1,3 +1,3 @@
-exports.do_parser_action = (func, context, bind, id, lookup, get, set, set_val, set_name, set_name_from_hash, push_after) => {
-  func.call(context, bind, id, lookup, get, set, set_val, set_name, set_name_from_hash, push_after);
+exports.do_parser_action = (func, context, bind, id, lookup, get, set, set_val, set_name, set_name_from_hash, push_after, begin, push_mode, pop_mode) => {
+  func.call(context, bind, id, lookup, get, set, set_val, set_name, set_name_from_hash, push_after, begin, push_mode, pop_mode);
 }
\ No newline at end of file
@@ -1,16 +1,34 @@
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use js_sys::{Function};
-use wasm_bindgen::prelude::{JsValue, wasm_bindgen};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
 
 use super::utils::*;
+use super::action::*;
+use super::error::*;
 use super::lex::*;
 use super::lalr::*;
+use super::rust_action::{RustStatement, RustExpr};
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub enum ParserType {
   LR1,
-  LALR1
+  LALR1,
+  MinimalLR1,
+  GLR
+}
+
+/// Builds states of the grammar and returns report of every its conflict.
+pub fn analyze_grammar(grammar: String, parser_type: ParserType) -> Result<Vec<ConflictReport>, Error> {
+  let grammar = GrammarBuilder::from_text(grammar)?;
+  match parser_type {
+    ParserType::LALR1 => LALRBuilder::analyze(&grammar),
+    ParserType::LR1 => LRBuilder::analyze(&grammar),
+    ParserType::MinimalLR1 => MinimalLRBuilder::analyze(&grammar),
+    ParserType::GLR => GLRBuilder::analyze(&grammar)
+  }
 }
 
 #[derive(PartialEq, Debug)]
@@ -38,25 +56,157 @@ impl StackItem {
 
 pub struct Stack (Vec<StackItem>);
 
+struct ReduceContext<'a> {
+  stack: &'a [StackItem],
+  cur_symbol: &'a GrammarSymbol,
+  new_symbol: &'a mut GrammarSymbol,
+  new_symbol_name: Option<usize>,
+  new_symbol_name1: Option<usize>,
+  new_symbol_val: Option<Vec<u8>>,
+  bind_id: Option<usize>,
+  nexts: Vec<Next>,
+  modes: Vec<ModeAction>,
+  error: Option<String>
+}
+
+impl<'a> ReduceContext<'a> {
+  fn item(&self, index: usize) -> Option<&StackItem> {
+    match self.stack.len().checked_sub(index + 1) {
+      Some(idx) => self.stack.get(idx),
+      None => None
+    }
+  }
+}
+
+impl<'a> ParserActionContext for ReduceContext<'a> {
+  fn bind(&mut self, id: usize) {
+    self.bind_id = Some(id);
+  }
+
+  fn id(&self, index: usize) -> Option<usize> {
+    match self.item(index) {
+      Some(item) => item.bind_id,
+      None => None
+    }
+  }
+
+  fn lookup(&self) -> Option<&[u8]> {
+    self.cur_symbol.value().as_deref()
+  }
+
+  fn get(&self, index: usize) -> Option<&[u8]> {
+    match self.item(index) {
+      Some(item) => match item.symbol {
+        Some(ref symbol) => symbol.value().as_deref(),
+        None => None
+      },
+      None => None
+    }
+  }
+
+  fn set(&mut self, index: usize) {
+    let value = match self.item(index) {
+      Some(item) => match item.symbol {
+        Some(ref symbol) => symbol.value().clone(),
+        None => None
+      },
+      None => None
+    };
+    self.new_symbol.set_value(value);
+  }
+
+  fn set_val(&mut self, value: Vec<u8>) {
+    self.new_symbol_val = Some(value);
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.new_symbol_name = Some(hash(name));
+  }
+
+  fn set_name_from_hash(&mut self, name: usize) {
+    self.new_symbol_name1 = Some(name);
+  }
+
+  fn push_after(&mut self, name: &str, insert_name: Option<&str>,
+    insert_value: Option<Vec<u8>>, size: Option<usize>, stop_codes: Option<Vec<u8>>) {
+    let insert_name = match insert_name {
+      Some(insert_name) => Some(hash(insert_name)),
+      None => None
+    };
+    let mut set_params_count = 0;
+    if insert_value.is_some() {set_params_count += 1;}
+    if size.is_some() {set_params_count += 1;}
+    if stop_codes.is_some() {set_params_count += 1;}
+    if set_params_count > 1 {
+      self.error = Some("Only one parameter ('insert_value', 'size' or 'stop_codes') can be set at a time".to_string());
+      return;
+    }
+    if stop_codes.as_ref().map(|stop_codes| stop_codes.is_empty()).unwrap_or(false) {
+      self.error = Some("Parameter 'stop_codes' is empty".to_string());
+      return;
+    }
+    self.nexts.push(Next::new(hash(name), insert_name, insert_value, size, stop_codes));
+  }
+
+  fn read_bytes(&mut self, name: &str, insert_name: &str, size: usize) {
+    let mut next = Next::new(hash(name), Some(hash(insert_name)), None, Some(size), None);
+    next.keep_value = true;
+    self.nexts.push(next);
+  }
+
+  fn begin(&mut self, mode: &str) {
+    self.modes.push(ModeAction::Begin(hash(mode)));
+  }
+
+  fn push_mode(&mut self, mode: &str) {
+    self.modes.push(ModeAction::Push(hash(mode)));
+  }
+
+  fn pop_mode(&mut self) {
+    self.modes.push(ModeAction::Pop);
+  }
+}
+
+/// Lexer actions which collect the data of the token read by size instead of passing it.
+struct ReadBytesActions<'a> {
+  actions: &'a mut dyn LexActions,
+  value: &'a mut Vec<u8>
+}
+
+impl<'a> LexActions for ReadBytesActions<'a> {
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    self.actions.lex_action(tkn_name, action, ctx)
+  }
+
+  fn tkn_data(&mut self, _tkn_name: usize, data: &[u8], _end: bool) -> Result<(), String> {
+    self.value.extend_from_slice(data);
+    Ok(())
+  }
+}
+
 #[derive(Debug, Clone)]
 struct Next {
   name: usize,
   insert_name: Option<usize>,
   insert_value: Option<Vec<u8>>,
   size: Option<usize>,
-  stop_code: Option<u8>,
+  stop_codes: Option<Vec<u8>>,
+  /// Data of the token read by size is the value of the token.
+  keep_value: bool,
   last_name: Option<usize>
 }
 
 impl Next {
   pub fn new(name: usize, insert_name: Option<usize>, insert_value: Option<Vec<u8>>,
-    size: Option<usize>, stop_code: Option<u8>) -> Self {
+    size: Option<usize>, stop_codes: Option<Vec<u8>>) -> Self {
     Self {
       name,
       insert_name,
       insert_value,
       size,
-      stop_code,
+      stop_codes,
+      keep_value: false,
       last_name: None
     }
   }
@@ -74,6 +224,195 @@ impl Next {
   }
 }
 
+/// Result of the LR or the GLR part of parsing.
+enum ParseStep {
+  Done(ParseResult),
+  /// The symbol has conflicting actions, the stack is moved to the graph-structured stack.
+  Split(GrammarSymbol),
+  /// The single stack is left, it is moved back to the stack of LR parser.
+  Join
+}
+
+///
+/// Parse tree
+///
+/// Tree of grammar symbols built by the GLR parser, leaves are the shifted terminals.
+///
+#[derive(Debug, Clone)]
+pub struct ParseTree {
+  symbol: GrammarSymbol,
+  production: Option<Rc<GrammarProduction>>,
+  children: Vec<ParseTree>
+}
+
+impl ParseTree {
+  pub fn symbol(&self) -> &GrammarSymbol {
+    &self.symbol
+  }
+
+  /// Reduced production, `None` for the leaves.
+  pub fn production(&self) -> &Option<Rc<GrammarProduction>> {
+    &self.production
+  }
+
+  pub fn children(&self) -> &Vec<ParseTree> {
+    &self.children
+  }
+}
+
+/// Which of the parse trees of the ambiguous input are delivered by the GLR parser.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseTrees {
+  /// Actions of the productions are executed for the first accepted tree.
+  First,
+  /// Every tree is passed to `ParserActions::parse_trees`, actions of the productions are not executed.
+  All
+}
+
+/// Production with the forest nodes of its symbols.
+type Alternative = (Rc<GrammarProduction>, Vec<Rc<ForestNode>>);
+
+/// Nodes of the graph-structured stack on the path with the forest nodes between them.
+type GSSPath = (Vec<Rc<GSSNode>>, Vec<Rc<ForestNode>>);
+
+/// Node of the shared packed parse forest, every alternative is a production with its children.
+#[derive(Debug)]
+struct ForestNode {
+  symbol: GrammarSymbol,
+  /// Lookahead symbol of the reduce which created the node.
+  lookahead: GrammarSymbol,
+  bind_id: Option<usize>,
+  alternatives: RefCell<Vec<Alternative>>
+}
+
+impl ForestNode {
+  fn new(symbol: GrammarSymbol, lookahead: GrammarSymbol, alternatives: Vec<Alternative>) -> Rc<Self> {
+    Rc::new(Self {
+      symbol,
+      lookahead,
+      bind_id: None,
+      alternatives: RefCell::new(alternatives)
+    })
+  }
+
+  fn all_trees(&self) -> Vec<ParseTree> {
+    let alternatives = self.alternatives.borrow();
+    if alternatives.is_empty() {
+      return vec!(ParseTree {symbol: self.symbol.clone(), production: None, children: vec!()});
+    }
+    let mut result = vec!();
+    for (production, children) in alternatives.iter() {
+      let mut trees: Vec<Vec<ParseTree>> = vec!(vec!());
+      for child in children {
+        let child_trees = child.all_trees();
+        trees = trees.iter().flat_map(|trees1| child_trees.iter().map(move |tree| {
+          let mut trees1 = trees1.clone();
+          trees1.push(tree.clone());
+          trees1
+        })).collect();
+      }
+      for children in trees {
+        result.push(ParseTree {symbol: self.symbol.clone(), production: Some(production.clone()), children});
+      }
+    }
+    result
+  }
+}
+
+/// Node of the graph-structured stack, every edge leads to the previous node with the forest
+/// node of the symbol between them.
+#[derive(Debug)]
+struct GSSNode {
+  state: usize,
+  edges: RefCell<Vec<(Rc<GSSNode>, Rc<ForestNode>)>>
+}
+
+impl GSSNode {
+  fn new(state: usize) -> Rc<Self> {
+    Rc::new(Self {
+      state,
+      edges: RefCell::new(vec!())
+    })
+  }
+
+  /// Converts the stack of LR parser to the graph-structured stack, returns the top node.
+  fn from_stack(stack: &[StackItem]) -> Rc<GSSNode> {
+    let mut node = GSSNode::new(stack.first().map(|item| item.state).unwrap_or(0));
+    for item in stack.iter().skip(1) {
+      let forest = Rc::new(ForestNode {
+        symbol: item.symbol.clone().unwrap_or_else(GrammarSymbol::e_term),
+        lookahead: GrammarSymbol::e_term(),
+        bind_id: item.bind_id,
+        alternatives: RefCell::new(vec!())
+      });
+      let next = GSSNode::new(item.state);
+      next.edges.borrow_mut().push((node, forest));
+      node = next;
+    }
+    node
+  }
+
+  /// Returns states and forest nodes from the bottom to the node if there is the single path.
+  fn single_path(node: &Rc<GSSNode>) -> Option<Vec<(usize, Option<Rc<ForestNode>>)>> {
+    let mut path = vec!();
+    let mut node = node.clone();
+    loop {
+      let prev = {
+        let edges = node.edges.borrow();
+        match edges.len() {
+          0 => None,
+          1 => Some(edges[0].clone()),
+          _ => return None
+        }
+      };
+      match prev {
+        Some((prev, forest)) => {
+          path.push((node.state, Some(forest)));
+          node = prev;
+        },
+        _ => {
+          path.push((node.state, None));
+          break;
+        }
+      }
+    }
+    path.reverse();
+    Some(path)
+  }
+
+  /// Returns the nodes reached by the paths of the length from the node with the forest nodes
+  /// of the symbols on the path.
+  fn paths(node: &Rc<GSSNode>, len: usize) -> Vec<GSSPath> {
+    if len == 0 {
+      return vec!((vec!(node.clone()), vec!()));
+    }
+    let mut result = vec!();
+    for (prev, forest) in node.edges.borrow().iter() {
+      for (mut path, mut children) in GSSNode::paths(prev, len - 1) {
+        path.push(node.clone());
+        children.push(forest.clone());
+        result.push((path, children));
+      }
+    }
+    result
+  }
+
+  /// Adds the edge to the node, packs the alternative into the forest node if the edge exists.
+  /// Returns whether a new edge is added.
+  fn add_edge(&self, prev: &Rc<GSSNode>, symbol: GrammarSymbol, lookahead: &GrammarSymbol,
+    alternative: Option<Alternative>) -> bool {
+    let mut edges = self.edges.borrow_mut();
+    if let Some((_, forest)) = edges.iter().find(|(prev1, _)| Rc::ptr_eq(prev1, prev)) {
+      if let Some(alternative) = alternative {
+        forest.alternatives.borrow_mut().push(alternative);
+      }
+      return false;
+    }
+    edges.push((prev.clone(), ForestNode::new(symbol, lookahead.clone(), alternative.into_iter().collect())));
+    true
+  }
+}
+
 #[derive(Debug)]
 pub struct Parser {
   lex: Box<dyn LexBase>,
@@ -85,7 +424,17 @@ pub struct Parser {
   stack: Option<Vec<StackItem>>,
   cur_symbol: Option<GrammarSymbol>,
   is_e_symbol: bool,
-  state_logging: bool
+  err_flag: usize,
+  state_logging: bool,
+  frontier: Option<Vec<Rc<GSSNode>>>,
+  parse_trees: ParseTrees,
+  /// Data of the token being read by `read_bytes`.
+  read_value: Option<Vec<u8>>,
+  /// The lexer prefers the tokens expected in the current state.
+  context_lexing: bool,
+  /// Terminals of the grammar and the valid terminals of the states for the lexer.
+  terminals: Option<Rc<HashSet<usize>>>,
+  valid_terminals: HashMap<usize, Option<Rc<ValidTerminals>>>
 }
 
 impl Clone for Parser {
@@ -100,7 +449,14 @@ impl Clone for Parser {
       stack: Some(vec!()),
       cur_symbol: Some(GrammarSymbol::s_term()),
       is_e_symbol: false,
-      state_logging: self.state_logging
+      err_flag: 0,
+      state_logging: self.state_logging,
+      frontier: None,
+      parse_trees: self.parse_trees,
+      read_value: None,
+      context_lexing: self.context_lexing,
+      terminals: self.terminals.clone(),
+      valid_terminals: self.valid_terminals.clone()
     }
   }
 }
@@ -117,16 +473,30 @@ impl Parser {
       stack: Some(vec!()),
       cur_symbol: Some(GrammarSymbol::s_term()),
       is_e_symbol: false,
-      state_logging: true
+      err_flag: 0,
+      state_logging: true,
+      frontier: None,
+      parse_trees: ParseTrees::First,
+      read_value: None,
+      context_lexing: false,
+      terminals: None,
+      valid_terminals: HashMap::new()
     }
   }
 
   pub fn init(&mut self) {
     self.lex.init();
     self.nexts.clear();
+    self.read_value = None;
+    self.init_state();
+  }
+
+  fn init_state(&mut self) {
     self.stack = Some(vec!());
     self.cur_symbol = Some(GrammarSymbol::s_term());
     self.is_e_symbol = false;
+    self.err_flag = 0;
+    self.frontier = None;
   }
 
   pub fn enable_state_logging(&mut self) {
@@ -137,8 +507,14 @@ impl Parser {
     self.state_logging = false;
   }
 
-  pub fn set_grammar(&mut self, grammar: String, parser_type: ParserType) -> Result<(), String> {
-    let grammar = GrammarBuilder::from_text(grammar).unwrap();
+  /// Sets whether the lexer prefers the longest of the tokens which the parser accepts in the
+  /// current state to the other tokens, disabled by default.
+  pub fn set_context_lexing(&mut self, value: bool) {
+    self.context_lexing = value;
+  }
+
+  pub fn set_grammar(&mut self, grammar: String, parser_type: ParserType) -> Result<(), Error> {
+    let grammar = GrammarBuilder::from_text(grammar)?;
     let goto_states;
     let action_states;
     match parser_type {
@@ -149,19 +525,71 @@ impl Parser {
       ParserType::LR1 => {
         goto_states = LRBuilder::build_goto_states(&grammar);
         action_states = LRBuilder::build_action_states(&grammar, &goto_states)?;
+      },
+      ParserType::MinimalLR1 => {
+        goto_states = MinimalLRBuilder::build_goto_states(&grammar);
+        action_states = MinimalLRBuilder::build_action_states(&grammar, &goto_states)?;
+      },
+      ParserType::GLR => {
+        goto_states = GLRBuilder::build_goto_states(&grammar);
+        action_states = GLRBuilder::build_action_states(&grammar, &goto_states)?;
       }
     }
 
     self.grammar = Rc::new(grammar);
     self.goto_states = Rc::new(goto_states);
     self.action_states = Rc::new(action_states);
+    self.terminals = None;
+    self.valid_terminals.clear();
     Ok(())
   }
 
+  /// Sets which parse trees are delivered by the GLR parser, `ParseTrees::First` by default.
+  pub fn set_parse_trees(&mut self, parse_trees: ParseTrees) {
+    self.parse_trees = parse_trees;
+  }
+
+  /// Conflicts of the grammar resolved by precedence and associativity.
+  pub fn resolutions(&self) -> &Vec<ConflictResolution> {
+    self.action_states.resolutions()
+  }
+
   pub fn set_states(&mut self, grammar: Rc<Grammar>, goto_states: Rc<GotoStatesOpt>, action_states: Rc<ActionStatesOpt>) {
     self.grammar = grammar;
     self.goto_states = goto_states;
     self.action_states = action_states;
+    self.terminals = None;
+    self.valid_terminals.clear();
+  }
+
+  /// Terminals which the parser accepts in the states, `None` if any symbol is accepted by the
+  /// empty symbol or the terminals are not restricted (the error recovery).
+  fn valid_terminals(&mut self, states: &[usize]) -> Option<Rc<ValidTerminals>> {
+    if !self.context_lexing || states.is_empty() {return None}
+    if states.len() > 1 {
+      // the stacks of the GLR parser accept the terminals of every state
+      let mut valid = HashSet::new();
+      for state in states {
+        valid.extend(self.valid_terminals(&[*state])?.valid());
+      }
+      return Some(Rc::new(ValidTerminals::new(valid, self.terminals.clone().unwrap())));
+    }
+    let state = states[0];
+    if let Some(valid_terminals) = self.valid_terminals.get(&state) {
+      return valid_terminals.clone();
+    }
+    let action_states = self.action_states.clone();
+    let terminals = self.terminals.get_or_insert_with(|| {
+      Rc::new((0..action_states.len()).flat_map(|state| action_states.actions(state))
+        .map(|(name, _)| name).collect())
+    }).clone();
+    let actions = self.action_states.actions(state);
+    let valid_terminals = match actions.iter().any(|(name, _)| *name == GrammarSymbol::e_term().name()) {
+      true => None,
+      _ => Some(Rc::new(ValidTerminals::new(actions.iter().map(|(name, _)| *name).collect(), terminals)))
+    };
+    self.valid_terminals.insert(state, valid_terminals.clone());
+    valid_terminals
   }
 
   pub fn set_text(&mut self, value: String) {
@@ -172,6 +600,11 @@ impl Parser {
     self.lex.set_data(data);
   }
 
+  /// Marks the end of the data of the message, the message is ended by the end symbol.
+  pub fn set_end_of_data(&mut self) {
+    self.lex.set_end_of_data();
+  }
+
   pub fn has_data(&self) -> bool {
     self.lex.has_data()
   }
@@ -180,59 +613,41 @@ impl Parser {
     self.lex.data()
   }
 
-  pub fn set_on_tkn_data(&mut self, on_tkn_data: Option<Function>) {
-    self.lex.set_on_tkn_data(on_tkn_data);
+  pub fn parse<A: ParserActions>(&mut self, actions: &mut A) -> Result<ParseResult, Error> {
+    let mut symbol = None;
+    loop {
+      let glr = self.action_states.is_glr() && self.parse_trees == ParseTrees::All;
+      if self.frontier.is_some() || glr {
+        if let ParseStep::Done(result) = self.parse_glr(actions, symbol.take())? {
+          return Ok(result);
+        }
+      }
+      match self.parse_lr(actions)? {
+        ParseStep::Done(result) => return Ok(result),
+        ParseStep::Split(cur_symbol) => symbol = Some(cur_symbol),
+        ParseStep::Join => {}
+      }
+    }
   }
 
-  pub fn parse(&mut self, exec_context: &JsValue) -> Result<ParseResult, JsValue> {
+  fn parse_lr<A: ParserActions>(&mut self, actions: &mut A) -> Result<ParseStep, Error> {
     let e_term = GrammarSymbol::e_term();
     let w_term = GrammarSymbol::w_term();
-    let rust_action_name = &hash("rust_action");
-    let action_name = &hash("action");
+    let s_term = GrammarSymbol::s_term();
+    let error_term = GrammarSymbol::error_term();
 
     macro_rules! get_symbol {
-      ($exec_context: ident) => {
+      ($stack: ident) => {
         {
-          let mut res: Option<GrammarSymbol> = None;
-          if self.nexts.len() > 0 {
-            let next = &self.nexts[0];
-            let mut is_equal_last_name = false;
-            if let Some(ref last_name) = next.last_name {
-              is_equal_last_name = next.name() == *last_name;
-            }
-            if next.name() == e_term.name() || is_equal_last_name {
-              if next.size.is_some() {
-                self.lex.set_read_size(next.insert_name().unwrap(), next.size.unwrap());
-              } else {
-                let symbol = match next.insert_name() {
-                  Some(insert_name) => GrammarSymbol::term(*insert_name, next.insert_value().clone()),
-                  _ => GrammarSymbol::s_term()
-                };
-                res = Some(symbol);
-              }
-              self.nexts.remove(0);
-            }
-          }
-
-          if res.is_some() {
-            res.unwrap()
-          } else {
-            let tkn = self.lex.get_token(exec_context)?;
-            let symbol = GrammarSymbol::from_token(tkn);
-            if self.nexts.len() > 0 {
-              if let Some(ref mut next) = self.nexts.get_mut(0) {
-                next.last_name = Some(symbol.name());
-              }
-            }
-            symbol
-          }
+          let state = $stack.last().map_or(0, |item| item.state);
+          self.get_symbol(actions, &[state])?
         }
       }
     }
 
     macro_rules! restore_state {
       ($stack: ident) => {
-        $stack = self.stack.take().unwrap();
+        $stack = self.stack.take().unwrap_or_default();
       }
     }
 
@@ -248,22 +663,38 @@ impl Parser {
         self.stack = Some($stack);
         self.cur_symbol = Some(GrammarSymbol::s_term());
         self.is_e_symbol = false;
+        self.err_flag = 0;
       }
     }
 
-    fn err() -> Result<ParseResult, JsValue> {
-      Err(JsValue::from("Error parse"))
-    }
-
     let mut stack: Vec<StackItem>;
     let mut cur_symbol;
     let mut is_e_symbol;
     restore_state!(stack);
 
-    cur_symbol = get_symbol!(exec_context);
+    macro_rules! unexpected_symbol {
+      ($state: expr) => {
+        {
+          let state = $state;
+          Error::UnexpectedSymbol {state, symbol: cur_symbol.name(), value: cur_symbol.value().clone(),
+            offset: self.lex.token_offset(), expected: self.action_states.expected(state)}
+        }
+      }
+    }
+
+    macro_rules! err {
+      ($state: expr) => {
+        {
+          self.err_flag = 0;
+          return Err(unexpected_symbol!($state));
+        }
+      }
+    }
+
+    cur_symbol = get_symbol!(stack);
     if cur_symbol.name() == w_term.name() {
       save_state!(stack);
-      return Ok(ParseResult::ParseWait);
+      return Ok(ParseStep::Done(ParseResult::ParseWait));
     }
     is_e_symbol = cur_symbol.name() == e_term.name();
 
@@ -273,14 +704,63 @@ impl Parser {
 
     loop {
       let stack_item = stack.last();
-      if stack_item.is_none() {return err()}
-      let stack_item = stack_item.unwrap();
-      let mut action = self.action_states.state(stack_item.state, cur_symbol.name());
+      if stack_item.is_none() {err!(0)}
+      let state = stack_item.unwrap().state;
+      let mut action = self.action_states.state(state, cur_symbol.name());
       if action.is_none() {
         is_e_symbol = true;
-        action = self.action_states.state(stack_item.state, e_term.name());
+        action = self.action_states.state(state, e_term.name());
+      }
+      if self.action_states.is_glr() {
+        let name = if is_e_symbol {e_term.name()} else {cur_symbol.name()};
+        if self.action_states.glr_actions(state, name).len() > 1 {
+          // conflicting actions, the stack is split into the graph-structured stack
+          self.frontier = Some(vec!(GSSNode::from_stack(&stack)));
+          clear_state!(stack);
+          return Ok(ParseStep::Split(cur_symbol));
+        }
+      }
+      if action.is_none() {
+        is_e_symbol = false;
+        if self.err_flag == 3 {
+          // Error recovery: discards symbols until one of them can follow the error
+          if cur_symbol.name() == s_term.name() {err!(state)}
+          cur_symbol = self.get_symbol(actions, &[])?;
+          if cur_symbol.name() == w_term.name() {
+            save_state!(stack);
+            return Ok(ParseStep::Done(ParseResult::ParseWait));
+          }
+          continue;
+        }
+
+        let error = unexpected_symbol!(state);
+        // Error recovery: pops the stack until a state which shifts the error symbol
+        let recovery = stack.iter().enumerate().rev().find_map(|(index, item)| {
+          match self.action_states.state(item.state, error_term.name()) {
+            Some(action) if action.state() == &ActionState::Shift => (*action.goto()).map(|goto| (index, goto)),
+            _ => None
+          }
+        });
+        let (index, goto) = match recovery {
+          Some(recovery) => recovery,
+          _ => {
+            self.err_flag = 0;
+            return Err(error);
+          }
+        };
+        if self.err_flag == 0 {
+          actions.parse_error(&error).map_err(|message| Error::Action {message})?;
+        }
+
+        if cfg!(debug_assertions) && self.state_logging {
+          log(format!("Error,  discarded states: {:?}", stack.len() - index - 1).as_str());
+        }
+
+        stack.truncate(index + 1);
+        stack.push(StackItem::new(goto, None, Some(error_term.clone())));
+        self.err_flag = 3;
+        continue;
       }
-      if action.is_none() {return err()}
       let action = action.unwrap();
       match action.state() {
         ActionState::Shift => {
@@ -307,196 +787,31 @@ impl Parser {
           }
 
           let goto = action.goto();
-          if goto.is_none() {return err()}
+          if goto.is_none() {err!(stack.last().unwrap().state)}
           let goto = goto.unwrap();
           stack.push(StackItem::new(goto, None, symbol));
           if !is_e_symbol {
-            cur_symbol = get_symbol!(exec_context);
+            if self.err_flag > 0 {self.err_flag -= 1}
+            cur_symbol = get_symbol!(stack);
             if cur_symbol.name() == w_term.name() {
               save_state!(stack);
-              return Ok(ParseResult::ParseWait);
+              return Ok(ParseStep::Done(ParseResult::ParseWait));
             }
           }
           is_e_symbol = false;
         },
         ActionState::Reduce => {
           if let Some(action_prod) = action.production() {
-            let mut new_symbol = GrammarSymbol::non_term(action_prod.name(), None);
-            let mut new_symbol_name: Option<usize> = None;
-            let mut new_symbol_name1: Option<usize> = None;
-            let mut new_symbol_val: Option<Vec<u8>> = None;
-            let mut bind_id: Option<usize> = None;
-            let mut nexts: Option<Vec<Next>> = None;
-
-            let action = action_prod.attr(rust_action_name);
-            if let Some(action) = action {
-              if let Some(action) = action.as_rust_action() {
-
-                let mut set = |index: usize, index2: Option<usize>, index3: Option<usize>,
-                index4: Option<usize>, index5: Option<usize>| {
-                  let idx = stack.len() - 1 - index;
-                  match stack.get_mut(idx) {
-                    Some(item) => {
-                      if let Some(ref mut symbol) = item.symbol {
-                        new_symbol.set_value(symbol.take_value());
-                      } else {
-                        new_symbol.set_value(None);
-                      }
-                    },
-                    None => {new_symbol.set_value(None);}
-                  };
-
-                  if let Some(index2) = index2 {
-                    let idx = stack.len() - 1 - index2;
-                    if let Some(item) = stack.get_mut(idx) {
-                      if let Some(ref mut symbol) = item.symbol {
-                        new_symbol.extend_value(symbol.take_value());
-                      }
-                    }
-                  }
-
-                  if let Some(index3) = index3 {
-                    let idx = stack.len() - 1 - index3;
-                    if let Some(item) = stack.get_mut(idx) {
-                      if let Some(ref mut symbol) = item.symbol {
-                        new_symbol.extend_value(symbol.take_value());
-                      }
-                    }
-                  }
-
-                  if let Some(index4) = index4 {
-                    let idx = stack.len() - 1 - index4;
-                    if let Some(item) = stack.get_mut(idx) {
-                      if let Some(ref mut symbol) = item.symbol {
-                        new_symbol.extend_value(symbol.take_value());
-                      }
-                    }
-                  }
-
-                  if let Some(index5) = index5 {
-                    let idx = stack.len() - 1 - index5;
-                    if let Some(item) = stack.get_mut(idx) {
-                      if let Some(ref mut symbol) = item.symbol {
-                        new_symbol.extend_value(symbol.take_value());
-                      }
-                    }
-                  }
-                };
-
-                match action.command() {
-                  RustActionCommand::Set => {
-                    set(action.index(), action.index2(), action.index3(), action.index4(), action.index5());
-                  },
-                }
-              }
-            } else {
-
-              let action = action_prod.attr(action_name);
-              if let Some(action) = action {
-                if let Some(func) = action.as_function() {
-                  let mut bind = |id: usize| {
-                    bind_id = Some(id);
-                  };
-
-                  let id = |index: usize| -> Option<usize> {
-                    match stack.get(stack.len() - 1 - index) {
-                      Some(item) => {
-                        item.bind_id
-                      },
-                      None => None
-                    }
-                  };
-
-                  let lookup = || -> Option<Vec<u8>> {
-                    cur_symbol.value().clone()
-                  };
-
-                  let get = |index: usize| -> Option<Vec<u8>> {
-                    match stack.get(stack.len() - 1 - index) {
-                      Some(item) => {
-                        if let Some(ref symbol) = item.symbol {
-                          symbol.value().clone()
-                        } else {
-                          None
-                        }
-                      },
-                      None => None
-                    }
-                  };
-
-                  let mut set = |index: usize| {
-                    match stack.get(stack.len() - 1 - index) {
-                      Some(item) => {
-                        if let Some(ref symbol) = item.symbol {
-                          new_symbol.set_value(symbol.value().clone());
-                        } else {
-                          new_symbol.set_value(None);
-                        }
-                      },
-                      None => {new_symbol.set_value(None);}
-                    }
-                  };
-
-                  let mut set_val = |value: Vec<u8>| {
-                    new_symbol_val = Some(value);
-                  };
-
-                  let mut set_name = |name: String| {
-                    new_symbol_name = Some(hash(&name));
-                  };
-
-                  let mut set_name_from_hash = |hash_name: usize| {
-                    new_symbol_name1 = Some(hash_name);
-                  };
-
-                  let mut push_after = |name: String, insert_name: Option<String>,
-                    insert_value: Option<Vec<u8>>, size: Option<usize>, stop_code: Option<u8>| {
-                    let insert_name = match insert_name {
-                      Some(ref insert_name) => Some(hash(insert_name)),
-                      None => None
-                    };
-                    let mut set_params_count = 0;
-                    if insert_value.is_some() {set_params_count += 1;}
-                    if size.is_some() {set_params_count += 1;}
-                    if stop_code.is_some() {set_params_count += 1;}
-                    if set_params_count > 1 {panic!("Only one parameter ('insert_value', 'size' or 'stop_code') can be set at a time");}
-                    let next = Next::new(hash(&name), insert_name, insert_value, size, stop_code);
-                    match nexts {
-                      Some(ref mut nexts) => nexts.push(next),
-                      _ => {
-                        let mut _nexts: Vec<Next> = vec!();
-                        _nexts.push(next);
-                        nexts = Some(_nexts);
-                      }
-                    }
-                  };
-
-                  do_parser_action(&func, exec_context, &mut bind, &id, &lookup, &get, &mut set, &mut set_val, &mut set_name, &mut set_name_from_hash, &mut push_after)?;
-
-                  if new_symbol_val.is_some() {
-                    new_symbol.set_value(new_symbol_val);
-                  }
-
-                  if new_symbol_name1.is_some() {
-                    new_symbol.set_name(new_symbol_name1.unwrap());
-                  } else if new_symbol_name.is_some() {
-                    new_symbol.set_name(new_symbol_name.unwrap());
-                  }
-
-                  if let Some(nexts) = nexts {
-                    self.nexts.extend(nexts);
-                  }
-                }
-              }
-            }
+            let action_prod = action_prod.clone();
+            let (new_symbol, bind_id) = self.reduce_action(actions, &action_prod, &mut stack, &cur_symbol, false)?;
   
             stack.truncate(stack.len() - action_prod.len());
   
             let stack_item = stack.last();
-            if stack_item.is_none() {return err()}
+            if stack_item.is_none() {err!(0)}
             let stack_item = stack_item.unwrap();
             let new_state = self.goto_states.state(stack_item.state, new_symbol.name());
-            if new_state.is_none() {return err()}
+            if new_state.is_none() {err!(stack_item.state)}
             let new_state = new_state.unwrap();
   
             if cfg!(debug_assertions) {
@@ -507,25 +822,357 @@ impl Parser {
 
             stack.push(StackItem::new(*new_state, bind_id, Some(new_symbol)));
           } else {
-            return err();
+            err!(stack.last().unwrap().state);
           }
         },
         ActionState::Accept => {
           clear_state!(stack);
-          return Ok(ParseResult::ParseSuccess);
+          return Ok(ParseStep::Done(ParseResult::ParseSuccess));
         }
       }
     }
   }
-}
 
-#[wasm_bindgen(module = "/src/parser.js")]
-extern "C" {
-  #[wasm_bindgen(catch)]
-  fn do_parser_action(action: &Function, context: &JsValue, bind: &mut dyn FnMut(usize), id: &dyn Fn(usize) -> Option<usize>,
-    lookup: &dyn Fn() -> Option<Vec<u8>>, get: &dyn Fn(usize) -> Option<Vec<u8>>,
-    set: &mut dyn FnMut(usize), set_val: &mut dyn FnMut(Vec<u8>),
-    set_name: &mut dyn FnMut(String), set_name_from_hash: &mut dyn FnMut(usize),
-    push_after: &mut dyn FnMut(String, Option<String>, Option<Vec<u8>>, Option<usize>, Option<u8>)
-  ) -> Result<(), JsValue>;
-}
\ No newline at end of file
+  /// Executes the action of the production reduced on the top of the stack, returns the new
+  /// symbol and its bind id. The deferred actions of GLR parser run after the lexer has read
+  /// further, so they can not change the input of the lexer.
+  fn reduce_action<A: ParserActions>(&mut self, actions: &mut A, action_prod: &GrammarProduction,
+    stack: &mut [StackItem], cur_symbol: &GrammarSymbol, deferred: bool) -> Result<(GrammarSymbol, Option<usize>), Error> {
+    let rust_action_name = &hash("rust_action");
+    let action_name = &hash("action");
+
+    let mut new_symbol = GrammarSymbol::non_term(action_prod.name(), None);
+    let mut bind_id: Option<usize> = None;
+
+    let rust_action = action_prod.attr(rust_action_name).and_then(|action| action.as_rust_action());
+    let set_args = match rust_action.map(|action| action.statements().as_slice()) {
+      Some([RustStatement::Set(args)]) if args.iter().all(|arg| matches!(arg, RustExpr::Symbol(_))) => Some(args),
+      _ => None
+    };
+    if let Some(args) = set_args {
+      // values are moved from the stack, because the symbols are removed after the reduce
+      for (i, arg) in args.iter().enumerate() {
+        let value = match arg {
+          RustExpr::Symbol(index) => stack.len().checked_sub(index + 1)
+            .and_then(|idx| stack.get_mut(idx))
+            .and_then(|item| item.symbol.as_mut())
+            .and_then(|symbol| symbol.take_value()),
+          _ => None
+        };
+        if i == 0 {
+          new_symbol.set_value(value);
+        } else {
+          new_symbol.extend_value(value);
+        }
+      }
+    } else {
+      let action = match action_prod.attr(action_name) {
+        Some(action) => match action.as_string() {
+          Some(action) => Some(action.as_str()),
+          _ => None
+        },
+        _ => None
+      };
+
+      let mut ctx = ReduceContext {
+        stack: &*stack,
+        cur_symbol,
+        new_symbol: &mut new_symbol,
+        new_symbol_name: None,
+        new_symbol_name1: None,
+        new_symbol_val: None,
+        bind_id: None,
+        nexts: vec!(),
+        modes: vec!(),
+        error: None
+      };
+
+      match rust_action {
+        Some(rust_action) => rust_action.execute(&mut ctx),
+        _ => actions.parser_action(action_prod, action, &mut ctx)
+      }.map_err(|message| Error::Action {message})?;
+      if let Some(message) = ctx.error {
+        return Err(Error::Action {message});
+      }
+
+      let new_symbol_name = ctx.new_symbol_name;
+      let new_symbol_name1 = ctx.new_symbol_name1;
+      let new_symbol_val = ctx.new_symbol_val;
+      bind_id = ctx.bind_id;
+      if deferred && !(ctx.nexts.is_empty() && ctx.modes.is_empty()) {
+        return Err(Error::Action {
+          message: "push_after, read_bytes and lexer modes are not supported by GLR parser".to_string()
+        });
+      }
+      self.nexts.extend(ctx.nexts);
+      for mode in &ctx.modes {
+        self.lex.change_mode(mode)?;
+      }
+
+      if new_symbol_val.is_some() {
+        new_symbol.set_value(new_symbol_val);
+      }
+
+      if new_symbol_name1.is_some() {
+        new_symbol.set_name(new_symbol_name1.unwrap());
+      } else if new_symbol_name.is_some() {
+        new_symbol.set_name(new_symbol_name.unwrap());
+      }
+    }
+
+    Ok((new_symbol, bind_id))
+  }
+
+  /// Returns the symbol inserted by `push_after` or the next token of the lexer.
+  /// Returns the next symbol, the lexer prefers the terminals accepted in the states.
+  fn get_symbol<A: ParserActions>(&mut self, actions: &mut A, states: &[usize]) -> Result<GrammarSymbol, Error> {
+    let e_term = GrammarSymbol::e_term();
+    let mut res: Option<GrammarSymbol> = None;
+    if self.nexts.len() > 0 {
+      let next = &self.nexts[0];
+      let mut is_equal_last_name = false;
+      if let Some(ref last_name) = next.last_name {
+        is_equal_last_name = next.name() == *last_name;
+      }
+      if next.name() == e_term.name() || is_equal_last_name {
+        if next.size == Some(0) {
+          // nothing to read, the empty token is inserted
+          let insert_name = next.insert_name().unwrap();
+          let value = match next.keep_value {
+            true => Some(vec!()),
+            _ => {
+              actions.tkn_data(insert_name, &[], true).map_err(|message| Error::Action {message})?;
+              None
+            }
+          };
+          res = Some(GrammarSymbol::term(insert_name, value));
+        } else if next.size.is_some() {
+          self.lex.set_read_size(next.insert_name().unwrap(), next.size.unwrap());
+          if next.keep_value {
+            self.read_value = Some(vec!());
+          }
+        } else if let Some(ref stop_codes) = next.stop_codes {
+          self.lex.set_read_to_codes(next.insert_name().unwrap(), stop_codes.clone());
+        } else {
+          let symbol = match next.insert_name() {
+            Some(insert_name) => GrammarSymbol::term(*insert_name, next.insert_value().clone()),
+            _ => GrammarSymbol::s_term()
+          };
+          res = Some(symbol);
+        }
+        self.nexts.remove(0);
+      }
+    }
+
+    if let Some(res) = res {
+      Ok(res)
+    } else {
+      let valid_terminals = self.valid_terminals(states);
+      self.lex.set_valid_terminals(valid_terminals);
+      let tkn = match self.read_value.take() {
+        Some(mut value) => {
+          let tkn = self.lex.get_token(&mut ReadBytesActions {actions, value: &mut value})?;
+          match tkn {
+            Some(tkn) if tkn.name() == GrammarSymbol::w_term().name() => {
+              self.read_value = Some(value);
+              Some(tkn)
+            },
+            Some(tkn) => Some(Token::new(tkn.name(), value)),
+            None => None
+          }
+        },
+        _ => self.lex.get_token(actions)?
+      };
+      let symbol = GrammarSymbol::from_token(tkn);
+      if self.nexts.len() > 0 {
+        if let Some(ref mut next) = self.nexts.get_mut(0) {
+          next.last_name = Some(symbol.name());
+        }
+      }
+      Ok(symbol)
+    }
+  }
+
+  /// Parses by the graph-structured stack which keeps every stack of the conflicting actions.
+  /// Actions of the productions are deferred while there is more than one stack, `symbol` is
+  /// the symbol on which the stack is split.
+  fn parse_glr<A: ParserActions>(&mut self, actions: &mut A, mut symbol: Option<GrammarSymbol>) -> Result<ParseStep, Error> {
+    let e_term = GrammarSymbol::e_term();
+    let w_term = GrammarSymbol::w_term();
+
+    loop {
+      let cur_symbol = match symbol.take() {
+        Some(symbol) => symbol,
+        _ => {
+          let states: Vec<usize> = match self.frontier {
+            Some(ref nodes) => nodes.iter().map(|node| node.state).collect(),
+            _ => vec!(0)
+          };
+          self.get_symbol(actions, &states)?
+        }
+      };
+      if cur_symbol.name() == w_term.name() {
+        return Ok(ParseStep::Done(ParseResult::ParseWait));
+      }
+      let mut nodes = self.frontier.take().unwrap_or_else(|| vec!(GSSNode::new(0)));
+      let mut accepted: Vec<Rc<ForestNode>> = vec!();
+
+      // reduces until no new edge appears, the node of every state is single on the level
+      let mut done: HashSet<(usize, usize, Vec<usize>)> = HashSet::new();
+      loop {
+        let mut changed = false;
+        let mut i = 0;
+        while i < nodes.len() {
+          let node = nodes[i].clone();
+          let mut node_actions = self.action_states.glr_actions(node.state, cur_symbol.name());
+          let is_e_symbol = node_actions.is_empty();
+          if is_e_symbol {
+            node_actions = self.action_states.glr_actions(node.state, e_term.name());
+          }
+          let node_actions: Vec<ActionState2> = node_actions.into_iter().cloned().collect();
+          for action in node_actions {
+            let (production, len) = match action.state() {
+              ActionState::Reduce => match action.production() {
+                Some(production) => (Some(production.clone()), production.len()),
+                _ => continue
+              },
+              ActionState::Shift if is_e_symbol => (None, 0),
+              ActionState::Accept => {
+                for (_, forest) in node.edges.borrow().iter() {
+                  if !accepted.iter().any(|forest1| Rc::ptr_eq(forest1, forest)) {
+                    accepted.push(forest.clone());
+                  }
+                }
+                continue;
+              },
+              _ => continue
+            };
+            for (path, children) in GSSNode::paths(&node, len) {
+              let key = (
+                Rc::as_ptr(&node) as usize,
+                production.as_ref().map(|production| Rc::as_ptr(production) as usize).unwrap_or(0),
+                path.iter().map(|node| Rc::as_ptr(node) as usize).collect()
+              );
+              if !done.insert(key) {continue}
+              changed = true;
+              let prev = &path[0];
+              let (goto, symbol, alternative) = match production {
+                Some(ref production) => (
+                  self.goto_states.state(prev.state, production.name()).cloned(),
+                  GrammarSymbol::non_term(production.name(), None),
+                  Some((production.clone(), children))
+                ),
+                _ => (*action.goto(), e_term.clone(), None)
+              };
+              let goto = match goto {
+                Some(goto) => goto,
+                _ => continue
+              };
+              match nodes.iter().find(|node| node.state == goto) {
+                Some(next) => {
+                  next.add_edge(prev, symbol, &cur_symbol, alternative);
+                },
+                _ => {
+                  let next = GSSNode::new(goto);
+                  next.add_edge(prev, symbol, &cur_symbol, alternative);
+                  nodes.push(next);
+                }
+              }
+            }
+          }
+          i += 1;
+        }
+        if !changed {break}
+      }
+
+      if !accepted.is_empty() {
+        self.frontier = None;
+        match self.parse_trees {
+          ParseTrees::First => {
+            let mut stack = vec!(StackItem::new(0, None, None));
+            self.reduce_forest(actions, &accepted[0], &mut stack)?;
+          },
+          ParseTrees::All => {
+            let trees = accepted.iter().flat_map(|forest| forest.all_trees()).collect();
+            actions.parse_trees(trees).map_err(|message| Error::Action {message})?;
+          }
+        }
+        self.init_state();
+        return Ok(ParseStep::Done(ParseResult::ParseSuccess));
+      }
+
+      // shifts the symbol on every stack which accepts it
+      let leaf = ForestNode::new(cur_symbol.clone(), cur_symbol.clone(), vec!());
+      let mut next_nodes: Vec<Rc<GSSNode>> = vec!();
+      for node in &nodes {
+        for action in self.action_states.glr_actions(node.state, cur_symbol.name()) {
+          let goto = match (action.state(), action.goto()) {
+            (ActionState::Shift, Some(goto)) => *goto,
+            _ => continue
+          };
+          let next = match next_nodes.iter().find(|node| node.state == goto) {
+            Some(next) => next.clone(),
+            _ => {
+              let next = GSSNode::new(goto);
+              next_nodes.push(next.clone());
+              next
+            }
+          };
+          let mut edges = next.edges.borrow_mut();
+          if !edges.iter().any(|(prev, _)| Rc::ptr_eq(prev, node)) {
+            edges.push((node.clone(), leaf.clone()));
+          }
+        }
+      }
+
+      if cfg!(debug_assertions) && self.state_logging {
+        log(format!("Shift,  symbol name: {:?}, symbol value: {:?}, stacks: {}",
+          get_original_name(cur_symbol.name()), cur_symbol.value(), next_nodes.len()).as_str());
+      }
+
+      if next_nodes.is_empty() {
+        self.init_state();
+        let state = nodes.first().map(|node| node.state).unwrap_or(0);
+        return Err(Error::UnexpectedSymbol {state, symbol: cur_symbol.name(), value: cur_symbol.value().clone(),
+          offset: self.lex.token_offset(), expected: self.action_states.expected(state)});
+      }
+
+      // the single stack is left, deferred actions are executed and parsing continues by LR
+      if self.parse_trees == ParseTrees::First && next_nodes.len() == 1 {
+        if let Some(path) = GSSNode::single_path(&next_nodes[0]) {
+          let mut stack = vec!(StackItem::new(path[0].0, None, None));
+          for (state, forest) in &path[1..] {
+            let forest = forest.as_ref().unwrap();
+            self.reduce_forest(actions, forest, &mut stack)?;
+            if let Some(item) = stack.last_mut() {
+              item.state = *state;
+            }
+          }
+          self.stack = Some(stack);
+          return Ok(ParseStep::Join);
+        }
+      }
+      self.frontier = Some(next_nodes);
+    }
+  }
+
+  /// Executes actions of the productions of the first tree of the forest in the order of
+  /// the reduces of LR parser and pushes the symbol of the tree to the stack.
+  fn reduce_forest<A: ParserActions>(&mut self, actions: &mut A, forest: &ForestNode,
+    stack: &mut Vec<StackItem>) -> Result<(), Error> {
+    let (production, children) = match forest.alternatives.borrow().first() {
+      Some((production, children)) => (production.clone(), children.clone()),
+      _ => {
+        stack.push(StackItem::new(0, forest.bind_id, Some(forest.symbol.clone())));
+        return Ok(());
+      }
+    };
+    for child in &children {
+      self.reduce_forest(actions, child, stack)?;
+    }
+    let (new_symbol, bind_id) = self.reduce_action(actions, &production, stack, &forest.lookahead, true)?;
+    stack.truncate(stack.len() - production.len());
+    stack.push(StackItem::new(0, bind_id, Some(new_symbol)));
+    Ok(())
+  }
+}
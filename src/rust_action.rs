@@ -0,0 +1,711 @@
+use std::convert::TryFrom;
+
+use super::utils::Labels;
+use super::action::ParserActionContext;
+use super::binary::{BinaryWriter, BinaryReader};
+use super::error::Error;
+
+///
+/// Function of the expression of rust action
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RustFunction {
+  Concat,
+  Trim,
+  Lower,
+  Upper,
+  ParseInt,
+  ParseHex,
+  Eq,
+  Ne,
+  Lt,
+  Gt,
+  Not,
+  And,
+  Or,
+  Empty,
+  Lookup,
+  ParseBE,
+  ParseLE
+}
+
+/// Names of the functions with the minimal and the maximal number of arguments.
+const FUNCTIONS: [(&str, RustFunction, usize, usize); 17] = [
+  ("concat", RustFunction::Concat, 1, usize::MAX),
+  ("trim", RustFunction::Trim, 1, 1),
+  ("lower", RustFunction::Lower, 1, 1),
+  ("upper", RustFunction::Upper, 1, 1),
+  ("parse_int", RustFunction::ParseInt, 1, 1),
+  ("parse_hex", RustFunction::ParseHex, 1, 1),
+  ("eq", RustFunction::Eq, 2, 2),
+  ("ne", RustFunction::Ne, 2, 2),
+  ("lt", RustFunction::Lt, 2, 2),
+  ("gt", RustFunction::Gt, 2, 2),
+  ("not", RustFunction::Not, 1, 1),
+  ("and", RustFunction::And, 2, usize::MAX),
+  ("or", RustFunction::Or, 2, usize::MAX),
+  ("empty", RustFunction::Empty, 1, 1),
+  ("lookup", RustFunction::Lookup, 0, 0),
+  ("parse_be", RustFunction::ParseBE, 1, 1),
+  ("parse_le", RustFunction::ParseLE, 1, 1)
+];
+
+///
+/// Expression of rust action
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum RustExpr {
+  /// Value of the symbol by the index counted from the rightmost symbol.
+  Symbol(usize),
+  Bytes(Vec<u8>),
+  None,
+  Call(RustFunction, Vec<RustExpr>)
+}
+
+///
+/// Statement of rust action
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum RustStatement {
+  /// Sets value of the new symbol to the concatenation of the values.
+  Set(Vec<RustExpr>),
+  Bind(RustExpr),
+  SetName(RustExpr),
+  /// Arguments are `name`, `insert_name`, `insert_value`, `size` and `stop_codes` as in the javascript function.
+  PushAfter(Vec<RustExpr>),
+  /// Arguments are `size`, `insert_name` and `name` (the lookahead symbol by default), the read
+  /// bytes are the value of the inserted symbol.
+  ReadBytes(Vec<RustExpr>),
+  /// Switches the lexer to the mode.
+  Begin(RustExpr),
+  /// Saves the current mode of the lexer and switches to the mode.
+  PushMode(RustExpr),
+  /// Restores the saved mode of the lexer.
+  PopMode,
+  If(RustExpr, Vec<RustStatement>, Vec<RustStatement>)
+}
+
+/// Value of the expression, the numeric value is converted to the decimal text when it is set.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+  None,
+  Bytes(Vec<u8>),
+  Int(i64)
+}
+
+impl Value {
+  fn from(value: Option<&[u8]>) -> Self {
+    match value {
+      Some(value) => Value::Bytes(value.to_vec()),
+      _ => Value::None
+    }
+  }
+
+  fn bool(value: bool) -> Self {
+    Value::Int(value as i64)
+  }
+
+  fn into_bytes(self) -> Option<Vec<u8>> {
+    match self {
+      Value::None => None,
+      Value::Bytes(value) => Some(value),
+      Value::Int(value) => Some(value.to_string().into_bytes())
+    }
+  }
+
+  fn into_string(self) -> Option<String> {
+    self.into_bytes().map(|value| String::from_utf8_lossy(&value).to_string())
+  }
+
+  fn map_bytes<F: Fn(Vec<u8>) -> Vec<u8>>(self, f: F) -> Self {
+    match self.into_bytes() {
+      Some(value) => Value::Bytes(f(value)),
+      _ => Value::None
+    }
+  }
+
+  fn parse(&self, radix: u32) -> Result<i64, String> {
+    match self {
+      Value::Int(value) => Ok(*value),
+      Value::Bytes(value) => {
+        let text = String::from_utf8_lossy(value);
+        i64::from_str_radix(text.trim(), radix).map_err(|_| format!("Invalid number '{}'", text))
+      },
+      Value::None => Err("Number is expected, got none".to_string())
+    }
+  }
+
+  /// Parses the bytes as the big-endian or the little-endian unsigned integer.
+  fn parse_binary(self, big_endian: bool) -> Result<i64, String> {
+    let mut value = match self {
+      Value::Int(value) => return Ok(value),
+      Value::Bytes(value) => value,
+      Value::None => return Err("Number is expected, got none".to_string())
+    };
+    if value.len() > 8 {
+      return Err(format!("Invalid number of {} bytes", value.len()));
+    }
+    if !big_endian {
+      value.reverse();
+    }
+    Ok(value.iter().fold(0u64, |result, code| (result << 8) | *code as u64) as i64)
+  }
+
+  fn is_true(&self) -> bool {
+    match self {
+      Value::None => false,
+      Value::Bytes(value) => !value.is_empty(),
+      Value::Int(value) => *value != 0
+    }
+  }
+}
+
+///
+/// Rust action
+///
+/// Action of the production in square brackets executed natively without the call of
+/// `ParserActions`, e.g. `[set(trim(value)); if eq(lower(1), 'chunked') {set_name('chunked')}]`.
+///
+#[derive(Debug, Clone)]
+pub struct RustAction {
+  text: String,
+  statements: Vec<RustStatement>
+}
+
+impl RustAction {
+  pub fn new(text: String, statements: Vec<RustStatement>) -> Self {
+    Self {
+      text,
+      statements
+    }
+  }
+
+  /// Creates the action which concatenates values of the symbols by the indices.
+  pub fn set(indices: Vec<usize>) -> Self {
+    let text = indices.iter().map(|index| index.to_string()).collect::<Vec<String>>().join(", ");
+    let args = indices.into_iter().map(RustExpr::Symbol).collect();
+    Self::new(format!("set({})", text), vec!(RustStatement::Set(args)))
+  }
+
+  /// Parses the text of the action without square brackets, labels are resolved to indices.
+  pub fn parse(text: &str, labels: Option<&Labels>) -> Result<Self, String> {
+    let mut parser = ActionParser {tokens: tokenize(text)?, position: 0, labels};
+    let statements = parser.statements()?;
+    if parser.position < parser.tokens.len() {
+      return Err(parser.error("end of action"));
+    }
+    Ok(Self::new(text.trim().to_string(), statements))
+  }
+
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  pub fn statements(&self) -> &Vec<RustStatement> {
+    &self.statements
+  }
+
+  /// Returns indices of the symbols if the action only concatenates their values.
+  pub fn symbols(&self) -> Option<Vec<usize>> {
+    match self.statements.as_slice() {
+      [RustStatement::Set(args)] => args.iter().map(|arg| match arg {
+        RustExpr::Symbol(index) => Some(*index),
+        _ => None
+      }).collect(),
+      _ => None
+    }
+  }
+
+  pub fn execute(&self, ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    execute_statements(&self.statements, ctx)
+  }
+
+  pub fn write(&self, writer: &mut BinaryWriter) {
+    writer.write_str(&self.text);
+    write_statements(writer, &self.statements);
+  }
+
+  pub fn read(reader: &mut BinaryReader) -> Result<Self, Error> {
+    let text = reader.read_string()?;
+    let statements = read_statements(reader)?;
+    Ok(Self::new(text, statements))
+  }
+}
+
+fn evaluate(expr: &RustExpr, ctx: &dyn ParserActionContext) -> Result<Value, String> {
+  let (function, args) = match expr {
+    RustExpr::Symbol(index) => return Ok(Value::from(ctx.get(*index))),
+    RustExpr::Bytes(value) => return Ok(Value::Bytes(value.clone())),
+    RustExpr::None => return Ok(Value::None),
+    RustExpr::Call(function, args) => (function, args)
+  };
+  let arg = |index: usize| evaluate(&args[index], ctx);
+  let value = match function {
+    RustFunction::Concat => {
+      let mut result: Option<Vec<u8>> = None;
+      for arg in args {
+        if let Some(value) = evaluate(arg, ctx)?.into_bytes() {
+          result.get_or_insert_with(Vec::new).extend(value);
+        }
+      }
+      result.map(Value::Bytes).unwrap_or(Value::None)
+    },
+    RustFunction::Trim => arg(0)?.map_bytes(|value| value.trim_ascii().to_vec()),
+    RustFunction::Lower => arg(0)?.map_bytes(|value| value.to_ascii_lowercase()),
+    RustFunction::Upper => arg(0)?.map_bytes(|value| value.to_ascii_uppercase()),
+    RustFunction::ParseInt => Value::Int(arg(0)?.parse(10)?),
+    RustFunction::ParseHex => Value::Int(arg(0)?.parse(16)?),
+    RustFunction::Eq | RustFunction::Ne => {
+      let equal = match (arg(0)?, arg(1)?) {
+        (Value::Int(value1), Value::Int(value2)) => value1 == value2,
+        (value1, value2) => value1.into_bytes() == value2.into_bytes()
+      };
+      Value::bool(equal == (*function == RustFunction::Eq))
+    },
+    RustFunction::Lt => Value::bool(arg(0)?.parse(10)? < arg(1)?.parse(10)?),
+    RustFunction::Gt => Value::bool(arg(0)?.parse(10)? > arg(1)?.parse(10)?),
+    RustFunction::Not => Value::bool(!arg(0)?.is_true()),
+    RustFunction::And => {
+      for arg in args {
+        if !evaluate(arg, ctx)?.is_true() {return Ok(Value::bool(false))}
+      }
+      Value::bool(true)
+    },
+    RustFunction::Or => {
+      for arg in args {
+        if evaluate(arg, ctx)?.is_true() {return Ok(Value::bool(true))}
+      }
+      Value::bool(false)
+    },
+    RustFunction::Empty => Value::bool(arg(0)?.into_bytes().map(|value| value.is_empty()).unwrap_or(true)),
+    RustFunction::Lookup => Value::from(ctx.lookup()),
+    RustFunction::ParseBE => Value::Int(arg(0)?.parse_binary(true)?),
+    RustFunction::ParseLE => Value::Int(arg(0)?.parse_binary(false)?)
+  };
+  Ok(value)
+}
+
+fn execute_statements(statements: &[RustStatement], ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+  for statement in statements {
+    match statement {
+      RustStatement::Set(args) => {
+        if let Some(value) = evaluate(&RustExpr::Call(RustFunction::Concat, args.clone()), ctx)?.into_bytes() {
+          ctx.set_val(value);
+        }
+      },
+      RustStatement::Bind(arg) => {
+        let id = evaluate(arg, ctx)?.parse(10)?;
+        ctx.bind(usize::try_from(id).map_err(|_| format!("Invalid id {}", id))?);
+      },
+      RustStatement::SetName(arg) => {
+        let name = evaluate(arg, ctx)?.into_string().ok_or("Name is expected, got none")?;
+        ctx.set_name(&name);
+      },
+      RustStatement::PushAfter(args) => {
+        let arg = |index: usize| match args.get(index) {
+          Some(arg) => evaluate(arg, &*ctx),
+          _ => Ok(Value::None)
+        };
+        let name = arg(0)?.into_string().ok_or("Name is expected, got none")?;
+        let insert_name = arg(1)?.into_string();
+        let insert_value = arg(2)?.into_bytes();
+        let size = match arg(3)? {
+          Value::None => None,
+          size => {
+            let size = size.parse(10)?;
+            Some(usize::try_from(size).map_err(|_| format!("Invalid size {}", size))?)
+          }
+        };
+        let stop_codes = arg(4)?.into_bytes();
+        ctx.push_after(&name, insert_name.as_deref(), insert_value, size, stop_codes);
+      },
+      RustStatement::ReadBytes(args) => {
+        let size = evaluate(&args[0], ctx)?.parse(10)?;
+        let size = usize::try_from(size).map_err(|_| format!("Invalid size {}", size))?;
+        let insert_name = evaluate(&args[1], ctx)?.into_string().ok_or("Name is expected, got none")?;
+        let name = match args.get(2) {
+          Some(arg) => evaluate(arg, ctx)?.into_string().ok_or("Name is expected, got none")?,
+          _ => String::new()
+        };
+        ctx.read_bytes(&name, &insert_name, size);
+      },
+      RustStatement::Begin(arg) => {
+        let mode = evaluate(arg, ctx)?.into_string().ok_or("Mode is expected, got none")?;
+        ctx.begin(&mode);
+      },
+      RustStatement::PushMode(arg) => {
+        let mode = evaluate(arg, ctx)?.into_string().ok_or("Mode is expected, got none")?;
+        ctx.push_mode(&mode);
+      },
+      RustStatement::PopMode => ctx.pop_mode(),
+      RustStatement::If(condition, statements, else_statements) => {
+        if evaluate(condition, ctx)?.is_true() {
+          execute_statements(statements, ctx)?;
+        } else {
+          execute_statements(else_statements, ctx)?;
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+fn write_exprs(writer: &mut BinaryWriter, exprs: &[RustExpr]) {
+  writer.write_usize(exprs.len());
+  for expr in exprs {
+    write_expr(writer, expr);
+  }
+}
+
+fn write_expr(writer: &mut BinaryWriter, expr: &RustExpr) {
+  match expr {
+    RustExpr::Symbol(index) => {
+      writer.write_usize(0);
+      writer.write_usize(*index);
+    },
+    RustExpr::Bytes(value) => {
+      writer.write_usize(1);
+      writer.write_bytes(value);
+    },
+    RustExpr::None => writer.write_usize(2),
+    RustExpr::Call(function, args) => {
+      writer.write_usize(3);
+      writer.write_usize(FUNCTIONS.iter().position(|(_, function1, _, _)| function1 == function).unwrap());
+      write_exprs(writer, args);
+    }
+  }
+}
+
+fn write_statements(writer: &mut BinaryWriter, statements: &[RustStatement]) {
+  writer.write_usize(statements.len());
+  for statement in statements {
+    match statement {
+      RustStatement::Set(args) => {
+        writer.write_usize(0);
+        write_exprs(writer, args);
+      },
+      RustStatement::Bind(arg) => {
+        writer.write_usize(1);
+        write_expr(writer, arg);
+      },
+      RustStatement::SetName(arg) => {
+        writer.write_usize(2);
+        write_expr(writer, arg);
+      },
+      RustStatement::PushAfter(args) => {
+        writer.write_usize(3);
+        write_exprs(writer, args);
+      },
+      RustStatement::If(condition, statements, else_statements) => {
+        writer.write_usize(4);
+        write_expr(writer, condition);
+        write_statements(writer, statements);
+        write_statements(writer, else_statements);
+      },
+      RustStatement::ReadBytes(args) => {
+        writer.write_usize(5);
+        write_exprs(writer, args);
+      },
+      RustStatement::Begin(arg) => {
+        writer.write_usize(6);
+        write_expr(writer, arg);
+      },
+      RustStatement::PushMode(arg) => {
+        writer.write_usize(7);
+        write_expr(writer, arg);
+      },
+      RustStatement::PopMode => writer.write_usize(8)
+    }
+  }
+}
+
+fn invalid(message: String) -> Error {
+  Error::InvalidBinary {message}
+}
+
+/// Reads the arguments of the function or the statement, their number is checked as by `ActionParser`.
+fn read_exprs(reader: &mut BinaryReader, name: &str, min: usize, max: usize) -> Result<Vec<RustExpr>, Error> {
+  let len = reader.read_usize()?;
+  if len < min || len > max {
+    return Err(invalid(format!("Invalid number of arguments of {}", name)));
+  }
+  let mut exprs = vec!();
+  for _ in 0..len {
+    exprs.push(read_expr(reader)?);
+  }
+  Ok(exprs)
+}
+
+fn read_expr(reader: &mut BinaryReader) -> Result<RustExpr, Error> {
+  let expr = match reader.read_usize()? {
+    0 => RustExpr::Symbol(reader.read_usize()?),
+    1 => RustExpr::Bytes(reader.read_bytes()?),
+    2 => RustExpr::None,
+    3 => {
+      let index = reader.read_usize()?;
+      let (name, function, min, max) = FUNCTIONS.get(index).ok_or_else(|| invalid(format!("Invalid function {}", index)))?;
+      RustExpr::Call(*function, read_exprs(reader, name, *min, *max)?)
+    },
+    value => return Err(invalid(format!("Invalid type of expression {}", value)))
+  };
+  Ok(expr)
+}
+
+fn read_statements(reader: &mut BinaryReader) -> Result<Vec<RustStatement>, Error> {
+  let len = reader.read_usize()?;
+  let mut statements = vec!();
+  for _ in 0..len {
+    statements.push(match reader.read_usize()? {
+      0 => RustStatement::Set(read_exprs(reader, "set", 1, usize::MAX)?),
+      1 => RustStatement::Bind(read_expr(reader)?),
+      2 => RustStatement::SetName(read_expr(reader)?),
+      3 => RustStatement::PushAfter(read_exprs(reader, "push_after", 1, 5)?),
+      4 => RustStatement::If(read_expr(reader)?, read_statements(reader)?, read_statements(reader)?),
+      5 => RustStatement::ReadBytes(read_exprs(reader, "read_bytes", 2, 3)?),
+      6 => RustStatement::Begin(read_expr(reader)?),
+      7 => RustStatement::PushMode(read_expr(reader)?),
+      8 => RustStatement::PopMode,
+      value => return Err(invalid(format!("Invalid type of statement {}", value)))
+    });
+  }
+  Ok(statements)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Name(String),
+  Index(usize),
+  Bytes(Vec<u8>),
+  Punct(char)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = vec!();
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c.is_whitespace() {
+      continue;
+    } else if c.is_ascii_digit() {
+      let mut value = c.to_string();
+      while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        value.push(c);
+      }
+      tokens.push(Token::Index(value.parse().map_err(|_| format!("Invalid index {}", value))?));
+    } else if c == '_' || c.is_ascii_alphabetic() {
+      let mut value = c.to_string();
+      while let Some(c) = chars.next_if(|c| *c == '_' || c.is_ascii_alphanumeric()) {
+        value.push(c);
+      }
+      tokens.push(Token::Name(value));
+    } else if c == '\'' {
+      // `\xHH` escapes are bytes, other characters are UTF-8
+      let mut value = vec!();
+      loop {
+        match chars.next() {
+          Some('\'') => break,
+          Some('\\') => match chars.next() {
+            Some('n') => value.push(b'\n'),
+            Some('r') => value.push(b'\r'),
+            Some('t') => value.push(b'\t'),
+            Some('x') => {
+              let code: String = [chars.next(), chars.next()].iter().flatten().collect();
+              let code = u8::from_str_radix(&code, 16).map_err(|_| format!("Invalid escape \\x{}", code))?;
+              value.push(code);
+            },
+            Some(c) => value.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            None => return Err("Unterminated string".to_string())
+          },
+          Some(c) => value.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+          None => return Err("Unterminated string".to_string())
+        }
+      }
+      tokens.push(Token::Bytes(value));
+    } else if "(),;{}".contains(c) {
+      tokens.push(Token::Punct(c));
+    } else {
+      return Err(format!("Unexpected character {}", c));
+    }
+  }
+  Ok(tokens)
+}
+
+///
+/// Parser of rust action
+///
+/// statements: statement (';' statement)* ';'?
+/// statement: 'if' expr '{' statements '}' ('else' ('{' statements '}' | statement))?
+///   | name '(' (expr (',' expr)*)? ')'
+/// expr: index | label | string | 'none' | name '(' (expr (',' expr)*)? ')'
+///
+struct ActionParser<'a> {
+  tokens: Vec<Token>,
+  position: usize,
+  labels: Option<&'a Labels>
+}
+
+impl<'a> ActionParser<'a> {
+  fn error(&self, expected: &str) -> String {
+    match self.tokens.get(self.position) {
+      Some(Token::Name(name)) => format!("Expected {}, got {}", expected, name),
+      Some(Token::Index(index)) => format!("Expected {}, got {}", expected, index),
+      Some(Token::Bytes(value)) => format!("Expected {}, got '{}'", expected, String::from_utf8_lossy(value)),
+      Some(Token::Punct(c)) => format!("Expected {}, got {}", expected, c),
+      None => format!("Expected {}, got end of action", expected)
+    }
+  }
+
+  fn next_if(&mut self, token: &Token) -> bool {
+    if self.tokens.get(self.position) == Some(token) {
+      self.position += 1;
+      return true;
+    }
+    false
+  }
+
+  fn expect(&mut self, c: char) -> Result<(), String> {
+    if !self.next_if(&Token::Punct(c)) {
+      return Err(self.error(&format!("'{}'", c)));
+    }
+    Ok(())
+  }
+
+  fn statements(&mut self) -> Result<Vec<RustStatement>, String> {
+    let mut statements = vec!();
+    loop {
+      match self.tokens.get(self.position) {
+        None | Some(Token::Punct('}')) => break,
+        _ => {}
+      }
+      let statement = self.statement()?;
+      let is_block = matches!(statement, RustStatement::If(..));
+      statements.push(statement);
+      if !self.next_if(&Token::Punct(';')) && !is_block {
+        break;
+      }
+    }
+    if statements.is_empty() {
+      return Err(self.error("statement"));
+    }
+    Ok(statements)
+  }
+
+  fn block(&mut self) -> Result<Vec<RustStatement>, String> {
+    self.expect('{')?;
+    let statements = self.statements()?;
+    self.expect('}')?;
+    Ok(statements)
+  }
+
+  fn statement(&mut self) -> Result<RustStatement, String> {
+    let name = match self.tokens.get(self.position) {
+      Some(Token::Name(name)) => name.clone(),
+      _ => return Err(self.error("statement"))
+    };
+    self.position += 1;
+    if name == "if" {
+      let condition = self.expr()?;
+      let statements = self.block()?;
+      let mut else_statements = vec!();
+      if self.next_if(&Token::Name("else".to_string())) {
+        else_statements = match self.tokens.get(self.position) {
+          Some(Token::Punct('{')) => self.block()?,
+          _ => vec!(self.statement()?)
+        };
+      }
+      return Ok(RustStatement::If(condition, statements, else_statements));
+    }
+    let args = self.args()?;
+    let count = |min: usize, max: usize| {
+      if args.len() < min || args.len() > max {
+        return Err(format!("Invalid number of arguments of {}", name));
+      }
+      Ok(())
+    };
+    let statement = match name.as_str() {
+      "set" => {
+        count(1, usize::MAX)?;
+        RustStatement::Set(args)
+      },
+      "bind" => {
+        count(1, 1)?;
+        RustStatement::Bind(args.into_iter().next().unwrap())
+      },
+      "set_name" => {
+        count(1, 1)?;
+        RustStatement::SetName(args.into_iter().next().unwrap())
+      },
+      "push_after" => {
+        count(1, 5)?;
+        RustStatement::PushAfter(args)
+      },
+      "read_bytes" => {
+        count(2, 3)?;
+        RustStatement::ReadBytes(args)
+      },
+      "begin" => {
+        count(1, 1)?;
+        RustStatement::Begin(args.into_iter().next().unwrap())
+      },
+      "push_mode" => {
+        count(1, 1)?;
+        RustStatement::PushMode(args.into_iter().next().unwrap())
+      },
+      "pop_mode" => {
+        count(0, 0)?;
+        RustStatement::PopMode
+      },
+      _ => return Err(format!("Unknown statement {}", name))
+    };
+    Ok(statement)
+  }
+
+  fn args(&mut self) -> Result<Vec<RustExpr>, String> {
+    self.expect('(')?;
+    let mut args = vec!();
+    if self.next_if(&Token::Punct(')')) {
+      return Ok(args);
+    }
+    loop {
+      args.push(self.expr()?);
+      if self.next_if(&Token::Punct(')')) {
+        return Ok(args);
+      }
+      self.expect(',')?;
+    }
+  }
+
+  fn expr(&mut self) -> Result<RustExpr, String> {
+    let token = match self.tokens.get(self.position) {
+      Some(token) => token.clone(),
+      _ => return Err(self.error("expression"))
+    };
+    self.position += 1;
+    let name = match token {
+      Token::Index(index) => return Ok(RustExpr::Symbol(index)),
+      Token::Bytes(value) => return Ok(RustExpr::Bytes(value)),
+      Token::Name(name) => name,
+      Token::Punct(_) => {
+        self.position -= 1;
+        return Err(self.error("expression"));
+      }
+    };
+    if self.tokens.get(self.position) != Some(&Token::Punct('(')) {
+      if name == "none" {
+        return Ok(RustExpr::None);
+      }
+      return match self.labels.and_then(|labels| labels.index(&name)) {
+        Some(index) => Ok(RustExpr::Symbol(index)),
+        _ => Err(format!("Unknown label {}", name))
+      };
+    }
+    let (function, min, max) = match FUNCTIONS.iter().find(|(name1, _, _, _)| *name1 == name) {
+      Some((_, function, min, max)) => (*function, *min, *max),
+      _ => return Err(format!("Unknown function {}", name))
+    };
+    let args = self.args()?;
+    if args.len() < min || args.len() > max {
+      return Err(format!("Invalid number of arguments of {}", name));
+    }
+    Ok(RustExpr::Call(function, args))
+  }
+}
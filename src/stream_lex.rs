@@ -1,30 +1,49 @@
 use std::rc::Rc;
-use js_sys::{Function, Uint8Array};
-use wasm_bindgen::prelude::{JsValue};
+use std::convert::TryFrom;
 
 use super::utils::*;
-use super::lex::{LexBase, do_lex_action};
-use super::dfa::{build, State, GotoStatesOpt};
+use super::action::*;
+use super::error::*;
+use super::lex::{LexBase, LexModes, ModeAction, ValidTerminals, IntFormat, ReadInt, exec_lex_action};
+use super::dfa::{build, Accept, State, GotoStatesOpt};
 
 pub struct StreamLex {
   buffer: Vec<u8>,
   preread: bool,
   preread_codes: Vec<u8>,
+  /// Codes read after the last accepting state, they are read again after the token.
+  lookahead_codes: Vec<u8>,
   cur_position: usize,
   states: Option<Rc<Vec<Option<State>>>>,
   goto_states: Option<Rc<GotoStatesOpt>>,
-  on_tkn_data: Option<Function>,
   error: bool,
   is_pass: bool,
   state: usize,
   tkn_name: Option<usize>,
   tkn_value: Option<Vec<u8>>,
-  action: Option<Rc<Function>>,
+  action: Option<Rc<String>>,
   push_tkn_name: Option<usize>,
   push_tkn_data_buffer: Option<Vec<u8>>,
   size: Option<usize>,
-  stop_code: Option<u8>,
-  w_term_name: usize
+  /// Delimiter of the token read up to it.
+  stop_codes: Option<Vec<u8>>,
+  /// Format of the integer being read and the flag of the blob prefixed by the length.
+  read_int: Option<(IntFormat, bool)>,
+  int_codes: Vec<u8>,
+  /// Data of the blob is the value of the token instead of passing it to `tkn_data`.
+  keep_data: bool,
+  /// End of the data of the message, the pending token is finished and the end symbol follows.
+  end: bool,
+  offset: usize,
+  tkn_offset: usize,
+  w_term_name: usize,
+  modes: LexModes,
+  /// Start state of the current mode, the state 0 of the token is replaced by it.
+  start_state: usize,
+  /// Terminals expected by the parser, the longest of the valid tokens is preferred.
+  valid_terminals: Option<Rc<ValidTerminals>>,
+  /// None of the valid tokens matches, the token is read again by all the rules.
+  fallback: bool
 }
 
 impl Clone for StreamLex {
@@ -33,10 +52,10 @@ impl Clone for StreamLex {
       buffer: vec!(),
       preread: false,
       preread_codes: vec!(),
+      lookahead_codes: vec!(),
       cur_position: 0,
       states: self.states.clone(),
       goto_states: self.goto_states.clone(),
-      on_tkn_data: self.on_tkn_data.clone(),
       error: false,
       is_pass: false,
       state: 0,
@@ -46,22 +65,32 @@ impl Clone for StreamLex {
       push_tkn_name: None,
       push_tkn_data_buffer: None,
       size: None,
-      stop_code: None,
-      w_term_name: self.w_term_name
+      stop_codes: None,
+      read_int: None,
+      int_codes: vec!(),
+      keep_data: false,
+      end: false,
+      offset: 0,
+      tkn_offset: 0,
+      w_term_name: self.w_term_name,
+      modes: LexModes::new(),
+      start_state: 0,
+      valid_terminals: None,
+      fallback: false
     }
   }
 }
 
 impl StreamLex {
-  pub fn new(on_tkn_data: Option<Function>) -> Self {
+  pub fn new() -> Self {
     Self {
       buffer: vec!(),
       preread: false,
       preread_codes: vec!(),
+      lookahead_codes: vec!(),
       cur_position: 0,
       states: None,
       goto_states: None,
-      on_tkn_data,
       error: false,
       is_pass: false,
       state: 0,
@@ -71,8 +100,18 @@ impl StreamLex {
       push_tkn_name: None,
       push_tkn_data_buffer: None,
       size: None,
-      stop_code: None,
-      w_term_name: GrammarSymbol::w_term().name()
+      stop_codes: None,
+      read_int: None,
+      int_codes: vec!(),
+      keep_data: false,
+      end: false,
+      offset: 0,
+      tkn_offset: 0,
+      w_term_name: GrammarSymbol::w_term().name(),
+      modes: LexModes::new(),
+      start_state: 0,
+      valid_terminals: None,
+      fallback: false
     }
   }
 }
@@ -82,6 +121,7 @@ impl LexBase for StreamLex {
     self.buffer.clear();
     self.preread = false;
     self.preread_codes.clear();
+    self.lookahead_codes.clear();
     self.cur_position = 0;
     self.error = false;
     self.is_pass = false;
@@ -92,32 +132,77 @@ impl LexBase for StreamLex {
     self.push_tkn_name = None;
     self.size = None;
     self.push_tkn_data_buffer = None;
+    self.stop_codes = None;
+    self.read_int = None;
+    self.int_codes.clear();
+    self.keep_data = false;
+    self.end = false;
+    self.offset = 0;
+    self.tkn_offset = 0;
+    self.modes.reset();
+    self.start_state = 0;
+    self.valid_terminals = None;
+    self.fallback = false;
+  }
+
+  fn change_mode(&mut self, action: &ModeAction) -> Result<(), Error> {
+    let mut modes = self.modes.clone();
+    modes.apply(action)?;
+    let start_state = match self.goto_states {
+      Some(ref goto_states) => goto_states.mode_state(modes.mode()),
+      _ => Some(0)
+    };
+    match start_state {
+      Some(start_state) => {
+        self.modes = modes;
+        self.start_state = start_state;
+        Ok(())
+      },
+      _ => Err(Error::Action {message: format!("Unknown lexer mode '{}'", get_original_name(modes.mode()).unwrap_or_default())})
+    }
   }
 
   fn box_clone(&self) -> Box<dyn LexBase> {
     Box::new(self.clone())
   }
 
+  fn set_valid_terminals(&mut self, valid_terminals: Option<Rc<ValidTerminals>>) {
+    self.valid_terminals = valid_terminals;
+  }
+
   fn set_read_size(&mut self, tkn_name: usize, size: usize) {
     self.push_tkn_name = Some(tkn_name);
     self.size = Some(size);
     self.preread = false;
+    self.fallback = false;
     self.state = 0;
     self.error = false;
     self.is_pass = false;
     self.tkn_name = None;
     self.tkn_value = None;
-    self.stop_code = None;
+    self.stop_codes = None;
   }
 
-  fn set_read_to_code(&mut self, tkn_name: usize, code: u8) {
-    self.push_tkn_name = Some(tkn_name);
-    self.stop_code = Some(code);
+  fn set_read_int(&mut self, tkn_name: usize, format: IntFormat) {
+    self.set_read_size(tkn_name, 0);
     self.size = None;
+    self.read_int = Some((format, false));
+    self.int_codes.clear();
   }
 
-  fn set_on_tkn_data(&mut self, on_tkn_data: Option<Function>) {
-    self.on_tkn_data = on_tkn_data;
+  fn set_read_blob(&mut self, tkn_name: usize, format: IntFormat) {
+    self.set_read_int(tkn_name, format);
+    self.read_int = Some((format, true));
+  }
+
+  fn set_read_to_codes(&mut self, tkn_name: usize, stop_codes: Vec<u8>) {
+    self.set_read_size(tkn_name, 0);
+    self.size = None;
+    self.stop_codes = Some(stop_codes);
+  }
+
+  fn set_end_of_data(&mut self) {
+    self.end = true;
   }
 
   fn set_data(&mut self, data: Vec<u8>) {
@@ -136,7 +221,11 @@ impl LexBase for StreamLex {
     }
   }
 
-  fn get_token(&mut self, exec_context: &JsValue) -> Result<Option<Token>, JsValue> {
+  fn token_offset(&self) -> usize {
+    self.tkn_offset
+  }
+
+  fn get_token(&mut self, actions: &mut dyn LexActions) -> Result<Option<Token>, Error> {
 
     macro_rules! restore_state {
       ($is_pass: ident, $state: ident, $tkn_name: ident, $tkn_value: ident, $action: ident,
@@ -176,19 +265,94 @@ impl LexBase for StreamLex {
     let mut state: usize;
     let mut code: Option<u8>;
     let mut tkn_name: Option<usize>;
-    let tkn_value: Vec<u8>;
-    let mut action: Option<Rc<Function>>;
+    let mut tkn_value: Vec<u8>;
+    let mut action: Option<Rc<String>>;
     let push_tkn_name: Option<usize>;
     let push_tkn_data_buffer: Option<Vec<u8>>;
     let size: Option<usize>;
 
+    if let Some((format, blob)) = self.read_int {
+      if self.int_codes.is_empty() {
+        self.tkn_offset = self.offset - self.preread_codes.len();
+      }
+      let value = loop {
+        match format.decode(&self.int_codes) {
+          Ok(Some(value)) => break value,
+          Ok(None) => {},
+          Err(_) => {
+            self.read_int = None;
+            self.int_codes.clear();
+            return Err(Error::LexNoMatch {offset: self.tkn_offset});
+          }
+        }
+        match self.get_code() {
+          Some(code) => self.int_codes.push(code),
+          _ => return Ok(Some(Token::new(self.w_term_name, vec!())))
+        }
+      };
+      self.read_int = None;
+      self.int_codes.clear();
+      let tkn_name = self.push_tkn_name.unwrap();
+      if !blob {
+        return Ok(Some(Token::new(tkn_name, value.to_string().into_bytes())));
+      }
+      if value == 0 {
+        return Ok(Some(Token::new(tkn_name, vec!())));
+      }
+      // the length of 64 bits may not fit in the size on 32-bit targets
+      self.size = match usize::try_from(value) {
+        Ok(size) => Some(size),
+        _ => return Err(Error::LexNoMatch {offset: self.tkn_offset})
+      };
+      self.keep_data = true;
+      self.push_tkn_data_buffer = Some(vec!());
+    }
+
+    if let Some(stop_codes) = self.stop_codes.take() {
+      let push_tkn_name = self.push_tkn_name.unwrap();
+      let mut push_tkn_data_buffer = match self.push_tkn_data_buffer.take() {
+        Some(push_tkn_data_buffer) => push_tkn_data_buffer,
+        _ => {
+          self.tkn_offset = self.offset - self.preread_codes.len();
+          Vec::with_capacity(HIGHWATERMARK)
+        }
+      };
+      loop {
+        let code = match self.get_code() {
+          Some(code) => code,
+          _ => {
+            // the part of the delimiter at the end of the buffer is kept to match it with the next data
+            self.push_tkn_data_buffer = Some(push_tkn_data_buffer);
+            self.stop_codes = Some(stop_codes);
+            return Ok(Some(Token::new(self.w_term_name, vec!())));
+          }
+        };
+        push_tkn_data_buffer.push(code);
+        if push_tkn_data_buffer.ends_with(&stop_codes) {
+          push_tkn_data_buffer.truncate(push_tkn_data_buffer.len() - stop_codes.len());
+          actions.tkn_data(push_tkn_name, &push_tkn_data_buffer, true)
+            .map_err(|message| Error::Action {message})?;
+          return Ok(Some(Token::new(push_tkn_name, vec!())));
+        }
+        if push_tkn_data_buffer.len() > HIGHWATERMARK + stop_codes.len() {
+          let len = push_tkn_data_buffer.len() - stop_codes.len();
+          actions.tkn_data(push_tkn_name, &push_tkn_data_buffer[..len], false)
+            .map_err(|message| Error::Action {message})?;
+          push_tkn_data_buffer.drain(..len);
+        }
+      }
+    }
+
     restore_state!(is_pass, state, tkn_name, tkn_value, action, push_tkn_name, push_tkn_data_buffer, size);
 
     if let Some(size) = size {
       let mut size_to_end = size;
       let mut push_tkn_data_buffer = match push_tkn_data_buffer {
         Some(push_tkn_data_buffer) => push_tkn_data_buffer,
-        _ => Vec::with_capacity(HIGHWATERMARK)
+        _ => {
+          self.tkn_offset = self.offset - self.preread_codes.len();
+          Vec::with_capacity(HIGHWATERMARK)
+        }
       };
       for _ in 0..self.preread_codes.len() {
         push_tkn_data_buffer.push(self.preread_codes.pop().unwrap());
@@ -207,34 +371,32 @@ impl LexBase for StreamLex {
         if size_to_end > (self.buffer.len() - self.cur_position) {
           push_tkn_data_buffer.extend_from_slice(&self.buffer[self.cur_position..]);
           size_to_end -= self.buffer.len() - self.cur_position;
+          self.offset += self.buffer.len() - self.cur_position;
           self.buffer.clear();
           self.cur_position = 0;
         } else {
           let new_position = self.cur_position + size_to_end;
           push_tkn_data_buffer.extend_from_slice(&self.buffer[self.cur_position..new_position]);
+          self.offset += size_to_end;
           if new_position < self.buffer.len() {
             self.cur_position = new_position;
           } else {
             self.buffer = vec!();
             self.cur_position = 0;
           }
-          if let Some(ref on_tkn_data) = self.on_tkn_data {
-            let push_tkn_name = JsValue::from(push_tkn_name.unwrap() as u32);
-            let push_tkn_data = unsafe {Uint8Array::view(&push_tkn_data_buffer)};
-            let end = JsValue::from(true);
-            let _ = on_tkn_data.call3(exec_context, &push_tkn_name, &push_tkn_data, &end)?;
+          if self.keep_data {
+            self.keep_data = false;
+            return Ok(Some(Token::new(push_tkn_name.unwrap(), push_tkn_data_buffer)));
           }
+          actions.tkn_data(push_tkn_name.unwrap(), &push_tkn_data_buffer, true)
+            .map_err(|message| Error::Action {message})?;
           push_tkn_data_buffer.clear();
           return Ok(Some(Token::new(push_tkn_name.unwrap(), vec!())));
         }
 
-        if push_tkn_data_buffer.len() > HIGHWATERMARK {
-          if let Some(ref on_tkn_data) = self.on_tkn_data {
-            let push_tkn_name = JsValue::from(push_tkn_name.unwrap() as u32);
-            let push_tkn_data = unsafe {Uint8Array::view(&push_tkn_data_buffer)};
-            let end = JsValue::from(false);
-            let _ = on_tkn_data.call3(exec_context, &push_tkn_name, &push_tkn_data, &end)?;
-          }
+        if push_tkn_data_buffer.len() > HIGHWATERMARK && !self.keep_data {
+          actions.tkn_data(push_tkn_name.unwrap(), &push_tkn_data_buffer, false)
+            .map_err(|message| Error::Action {message})?;
           push_tkn_data_buffer.clear();
         }
       }
@@ -246,38 +408,45 @@ impl LexBase for StreamLex {
 
       if self.preread_codes.len() == 0 {
         if self.buffer.len() == 0 {
-          save_state!(is_pass, state, tkn_name, tkn_value, action, push_tkn_name, None, size);
-          return Ok(Some(Token::new(self.w_term_name, vec!())))
+          if self.end && state == 0 {
+            self.end = false;
+            self.state = 0;
+            self.tkn_name = None;
+            return Ok(None);
+          }
+          if !self.end {
+            save_state!(is_pass, state, tkn_name, tkn_value, action, push_tkn_name, None, size);
+            return Ok(Some(Token::new(self.w_term_name, vec!())))
+          }
         }
       }
+      if state == 0 {
+        self.tkn_offset = self.offset - self.preread_codes.len();
+      }
       code = self.get_code();
-      let mut tkn_value = tkn_value.clone();
+      let mut tkn_value = std::mem::take(&mut tkn_value);
       if state == 0 {
         if let Some(code) = code {
           tkn_value.push(code);
         }
-      } else {
+      } else if let Some(code) = code {
         self.preread = true;
-        self.preread_codes.push(code.unwrap());
+        self.lookahead_codes.push(code);
       }
 
       while code != None {
         match self.goto(state, code.unwrap() as usize) {
           Some(data) => {
             state = *data;
-            if let Some(state_info) = self.state(state) {
-              tkn_name = Some(state_info.accept());
-              action = state_info.action().clone();
-            }
+            let accept = self.accept(state);
             let goto_next_exists = self.goto_exists(state);
-            if tkn_name.is_some() {
-              if self.preread && self.preread_codes.len() > 0 {
-                tkn_value.extend(self.preread_codes.clone());
-                self.preread_codes.clear();
-              }
+            if let Some((accept, accept_action)) = accept {
+              tkn_name = Some(accept);
+              action = accept_action;
+              tkn_value.append(&mut self.lookahead_codes);
               if goto_next_exists {self.preread = true}
               else {break}
-            }
+            } else if !goto_next_exists && self.is_filtered() {break}
           },
           None => {
             if tkn_name.is_none() {
@@ -291,7 +460,7 @@ impl LexBase for StreamLex {
         if code != None {
           let code = code.unwrap();
           if self.preread {
-            self.preread_codes.push(code);
+            self.lookahead_codes.push(code);
           }
           else {
             tkn_value.push(code);
@@ -299,56 +468,49 @@ impl LexBase for StreamLex {
         }
       }
 
-      if code == None {
+      if tkn_name.is_none() && self.is_filtered() && (code.is_some() || self.end) {
+        // none of the valid tokens matches, the codes of the token are read again by all the rules
+        self.fallback = true;
+        self.error = false;
+        tkn_value.append(&mut self.lookahead_codes);
+        self.lookahead_codes = tkn_value;
+        self.unread_lookahead();
+        state = 0;
+        continue;
+      }
+
+      if code == None && !(self.end && tkn_name.is_some()) {
+        if self.end {
+          // the data ends inside of the token
+          self.end = false;
+          self.lookahead_codes.clear();
+          self.state = 0;
+          self.tkn_name = None;
+          return Err(Error::LexNoMatch {offset: self.tkn_offset});
+        }
         save_state!(is_pass, state, tkn_name, tkn_value, action, push_tkn_name, None, size);
         return Ok(Some(Token::new(self.w_term_name, vec!())))
       }
+      self.unread_lookahead();
+      self.fallback = false;
 
       if tkn_name.is_some() {
-        let tkn_name = tkn_name.unwrap();
-        let mut tkn_name_changed: Option<usize> = None;
-        let mut tkn_name_changed1: Option<usize> = None;
-        let mut tkn_value_changed: Option<Vec<u8>> = None;
-        if let Some(ref func) = action {
-
-          let get = || -> Vec<u8> {
-            tkn_value.clone()
-          };
-
-          let mut set = |value: Vec<u8>| {
-            tkn_value_changed = Some(value);
-          };
-
-          let mut set_name = |name: String| {
-            tkn_name_changed = Some(hash(&name));
-          };
-
-          let mut set_name_from_hash = |hash_name: usize| {
-            tkn_name_changed1 = Some(hash_name);
-          };
-
-          let mut pass = || {
-            is_pass = true;
-          };
-
-          do_lex_action(&func, exec_context, &get, &mut set, &mut set_name, &mut set_name_from_hash, &mut pass)?;
-  
-          let tkn_name = match tkn_name_changed1 {
-            Some(tkn_name_changed1) => tkn_name_changed1,
-            _ => match tkn_name_changed {
-              Some(tkn_name_changed) => tkn_name_changed,
-              _ => tkn_name
-            }
-          };
-
-          let tkn_value = match tkn_value_changed {
-            Some(tkn_value_changed) => tkn_value_changed,
-            _ => tkn_value
-          };
-
-          tkn = Some(Token::new(tkn_name, tkn_value));
-        } else {
-          tkn = Some(Token::new(tkn_name, tkn_value));
+        let action = match action {
+          Some(ref action) => Some(action.as_str()),
+          _ => None
+        };
+        let (token, pass, read_int, modes) = exec_lex_action(actions, tkn_name.unwrap(), tkn_value, action)?;
+        tkn = Some(token);
+        is_pass = pass;
+        for mode in &modes {
+          self.change_mode(mode)?;
+        }
+        if let Some(read_int) = read_int {
+          self.read_int_after(read_int);
+          if is_pass {
+            return self.get_token(actions);
+          }
+          return Ok(tkn);
         }
       }
 
@@ -360,13 +522,16 @@ impl LexBase for StreamLex {
 
     self.state = 0;
     self.tkn_name = None;
+    if self.error {
+      return Err(Error::LexNoMatch {offset: self.tkn_offset});
+    }
     Ok(tkn)
   }
 }
 
 impl StreamLex {
-  pub fn set_regular_definition_text(&mut self, reg_exp: String) -> Result<(), String> {
-    let (states, goto_states) = build(reg_exp);
+  pub fn set_regular_definition_text(&mut self, reg_exp: String) -> Result<(), Error> {
+    let (states, goto_states) = build(reg_exp)?;
     self.states = Some(Rc::new(states));
     self.goto_states = Some(Rc::new(goto_states));
     Ok(())
@@ -391,12 +556,26 @@ impl StreamLex {
   }
 
   fn goto(&self, state: usize, code: usize) -> Option<&usize> {
+    let state = if state == 0 {self.start_state} else {state};
     match self.goto_states {
       Some(ref goto_states) => goto_states.state(state, code),
       None => None
     }
   }
 
+  /// Accepted token of the state, the first of the valid tokens if the parser sets them.
+  fn accept(&self, state: usize) -> Option<Accept> {
+    let state = self.state(state)?;
+    match self.valid_terminals {
+      Some(ref valid_terminals) if !self.fallback => state.find_accept(|accept| valid_terminals.contains(accept)),
+      _ => Some((state.accept(), state.action().clone()))
+    }
+  }
+
+  fn is_filtered(&self) -> bool {
+    self.valid_terminals.is_some() && !self.fallback
+  }
+
   fn goto_exists(&self, state: usize) -> bool {
     match self.goto_states {
       Some(ref goto_states) => goto_states.state_exists(state),
@@ -404,11 +583,27 @@ impl StreamLex {
     }
   }
 
+  /// Reads the token after the recognized one as the integer or as the blob.
+  fn read_int_after(&mut self, (tkn_name, format, blob): ReadInt) {
+    match blob {
+      true => self.set_read_blob(tkn_name, format),
+      _ => self.set_read_int(tkn_name, format)
+    }
+  }
+
+  /// Returns the codes read after the token to the codes read first.
+  fn unread_lookahead(&mut self) {
+    while let Some(code) = self.lookahead_codes.pop() {
+      self.preread_codes.push(code);
+    }
+  }
+
   fn get_code(&mut self) -> Option<u8> {
     if self.preread_codes.len() > 0 {return Some(self.preread_codes.pop().unwrap())}
     if self.buffer.len() == 0 {return None}
     let code = self.buffer[self.cur_position];
     self.cur_position += 1;
+    self.offset += 1;
     if self.cur_position > (self.buffer.len() - 1) {
       self.buffer.clear();
       self.cur_position = 0;
@@ -6,10 +6,15 @@ use std::hash::{Hash, Hasher, BuildHasherDefault};
 use std::fmt;
 use std::fmt::Debug;
 use std::any::{TypeId, Any};
-use js_sys::Function;
-use wasm_bindgen::prelude::{JsValue, wasm_bindgen};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
 use lazy_static::*;
 
+use super::error::Error;
+use super::binary::{BinaryWriter, BinaryReader};
+use super::rust_action::RustAction;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 #[wasm_bindgen]
 extern "C" {
   #[wasm_bindgen(js_namespace = console)]
@@ -18,6 +23,11 @@ extern "C" {
   pub fn perf_now() -> f64;
 }
 
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn log(s: &str) {
+  eprintln!("{}", s);
+}
+
 #[derive(Default)]
 pub struct UsizeHasher {
   hash: u64
@@ -47,7 +57,7 @@ lazy_static! {
   };
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn hash(name: &str) -> usize {
   let mut hasher = DefaultHasher::new();
   hasher.write(name.as_bytes());
@@ -59,10 +69,8 @@ pub fn hash(name: &str) -> usize {
     Some(hash) => *hash,
     _ => {
       names.insert(name_u64, next_hash);
-      if cfg!(debug_assertions) {
-        let mut original_names = ORIGINAL_NAMES.lock().unwrap();
-        original_names.insert(next_hash, name.to_string());
-      }
+      let mut original_names = ORIGINAL_NAMES.lock().unwrap();
+      original_names.insert(next_hash, name.to_string());
       next_hash
     }
   };
@@ -70,7 +78,7 @@ pub fn hash(name: &str) -> usize {
   hash as usize
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn get_original_name(name: usize) -> Option<String> {
   let original_names = ORIGINAL_NAMES.lock().unwrap();
   match original_names.get(&name) {
@@ -261,6 +269,11 @@ impl GrammarSymbol {
       "w"
     }
 
+  /// Gets error-terminal symbol name
+  pub fn error_term_name() -> &'static str {
+    "error"
+  }
+
   /// Creates new e-terminal symbol.
   pub fn e_term() -> GrammarSymbol {
     GrammarSymbol {
@@ -297,6 +310,15 @@ impl GrammarSymbol {
     }
   }
 
+  /// Creates new error-terminal symbol.
+  pub fn error_term() -> GrammarSymbol {
+    GrammarSymbol {
+      name: hash(GrammarSymbol::error_term_name()),
+      value: None,
+      tp: GSType::T
+    }
+  }
+
   /// Creates new nonterminal symbol.
   pub fn non_term(name: usize, value: Option<Vec<u8>>) -> GrammarSymbol {
     GrammarSymbol {
@@ -372,64 +394,34 @@ impl GrammarSymbol {
 
 }
 
-#[derive(PartialEq, Clone, Copy, Debug)]
-pub enum RustActionCommand {
-  Set
-}
-
+///
+/// Labels
+///
+/// Names given to the symbols of the production in the grammar text as `name=symbol`, every
+/// label is resolved to the index of the symbol counted from the rightmost symbol.
+///
 #[derive(Clone, Debug)]
-pub struct RustAction {
-  command: RustActionCommand,
-  index: usize,
-  index2: Option<usize>,
-  index3: Option<usize>,
-  index4: Option<usize>,
-  index5: Option<usize>
+pub struct Labels {
+  labels: Vec<(String, usize)>
 }
 
-impl RustAction {
-  pub fn new(command: RustActionCommand, index: usize, index2: Option<usize>, index3: Option<usize>,
-    index4: Option<usize>, index5: Option<usize>) -> Self {
+impl Labels {
+  pub fn new(labels: Vec<(String, usize)>) -> Self {
     Self {
-      command,
-      index,
-      index2,
-      index3,
-      index4,
-      index5
+      labels
     }
   }
 
-  pub fn command(&self) -> RustActionCommand {
-    self.command
-  }
-
-  pub fn index(&self) -> usize {
-    self.index
-  }
-
-  pub fn index2(&self) -> Option<usize> {
-    self.index2
-  }
-
-  pub fn index3(&self) -> Option<usize> {
-    self.index3
+  pub fn labels(&self) -> &Vec<(String, usize)> {
+    &self.labels
   }
 
-  pub fn index4(&self) -> Option<usize> {
-    self.index4
-  }
-
-  pub fn index5(&self) -> Option<usize> {
-    self.index5
+  pub fn index(&self, name: &str) -> Option<usize> {
+    self.labels.iter().find(|(label, _)| label == name).map(|(_, index)| *index)
   }
 }
 
 pub trait Attribute: Any {
-  fn as_function(&self) -> Option<&Function> {
-    None
-  }
-
   fn as_vec(&self) -> Option<&Vec<u8>> {
     None
   }
@@ -445,6 +437,10 @@ pub trait Attribute: Any {
   fn as_rust_action(&self) -> Option<&RustAction> {
     None
   }
+
+  fn as_labels(&self) -> Option<&Labels> {
+    None
+  }
 }
 
 impl Debug for dyn Attribute {
@@ -453,12 +449,6 @@ impl Debug for dyn Attribute {
   }  
 }
 
-impl Attribute for Function {
-  fn as_function(&self) -> Option<&Function> {
-    Some(self)
-  }
-}
-
 impl Attribute for Vec<u8> {
   fn as_vec(&self) -> Option<&Vec<u8>> {
     Some(self)
@@ -483,6 +473,12 @@ impl Attribute for RustAction {
   }
 }
 
+impl Attribute for Labels {
+  fn as_labels(&self) -> Option<&Labels> {
+    Some(self)
+  }
+}
+
 
 ///
 /// Attributes
@@ -497,14 +493,16 @@ impl Clone for Attributes {
     let mut attrs = Attributes::new();
     for (name, attr) in &self.attrs {
       let type_id = attr.as_ref().type_id();
-      if type_id == TypeId::of::<Function>() {
-        attrs.insert(*name, Box::new(attr.as_function().unwrap().clone()));
-      } else if type_id == TypeId::of::<Vec<u8>>() {
+      if type_id == TypeId::of::<Vec<u8>>() {
         attrs.insert(*name, Box::new(attr.as_vec().unwrap().clone()));
       } else if type_id == TypeId::of::<String>() {
         attrs.insert(*name, Box::new(attr.as_string().unwrap().clone()));
+      } else if type_id == TypeId::of::<usize>() {
+        attrs.insert(*name, Box::new(attr.as_usize().unwrap()));
       } else if type_id == TypeId::of::<RustAction>() {
         attrs.insert(*name, Box::new(attr.as_rust_action().unwrap().clone()));
+      } else if type_id == TypeId::of::<Labels>() {
+        attrs.insert(*name, Box::new(attr.as_labels().unwrap().clone()));
       }
     }
     attrs
@@ -637,6 +635,11 @@ impl GrammarProduction {
     self.attrs.get(key)
   }
 
+  /// Returns the index of the labeled symbol counted from the rightmost symbol.
+  pub fn label(&self, name: &str) -> Option<usize> {
+    self.attr(&hash("labels")).and_then(|attr| attr.as_labels()).and_then(|labels| labels.index(name))
+  }
+
   pub fn find(&self, name: usize) -> Vec<usize> {
     let mut result: Vec<usize> = Vec::new();
     for i in 0..self.symbols.len() {
@@ -649,19 +652,181 @@ impl GrammarProduction {
   }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Associativity {
+  Left,
+  Right,
+  NonAssoc
+}
+
+///
+/// Precedence of terminal declared by `%left`, `%right` or `%nonassoc`
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Precedence {
+  level: usize,
+  assoc: Associativity
+}
+
+impl Precedence {
+  pub fn new(level: usize, assoc: Associativity) -> Self {
+    Self {
+      level,
+      assoc
+    }
+  }
+
+  /// Level of precedence, the later declaration has the higher level.
+  pub fn level(&self) -> usize {
+    self.level
+  }
+
+  pub fn assoc(&self) -> &Associativity {
+    &self.assoc
+  }
+}
+
 ///
 /// Grammar
 ///
 #[derive(Debug, Clone)]
 pub struct Grammar {
-  productions: Vec<Rc<GrammarProduction>>
+  productions: Vec<Rc<GrammarProduction>>,
+  precedences: HashMap<usize, Precedence, BuildUsizeHasher>
 }
 
 impl Grammar {
   pub fn new() -> Grammar {
     Grammar {
-      productions: Vec::new()
+      productions: Vec::new(),
+      precedences: HashMap::default()
+    }
+  }
+
+  pub fn set_precedence(&mut self, name: usize, precedence: Precedence) {
+    self.precedences.insert(name, precedence);
+  }
+
+  pub fn precedence(&self, name: usize) -> Option<&Precedence> {
+    self.precedences.get(&name)
+  }
+
+  /// Precedence of the production: of the terminal given by `%prec` or else of the last
+  /// terminal of the production which has precedence.
+  pub fn production_precedence(&self, production: &GrammarProduction) -> Option<&Precedence> {
+    if let Some(prec) = production.attr(&hash("prec")) {
+      if let Some(name) = prec.as_usize() {
+        return self.precedence(name);
+      }
+    }
+    production.symbols.iter().rev()
+      .filter(|symbol| symbol.is_term())
+      .find_map(|symbol| self.precedence(symbol.name()))
+  }
+
+  /// Index of the production in the grammar.
+  pub fn production_index(&self, production: &GrammarProduction) -> Option<usize> {
+    self.productions.iter().position(|item| item.as_ref() == production)
+  }
+
+  /// Writes productions with their attributes and precedences of terminals. Attributes of
+  /// type `usize` are written as names of symbols (`%prec`).
+  pub fn write(&self, writer: &mut BinaryWriter) {
+    writer.write_usize(self.productions.len());
+    for production in &self.productions {
+      writer.write_name(production.name);
+      writer.write_usize(production.symbols.len());
+      for symbol in &production.symbols {
+        writer.write_name(symbol.name);
+        writer.write_bool(symbol.is_term());
+      }
+      let attrs = &production.attrs.attrs;
+      writer.write_usize(attrs.len());
+      for (key, attr) in attrs {
+        writer.write_name(*key);
+        if let Some(value) = attr.as_vec() {
+          writer.write_usize(0);
+          writer.write_bytes(value);
+        } else if let Some(value) = attr.as_string() {
+          writer.write_usize(1);
+          writer.write_str(value);
+        } else if let Some(value) = attr.as_usize() {
+          writer.write_usize(2);
+          writer.write_name(value);
+        } else if let Some(value) = attr.as_rust_action() {
+          writer.write_usize(3);
+          value.write(writer);
+        } else if let Some(value) = attr.as_labels() {
+          writer.write_usize(4);
+          writer.write_usize(value.labels.len());
+          for (label, index) in &value.labels {
+            writer.write_str(label);
+            writer.write_usize(*index);
+          }
+        }
+      }
+    }
+    writer.write_usize(self.precedences.len());
+    for (name, precedence) in &self.precedences {
+      writer.write_name(*name);
+      writer.write_usize(precedence.level);
+      writer.write_usize(match precedence.assoc {
+        Associativity::Left => 0,
+        Associativity::Right => 1,
+        Associativity::NonAssoc => 2
+      });
+    }
+  }
+
+  pub fn read(reader: &mut BinaryReader) -> Result<Grammar, Error> {
+    let invalid = |message: String| Error::InvalidBinary {message};
+    let mut grammar = Grammar::new();
+    let len = reader.read_usize()?;
+    for _ in 0..len {
+      let mut production = GrammarProduction::new(reader.read_name()?, None);
+      let count = reader.read_usize()?;
+      for _ in 0..count {
+        let name = reader.read_name()?;
+        production.push_symbol(match reader.read_bool()? {
+          true => GrammarSymbol::term(name, None),
+          false => GrammarSymbol::non_term(name, None)
+        });
+      }
+      let count = reader.read_usize()?;
+      for _ in 0..count {
+        let key = reader.read_name()?;
+        let attr: Box<dyn Attribute> = match reader.read_usize()? {
+          0 => Box::new(reader.read_bytes()?),
+          1 => Box::new(reader.read_string()?),
+          2 => Box::new(reader.read_name()?),
+          3 => Box::new(RustAction::read(reader)?),
+          4 => {
+            let count = reader.read_usize()?;
+            let mut labels = vec!();
+            for _ in 0..count {
+              labels.push((reader.read_string()?, reader.read_usize()?));
+            }
+            Box::new(Labels::new(labels))
+          },
+          value => return Err(invalid(format!("Invalid type of attribute {}", value)))
+        };
+        production.add_attr(key, attr);
+      }
+      grammar.push_production(Rc::new(production));
+    }
+    let len = reader.read_usize()?;
+    for _ in 0..len {
+      let name = reader.read_name()?;
+      let level = reader.read_usize()?;
+      let assoc = match reader.read_usize()? {
+        0 => Associativity::Left,
+        1 => Associativity::Right,
+        2 => Associativity::NonAssoc,
+        value => return Err(invalid(format!("Invalid associativity {}", value)))
+      };
+      grammar.set_precedence(name, Precedence::new(level, assoc));
     }
+    Ok(grammar)
   }
 
   pub fn len(&self) -> usize {
@@ -808,6 +973,26 @@ impl Clone for LRItem {
   }
 }
 
+impl fmt::Display for LRItem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = |name: usize| get_original_name(name).unwrap_or_default();
+    write!(f, "{}:", name(self.production.name()))?;
+    for i in 0..self.production.len() {
+      if i == self.position {write!(f, " .")?}
+      let symbol = self.production.symbol(i);
+      match symbol.is_term() {
+        true => write!(f, " '{}'", name(symbol.name()))?,
+        false => write!(f, " {}", name(symbol.name()))?
+      }
+    }
+    if self.position == self.production.len() {write!(f, " .")?}
+    if let Some(term_name) = self.term_name {
+      write!(f, ", '{}'", name(term_name))?;
+    }
+    Ok(())
+  }
+}
+
 impl LRItem {
   pub fn new(position: usize, production: Rc<GrammarProduction>, term_name: Option<usize>) -> LRItem {
     LRItem  {
@@ -1290,7 +1475,7 @@ impl ASTBuilder {
 
 
   pub fn add_leaf(&mut self, name: String, value: Vec<u8>,
-    attrs: Option<Attributes>) -> Result<Rc<dyn ASTItem>, JsValue> {
+    attrs: Option<Attributes>) -> Result<Rc<dyn ASTItem>, String> {
     let leaf: Rc<dyn ASTItem> = Rc::new(ASTLeaf::new(name, value, attrs));
     let id = leaf.id();
     self.insert_index(id, leaf);
@@ -1298,13 +1483,13 @@ impl ASTBuilder {
   }
 
   pub fn add_leaf_id(&mut self, name: String, value: Vec<u8>,
-    attrs: Option<Attributes>) -> Result<usize, JsValue> {
+    attrs: Option<Attributes>) -> Result<usize, String> {
     let leaf = self.add_leaf(name, value, attrs)?;
     Ok(leaf.id())
   }
 
   pub fn add_node(&mut self, name: String, left: Rc<dyn ASTItem>, right: Option<Rc<dyn ASTItem>>,
-    attrs: Option<Attributes>) -> Result<Rc<dyn ASTItem>, JsValue> {
+    attrs: Option<Attributes>) -> Result<Rc<dyn ASTItem>, String> {
     let node: Rc<dyn ASTItem> = Rc::new(ASTNode::new(name, left.clone(), right.clone(), attrs));
     let id = node.id();
     self.set_last_id(Some(id));
@@ -1313,14 +1498,14 @@ impl ASTBuilder {
   }
 
   pub fn add_node_id(&mut self, name: String, left_id: usize, right_id: Option<usize>,
-    attrs: Option<Attributes>) -> Result<usize, JsValue> {
+    attrs: Option<Attributes>) -> Result<usize, String> {
       let left = self.by_id(left_id);
-      if left.is_none() {return Err(JsValue::from(format!("Unknown left id: {}", left_id)));}
+      if left.is_none() {return Err(format!("Unknown left id: {}", left_id));}
       let left = left.unwrap();
       let right = match right_id {
         Some(right_id) => {
           let right = self.by_id(right_id);
-          if right.is_none() {return Err(JsValue::from(format!("Unknown right id: {}", right_id)));}
+          if right.is_none() {return Err(format!("Unknown right id: {}", right_id));}
           right
         },
         _ => None
@@ -1339,9 +1524,3 @@ impl Debug for dyn ParserExecContext {
     Ok(())
   }
 }
-
-impl ParserExecContext for JsValue {
-  fn builder(&mut self) -> &mut ASTBuilder {
-    panic!("Not implemented")
-  }
-}
@@ -0,0 +1,99 @@
+use server::utils::*;
+use server::error::*;
+use server::parser::*;
+use server::binary::*;
+
+mod fixtures;
+
+#[test]
+fn test_tables_bytes() {
+  let tables = Tables::build(fixtures::reg_exp(), fixtures::grammar(), ParserType::LALR1).expect("Error in build");
+  let data = tables.to_bytes();
+  let tables1 = Tables::from_bytes(&data).expect("Error in from_bytes");
+
+  assert_eq!(format!("{:?}", tables1.lex_states()), format!("{:?}", tables.lex_states()), "Invalid states of lexer");
+  assert_eq!(format!("{:?}", tables1.lex_goto_states()), format!("{:?}", tables.lex_goto_states()),
+    "Invalid goto states of lexer");
+  assert_eq!(tables1.grammar().len(), tables.grammar().len(), "Invalid number of productions");
+  for i in 0..tables.grammar().len() {
+    assert_eq!(tables1.grammar().production(i), tables.grammar().production(i), "Invalid production {}", i);
+  }
+  assert_eq!(format!("{:?}", tables1.goto_states()), format!("{:?}", tables.goto_states()),
+    "Invalid goto states of parser");
+  assert_eq!(format!("{:?}", tables1.action_states()), format!("{:?}", tables.action_states()),
+    "Invalid action states of parser");
+  assert_eq!(tables1.to_bytes(), data, "Invalid bytes of loaded tables");
+}
+
+#[test]
+fn test_labels_bytes() {
+  let tables = Tables::build(fixtures::reg_exp(), fixtures::labels_grammar(), ParserType::LALR1).expect("Error in build");
+  let tables1 = Tables::from_bytes(&tables.to_bytes()).expect("Error in from_bytes");
+  for i in 0..tables.grammar().len() {
+    let production = tables1.grammar().production(i);
+    for label in ["left", "op", "right", "value"] {
+      assert_eq!(production.label(label), tables.grammar().production(i).label(label), "Invalid label {} of production {}", label, i);
+    }
+  }
+}
+
+#[test]
+fn test_rust_action_bytes() {
+  let tables = Tables::build(fixtures::reg_exp(), fixtures::rust_action_grammar(), ParserType::LALR1).expect("Error in build");
+  let data = tables.to_bytes();
+  let tables1 = Tables::from_bytes(&data).expect("Error in from_bytes");
+  for i in 0..tables.grammar().len() {
+    let action = |tables: &Tables| tables.grammar().production(i).attr(&hash("rust_action"))
+      .and_then(|action| action.as_rust_action()).map(|action| action.statements().clone());
+    assert_eq!(action(&tables1), action(&tables), "Invalid rust action of production {}", i);
+  }
+  assert_eq!(tables1.to_bytes(), data, "Invalid bytes of loaded tables");
+}
+
+#[test]
+fn test_modes_bytes() {
+  let tables = Tables::build(fixtures::modes_reg_exp(), fixtures::modes_grammar(), ParserType::LALR1).expect("Error in build");
+  let data = tables.to_bytes();
+  let tables1 = Tables::from_bytes(&data).expect("Error in from_bytes");
+  for mode in ["INITIAL", "string", "comment"] {
+    let state = tables.lex_goto_states().mode_state(hash(mode));
+    assert_eq!(state.is_some(), true, "Not found start state of mode {}", mode);
+    assert_eq!(tables1.lex_goto_states().mode_state(hash(mode)), state, "Invalid start state of mode {}", mode);
+  }
+  assert_eq!(tables1.to_bytes(), data, "Invalid bytes of loaded tables");
+}
+
+#[test]
+fn test_lex_alternatives_bytes() {
+  let tables = Tables::build(fixtures::hex_reg_exp(), fixtures::hex_grammar(), ParserType::LALR1).expect("Error in build");
+  let data = tables.to_bytes();
+  let tables1 = Tables::from_bytes(&data).expect("Error in from_bytes");
+  let alternatives = |tables: &Tables| tables.lex_states().iter().flatten()
+    .map(|state| state.alternatives().iter().map(|(accept, _)| *accept).collect::<Vec<usize>>()).collect::<Vec<_>>();
+  assert_eq!(alternatives(&tables).contains(&vec!(hash("hexdig"))), true, "Not found alternative token");
+  assert_eq!(alternatives(&tables1), alternatives(&tables), "Invalid alternative tokens of lexer");
+  assert_eq!(tables1.to_bytes(), data, "Invalid bytes of loaded tables");
+}
+
+#[test]
+fn test_glr_bytes() {
+  let tables = Tables::build(fixtures::reg_exp(), fixtures::glr_grammar(), ParserType::GLR).expect("Error in build");
+  let data = tables.to_bytes();
+  for _ in 0..5 {
+    let tables1 = Tables::build(fixtures::reg_exp(), fixtures::glr_grammar(), ParserType::GLR).expect("Error in build");
+    assert_eq!(tables1.to_bytes(), data, "Invalid bytes of the same tables");
+  }
+  let tables1 = Tables::from_bytes(&data).expect("Error in from_bytes");
+  assert_eq!(tables1.to_bytes(), data, "Invalid bytes of loaded tables");
+}
+
+#[test]
+fn test_invalid_bytes() {
+  let result = Tables::from_bytes(b"data");
+  assert_eq!(matches!(result, Err(Error::InvalidBinary {..})), true, "Invalid result of wrong header");
+
+  let tables = Tables::build(fixtures::reg_exp(), fixtures::grammar(), ParserType::LALR1).expect("Error in build");
+  let data = tables.to_bytes();
+  let result = Tables::from_bytes(&data[..data.len() / 2]);
+  assert_eq!(matches!(result, Err(Error::InvalidBinary {..})), true, "Invalid result of truncated data");
+}
@@ -0,0 +1,89 @@
+use server::error::*;
+use server::parser::*;
+use server::binary::*;
+use server::codegen::*;
+
+mod fixtures;
+
+#[path = "generated/calc.rs"]
+mod calc;
+
+#[path = "generated/hex.rs"]
+mod hex;
+
+///
+/// Actions of the generated module of `reg_exp` and `grammar` which calculate the expression.
+///
+struct CalcActions;
+
+impl calc::Actions for CalcActions {
+  fn lex_action(&mut self, _symbol: usize, action: &str, _value: &mut Vec<u8>) -> Result<Option<usize>, String> {
+    let name = action.trim_start_matches("set_name('").trim_end_matches("')");
+    Ok(calc::symbol(name))
+  }
+
+  fn reduce(&mut self, _production: usize, _action: Option<&str>, values: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    fn get(value: &[u8]) -> i64 {
+      String::from_utf8_lossy(value).parse().unwrap()
+    }
+
+    let value = match values.len() {
+      3 if values[1] == b"+" => get(&values[0]) + get(&values[2]),
+      3 if values[1] == b"*" => get(&values[0]) * get(&values[2]),
+      3 => get(&values[1]),
+      _ => get(&values[0])
+    };
+    Ok(value.to_string().into_bytes())
+  }
+}
+
+impl hex::Actions for CalcActions {
+  fn lex_action(&mut self, _symbol: usize, action: &str, _value: &mut Vec<u8>) -> Result<Option<usize>, String> {
+    let name = action.trim_start_matches("set_name('").trim_end_matches("')");
+    Ok(hex::symbol(name))
+  }
+
+  fn reduce(&mut self, production: usize, action: Option<&str>, values: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    calc::Actions::reduce(self, production, action, values)
+  }
+}
+
+#[test]
+fn test_generate_rust() {
+  let tables = Tables::build(fixtures::reg_exp(), fixtures::grammar(), ParserType::LALR1).expect("Error in build");
+  let code = generate_rust(&tables).expect("Error in generation");
+  assert_eq!(code.contains("pub static PRODUCTIONS: [(usize, usize); 8]"), true, "Invalid table of productions");
+  assert_eq!(code.contains("pub fn parse<A: Actions>"), true, "Not found parse function");
+  assert_eq!(code, include_str!("generated/calc.rs"), "Generated module is out of date");
+
+  let tables = Tables::build(fixtures::reg_exp(), fixtures::rust_action_grammar(), ParserType::LALR1).expect("Error in build");
+  let code = generate_rust(&tables).expect("Error in generation");
+  assert_eq!(code, include_str!("generated/hex.rs"), "Generated module is out of date");
+
+  let unsupported = |reg_exp: String, grammar: String, parser_type: ParserType| {
+    let tables = Tables::build(reg_exp, grammar, parser_type).expect("Error in build");
+    matches!(generate_rust(&tables), Err(Error::Unsupported {..}))
+  };
+  assert_eq!(unsupported(fixtures::reg_exp(), fixtures::glr_grammar(), ParserType::GLR), true,
+    "Invalid generation of GLR parser");
+  assert_eq!(unsupported(fixtures::modes_reg_exp(), fixtures::modes_grammar(), ParserType::LALR1), true,
+    "Invalid generation of lexer modes");
+  assert_eq!(unsupported(fixtures::netstring_reg_exp(), fixtures::lines_grammar(), ParserType::LALR1), true,
+    "Invalid generation of push_after");
+}
+
+#[test]
+fn test_generated_parser() {
+  let result = calc::parse(b"(1+2)*3+4", &mut CalcActions);
+  assert_eq!(result, Ok(b"13".to_vec()), "Invalid result of calculation");
+
+  let result = calc::parse(b"(1+)", &mut CalcActions);
+  assert_eq!(result, Err(calc::Error::UnexpectedSymbol {state: 7, symbol: calc::symbol(")").unwrap(), offset: 3}),
+    "Invalid result of unexpected symbol");
+
+  let result = hex::parse(b"(10+2)*3", &mut CalcActions);
+  assert_eq!(result, Ok(b"54".to_vec()), "Invalid result of calculation");
+  let result = hex::parse(b"2*99999999999999999999", &mut CalcActions);
+  assert_eq!(result, Err(hex::Error::Action {message: "Invalid number '99999999999999999999'".to_string()}),
+    "Invalid result of action");
+}
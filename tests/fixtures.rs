@@ -1,3 +1,9 @@
+use server::utils::*;
+use server::action::*;
+use server::error::*;
+use server::parser::*;
+use server::lex::IntFormat;
+
 #[allow(dead_code)]
 pub fn reg_exp() -> String {
   "
@@ -53,6 +59,32 @@ pub fn not_lalr1_grammar2() -> String {
   ".to_string()
 }
 
+#[allow(dead_code)]
+pub fn ambiguous_grammar() -> String {
+  "
+    S1: E;
+    E: E '+' E | 'id';
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn precedence_reg_exp() -> String {
+  reg_exp() + "
+    less          <   {set_name('<')}
+  "
+}
+
+#[allow(dead_code)]
+pub fn precedence_grammar() -> String {
+  "
+    %left '+';
+    %left '*';
+    %nonassoc '<';
+    S1: E;
+    E: E '+' E | E '*' E | E '<' E | '(' E ')' | 'number';
+  ".to_string()
+}
+
 #[allow(dead_code)]
 pub fn not_lalr1_grammar3() -> String {
   "
@@ -61,4 +93,534 @@ pub fn not_lalr1_grammar3() -> String {
     A: 'd';
     B: 'd';
   ".to_string()
-}
\ No newline at end of file
+}
+
+/// Grammar with the nonterminal D which derives no terminal strings.
+#[allow(dead_code)]
+pub fn unproductive_grammar() -> String {
+  "
+    S1: S;
+    S: D;
+    S: 'a' 'a' 'b';
+    S: ;
+    A: B 'a' 'd';
+    A: ;
+    B: S;
+    B: 'a' D B;
+    C: ;
+    D: D D D;
+    E: D B C;
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn glr_grammar() -> String {
+  "
+    S1: E;
+    E: E '+' E | E '*' E | '(' E ')' | 'number';
+  ".to_string()
+}
+
+///
+/// Native lexer actions of `reg_exp`.
+///
+#[allow(dead_code)]
+pub struct CalcLexActions;
+
+impl LexActions for CalcLexActions {
+  fn lex_action(&mut self, tkn_name: usize, _action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    if tkn_name == hash("plus") {ctx.set_name("+")}
+    else if tkn_name == hash("mul") {ctx.set_name("*")}
+    else if tkn_name == hash("lbracket") {ctx.set_name("(")}
+    else if tkn_name == hash("rbracket") {ctx.set_name(")")}
+    else if tkn_name == hash("less") {ctx.set_name("<")}
+    Ok(())
+  }
+}
+
+impl ParserActions for CalcLexActions {}
+
+///
+/// Native actions of `grammar` which calculate the expression.
+///
+#[allow(dead_code)]
+pub struct CalcActions {
+  pub result: Option<i64>
+}
+
+impl LexActions for CalcActions {
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    CalcLexActions.lex_action(tkn_name, action, ctx)
+  }
+}
+
+impl ParserActions for CalcActions {
+  fn parser_action(&mut self, production: &GrammarProduction, _action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    fn get(ctx: &dyn ParserActionContext, index: usize) -> i64 {
+      let value = String::from_utf8(ctx.get(index).unwrap().to_vec()).unwrap();
+      value.parse().unwrap()
+    }
+
+    let value = match production.len() {
+      3 if production.symbol(1).name() == hash("+") => get(ctx, 2) + get(ctx, 0),
+      3 if production.symbol(1).name() == hash("*") => get(ctx, 2) * get(ctx, 0),
+      3 => get(ctx, 1),
+      _ => get(ctx, 0)
+    };
+    ctx.set_val(value.to_string().into_bytes());
+    if production.name() == hash("E") {
+      self.result = Some(value);
+    }
+    Ok(())
+  }
+}
+
+#[allow(dead_code)]
+pub fn statements_reg_exp() -> String {
+  reg_exp() + "
+    semicolon     ;   {set_name(';')}
+  "
+}
+
+#[allow(dead_code)]
+pub fn statements_grammar() -> String {
+  "
+    S1: L;
+    L: L M | M;
+    M: E ';' | error ';';
+    E: E '+' T | T;
+    T: T '*' F | F;
+    F: '(' E ')' | 'id' | 'number';
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn glr_lookup_grammar() -> String {
+  "
+    S1: L;
+    L: T '*' 'number';
+    T: E [set(lookup())];
+    E: E '+' E | 'number';
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn cyclic_grammar() -> String {
+  "
+    S1: S;
+    S: S;
+    S: A;
+    A: 'b' 'b';
+    A: 'b' B S;
+    A: 'b';
+    B: 'a' 'b';
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn ebnf_grammar() -> String {
+  "
+    S1: list;
+    list: '(' (item % ';')? ')';
+    item: 'number' (('+' | '*') 'number')* [set(1, 0)];
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn labels_grammar() -> String {
+  "
+    S1: E;
+    E: left=E op='+' right=T;
+    E: value=T [set(value)];
+    T: left=T op='*' right=F;
+    T: value=F [set(value)];
+    F: '(' value=E ')' [set(value)];
+    F: value='number' [set(value)];
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn rust_action_grammar() -> String {
+  "
+    E1: E;
+    E: E '+' T | T;
+    T: T '*' F | F;
+    F: '(' E ')';
+    F: number='number' [if empty(number) {set('0')} else {set(parse_hex(number))}];
+  ".to_string()
+}
+
+///
+/// Native actions of `statements_grammar` which count statements and syntax errors.
+///
+#[allow(dead_code)]
+pub struct StatementActions {
+  pub statements: usize,
+  pub errors: Vec<Error>
+}
+
+impl LexActions for StatementActions {
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    if tkn_name == hash("semicolon") {
+      ctx.set_name(";");
+      return Ok(());
+    }
+    CalcLexActions.lex_action(tkn_name, action, ctx)
+  }
+}
+
+impl ParserActions for StatementActions {
+  fn parser_action(&mut self, production: &GrammarProduction, _action: Option<&str>,
+    _ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    if production.name() == hash("M") {
+      self.statements += 1;
+    }
+    Ok(())
+  }
+
+  fn parse_error(&mut self, err: &Error) -> Result<(), String> {
+    self.errors.push(err.clone());
+    Ok(())
+  }
+}
+
+///
+/// Native actions of `glr_grammar` which collect the parse trees.
+///
+#[allow(dead_code)]
+pub struct GLRActions {
+  pub trees: Vec<ParseTree>
+}
+
+impl LexActions for GLRActions {
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    CalcLexActions.lex_action(tkn_name, action, ctx)
+  }
+}
+
+impl ParserActions for GLRActions {
+  fn parse_trees(&mut self, trees: Vec<ParseTree>) -> Result<(), String> {
+    self.trees = trees;
+    Ok(())
+  }
+}
+
+///
+/// Native actions of `glr_lookup_grammar` which keep the value of `T`.
+///
+#[allow(dead_code)]
+pub struct LookupActions {
+  pub result: Option<String>
+}
+
+impl LexActions for LookupActions {
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    CalcLexActions.lex_action(tkn_name, action, ctx)
+  }
+}
+
+impl ParserActions for LookupActions {
+  fn parser_action(&mut self, production: &GrammarProduction, _action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    if production.name() == hash("L") {
+      self.result = ctx.get(2).map(|value| String::from_utf8_lossy(value).to_string());
+    }
+    Ok(())
+  }
+}
+
+///
+/// Native actions of `ebnf_grammar` which keep the value of the list.
+///
+#[allow(dead_code)]
+pub struct EBNFActions {
+  pub result: Option<String>
+}
+
+impl LexActions for EBNFActions {
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    if tkn_name == hash("semicolon") {
+      ctx.set_name(";");
+      return Ok(());
+    }
+    CalcLexActions.lex_action(tkn_name, action, ctx)
+  }
+}
+
+impl ParserActions for EBNFActions {
+  fn parser_action(&mut self, production: &GrammarProduction, _action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    if production.name() == hash("list") {
+      self.result = ctx.get(1).map(|value| String::from_utf8_lossy(value).to_string());
+    }
+    Ok(())
+  }
+}
+
+///
+/// Native actions of `labels_grammar` which calculate the expression by the labels of symbols.
+///
+#[allow(dead_code)]
+pub struct LabelActions {
+  pub result: Option<i64>
+}
+
+impl LexActions for LabelActions {
+  fn lex_action(&mut self, tkn_name: usize, action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    CalcLexActions.lex_action(tkn_name, action, ctx)
+  }
+}
+
+impl ParserActions for LabelActions {
+  fn parser_action(&mut self, production: &GrammarProduction, _action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    let get = |label: &str| -> Result<i64, String> {
+      let index = production.label(label).ok_or(format!("Not found label {}", label))?;
+      let value = String::from_utf8(ctx.get(index).unwrap_or_default().to_vec()).unwrap();
+      value.parse().map_err(|_| format!("Invalid value {}", value))
+    };
+
+    let index = production.label("op").ok_or("Not found label op")?;
+    let value = match ctx.get(index) {
+      Some(b"+") => get("left")? + get("right")?,
+      _ => get("left")? * get("right")?
+    };
+    ctx.set_val(value.to_string().into_bytes());
+    if production.name() == hash("E") {
+      self.result = Some(value);
+    }
+    Ok(())
+  }
+}
+
+#[allow(dead_code)]
+pub fn netstring_reg_exp() -> String {
+  "
+    number        [0-9]+
+    colon         :
+    comma         \\,
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn netstring_grammar() -> String {
+  "
+    S1: items;
+    items: items item | item;
+    item: size 'colon' 'data' 'comma' [set(1)];
+    size: 'number' [read_bytes(0, 'data')];
+  ".to_string()
+}
+
+///
+/// Native actions of `netstring_grammar` which collect values of the items.
+///
+#[allow(dead_code)]
+pub struct NetstringActions {
+  pub items: Vec<Vec<u8>>
+}
+
+impl LexActions for NetstringActions {}
+
+impl ParserActions for NetstringActions {
+  fn parser_action(&mut self, _production: &GrammarProduction, _action: Option<&str>,
+    ctx: &mut dyn ParserActionContext) -> Result<(), String> {
+    self.items.push(ctx.get(0).unwrap_or_default().to_vec());
+    Ok(())
+  }
+}
+
+#[allow(dead_code)]
+pub fn classes_reg_exp() -> String {
+  "
+    number        \\d+
+    word          [[:alpha:]_]\\w*
+    space         \\s+
+    punct         [[:punct:]]
+    comment       #.*
+    other         [^\\s[:punct:][:alnum:]]+
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn unicode_reg_exp() -> String {
+  "
+    word          [а-яё]+
+    emoji         [\\U0001F600-\\U0001F64F]
+    space         \\s+
+    other         [^а-яё\\s]+
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn icase_reg_exp() -> String {
+  "
+    method        GET|POST          ICASE
+    host          host:             ICASE
+    letter        [a-zа-я]          DEF ICASE
+    word          {letter}+
+    version       (?i:v)\\d+
+    space         \\s+
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn modes_reg_exp() -> String {
+  "
+    <*>newline            \\x0a
+    word                  [a-z]+
+    space                 \\x20+
+    quote                 \"                  {push_mode('string')}
+    comment_start         /\\*                {begin('comment')}
+    <string>text          [^\"\\\\\\x0a]+
+    <string>escape        \\\\.
+    <string>close         \"                  {pop_mode()}
+    <comment>comment_text [^*]+|\\*
+    <comment>comment_end  \\*/               {begin('INITIAL')}
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn modes_grammar() -> String {
+  "
+    S1: items;
+    items: items item | item;
+    item: 'word' | 'space' | 'newline' | string | comment;
+    string: 'quote' parts 'close';
+    parts: parts part | part;
+    part: 'text' | 'escape' | 'newline';
+    comment: 'comment_start' comment_parts 'comment_end';
+    comment_parts: comment_parts 'comment_text' | 'comment_text';
+  ".to_string()
+}
+
+///
+/// Native lexer actions of `modes_reg_exp` which switch the modes of the lexer.
+///
+#[allow(dead_code)]
+pub struct ModeLexActions;
+
+impl LexActions for ModeLexActions {
+  fn lex_action(&mut self, tkn_name: usize, _action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    if tkn_name == hash("quote") {ctx.push_mode("string")}
+    else if tkn_name == hash("close") {ctx.pop_mode()}
+    else if tkn_name == hash("comment_start") {ctx.begin("comment")}
+    else if tkn_name == hash("comment_end") {ctx.begin("INITIAL")}
+    Ok(())
+  }
+}
+
+impl ParserActions for ModeLexActions {}
+
+#[allow(dead_code)]
+pub fn headers_reg_exp() -> String {
+  "
+    <*>space              \\x20+
+    <*>newline            \\x0a
+    name                  [a-z]+
+    colon                 :
+    <value>value          [^\\x20\\x0a][^\\x0a]*
+  ".to_string()
+}
+
+/// The value of the header is read in the mode `value`, the mode is switched by the
+/// action of the grammar before the token after the space.
+#[allow(dead_code)]
+pub fn headers_grammar() -> String {
+  "
+    S1: headers;
+    headers: headers header | header;
+    header: key 'space' header_value 'newline';
+    key: 'name' 'colon' [begin('value')];
+    header_value: 'value' [begin('INITIAL'); set(0)];
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn hex_reg_exp() -> String {
+  "
+    zero          0
+    x             x
+    hexdig        [0-9A-Fa-f]
+    space         \\x20
+  ".to_string()
+}
+
+/// The digit '0' of the hexadecimal number is the token `zero` unless the parser expects
+/// `hexdig`.
+#[allow(dead_code)]
+pub fn hex_grammar() -> String {
+  "
+    S1: numbers;
+    numbers: numbers 'space' number | number;
+    number: 'zero' 'x' digits;
+    digits: digits 'hexdig' | 'hexdig';
+  ".to_string()
+}
+
+#[allow(dead_code)]
+pub fn binary_reg_exp() -> String {
+  "
+    publish       \\x30
+    short         \\x31
+  ".to_string()
+}
+
+///
+/// Native lexer actions of `binary_reg_exp` which read the length of the short packet
+/// and the payload of the publish packet.
+///
+#[allow(dead_code)]
+pub struct BinaryLexActions;
+
+impl LexActions for BinaryLexActions {
+  fn lex_action(&mut self, tkn_name: usize, _action: Option<&str>,
+    ctx: &mut dyn LexActionContext) -> Result<(), String> {
+    if tkn_name == hash("publish") {ctx.read_blob("payload", IntFormat::VarInt)}
+    else if tkn_name == hash("short") {ctx.read_int("length", IntFormat::U16BE)}
+    Ok(())
+  }
+}
+
+impl ParserActions for BinaryLexActions {}
+
+#[allow(dead_code)]
+pub fn lines_grammar() -> String {
+  "
+    S1: items;
+    items: items item | item;
+    item: key 'colon' 'text';
+    key: 'number' [push_after('', 'text', none, none, '\\r\\n')];
+  ".to_string()
+}
+
+///
+/// Actions which collect the data of tokens read by size or up to the delimiter.
+///
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct DataActions {
+  pub data: Vec<u8>,
+  pub parts: Vec<Vec<u8>>
+}
+
+impl LexActions for DataActions {
+  fn tkn_data(&mut self, _tkn_name: usize, data: &[u8], end: bool) -> Result<(), String> {
+    self.data.extend_from_slice(data);
+    if end {
+      self.parts.push(std::mem::take(&mut self.data));
+    }
+    Ok(())
+  }
+}
+
+impl ParserActions for DataActions {}
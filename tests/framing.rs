@@ -0,0 +1,135 @@
+use server::error::*;
+use server::lex::*;
+use server::parser::*;
+use server::stream_lex::*;
+use server::framing::*;
+
+mod fixtures;
+
+fn decode(decoder: &mut dyn FrameDecoder, data: &[&[u8]]) -> Result<Vec<Frame>, Error> {
+  let mut frames = vec!();
+  for data in data {
+    frames.extend(decoder.decode(data)?);
+  }
+  Ok(frames)
+}
+
+#[test]
+fn test_chunked() {
+  let mut decoder = ChunkedDecoder::new();
+  let frames = decode(&mut decoder, &[b"4\r\nWi", b"ki\r\n5;name=value\r", b"\npedia\r\n0\r\nTrailer: 1\r\n\r\n0\r\n\r\n"])
+    .expect("Error in decode");
+  assert_eq!(frames, vec!(
+    Frame::new(b"Wi".to_vec(), false),
+    Frame::new(b"ki".to_vec(), false),
+    Frame::new(b"pedia".to_vec(), true),
+    Frame::new(vec!(), true)
+  ), "Invalid frames of chunked data");
+
+  decoder.reset();
+  let result = decoder.decode(b"4\r\nWiki!\r\n");
+  assert_eq!(result, Err(Error::InvalidFrame {offset: 10, message: "Expected CRLF after data of chunk".to_string()}),
+    "Invalid result of wrong chunk");
+  decoder.reset();
+  assert_eq!(decoder.decode(b"x\r\n").is_err(), true, "Invalid result of wrong size of chunk");
+}
+
+#[test]
+fn test_length() {
+  let mut decoder = LengthDecoder::new(IntFormat::U16BE);
+  let frames = decode(&mut decoder, &[b"\x00", b"\x03ab", b"c\x00\x00\x00\x01x"]).expect("Error in decode");
+  assert_eq!(frames, vec!(
+    Frame::new(b"ab".to_vec(), false),
+    Frame::new(b"c".to_vec(), true),
+    Frame::new(vec!(), true),
+    Frame::new(b"x".to_vec(), true)
+  ), "Invalid frames prefixed by length");
+
+  let mut decoder = Framing::U32Length.decoder();
+  let frames = decoder.decode(b"\x00\x00\x00\x02ab").expect("Error in decode");
+  assert_eq!(frames, vec!(Frame::new(b"ab".to_vec(), true)), "Invalid frames prefixed by u32 length");
+}
+
+#[test]
+fn test_newline() {
+  let mut decoder = NewlineDecoder::new();
+  let frames = decode(&mut decoder, &[b"ab\r", b"\nc\rd\n\n", b"e\r", b"f"]).expect("Error in decode");
+  assert_eq!(frames, vec!(
+    Frame::new(b"ab".to_vec(), false),
+    Frame::new(vec!(), true),
+    Frame::new(b"c\rd".to_vec(), true),
+    Frame::new(vec!(), true),
+    Frame::new(b"e".to_vec(), false),
+    Frame::new(b"\rf".to_vec(), false)
+  ), "Invalid frames ended by newline");
+}
+
+#[test]
+fn test_parse_frames() {
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+
+  let mut decoder = NewlineDecoder::new();
+  let mut results = vec!();
+  for data in ["2*(3", "+4)\n1", "0\n5+\n"] {
+    for frame in decoder.decode(data.as_bytes()).expect("Error in decode") {
+      let mut actions = fixtures::CalcActions {result: None};
+      parser.set_data(frame.data().clone());
+      let res = parser.parse(&mut actions);
+      assert_eq!(res, Ok(ParseResult::ParseWait), "Invalid result of parse: {:?}", frame);
+      if frame.end() {
+        parser.set_end_of_data();
+        let res = parser.parse(&mut actions);
+        results.push(res.map(|_| actions.result));
+      }
+    }
+  }
+  assert_eq!(results.len(), 3, "Invalid number of messages");
+  assert_eq!(results[0], Ok(Some(14)), "Invalid result of message");
+  assert_eq!(results[1], Ok(Some(10)), "Invalid result of message");
+  assert_eq!(results[2].is_err(), true, "Invalid result of message: {:?}", results[2]);
+}
+
+#[test]
+fn test_interrupt_frames() {
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+
+  let mut decoder = NewlineDecoder::new();
+  let mut queue = FrameQueue::new();
+  let mut results = vec!();
+  // parsing is interrupted after every message, the rest of frames are kept in the queue
+  let mut parse = |queue: &mut FrameQueue, results: &mut Vec<Option<i64>>| queue.parse(|frame| {
+    if frame.end() {
+      parser.set_end_of_data();
+    } else {
+      parser.set_data(frame.data().clone());
+    }
+    let mut actions = fixtures::CalcActions {result: None};
+    let res = parser.parse(&mut actions)?;
+    if res == ParseResult::ParseSuccess {
+      results.push(actions.result);
+      return Ok(true);
+    }
+    Ok::<bool, Error>(false)
+  });
+
+  queue.push(decoder.decode(b"2*3\n4+5\n6").expect("Error in decode"));
+  assert_eq!(parse(&mut queue, &mut results), Ok(true), "Invalid interruption of parse");
+  assert_eq!(results, vec!(Some(6)), "Invalid results of messages");
+  assert_eq!(queue.data(), b"4+56".to_vec(), "Invalid data of queued frames");
+  assert_eq!(parse(&mut queue, &mut results), Ok(true), "Invalid interruption of parse");
+  assert_eq!(results, vec!(Some(6), Some(9)), "Invalid results of messages");
+  assert_eq!(parse(&mut queue, &mut results), Ok(false), "Invalid interruption of parse");
+  assert_eq!(queue.has_data(), false, "Invalid data of queued frames");
+
+  queue.push(decoder.decode(b"7\n").expect("Error in decode"));
+  assert_eq!(parse(&mut queue, &mut results), Ok(true), "Invalid interruption of parse");
+  assert_eq!(results, vec!(Some(6), Some(9), Some(67)), "Invalid results of messages");
+}
@@ -0,0 +1,250 @@
+// Generated by server::codegen, do not edit.
+
+/// Names of symbols, the symbol is the index of its name.
+pub static SYMBOLS: [&str; 17] = [
+  "",
+  "$",
+  "E1",
+  "E",
+  "+",
+  "T",
+  "*",
+  "F",
+  "(",
+  ")",
+  "id",
+  "number",
+  "space",
+  "plus",
+  "mul",
+  "lbracket",
+  "rbracket",
+];
+pub const E_TERM: usize = 0;
+pub const S_TERM: usize = 1;
+
+/// Accepted symbol and action of the lexer state.
+static LEX_ACCEPT: [Option<(usize, Option<&str>)>; 16] = [
+  None,
+  Some((12, None)),
+  None,
+  None,
+  None,
+  None,
+  Some((10, None)),
+  Some((10, None)),
+  Some((11, None)),
+  Some((13, Some("set_name('+')"))),
+  Some((14, Some("set_name('*')"))),
+  Some((15, Some("set_name('(')"))),
+  Some((16, Some("set_name(')')"))),
+  None,
+  None,
+  None,
+];
+/// Transitions of the lexer state as pairs of byte and goto state.
+static LEX_GOTO: [&[(u8, usize)]; 16] = [
+  &[(9, 1), (10, 1), (11, 1), (12, 1), (13, 1), (32, 1), (40, 11), (41, 12), (42, 10), (43, 9), (48, 8), (49, 8), (50, 8), (51, 8), (52, 8), (53, 8), (54, 8), (55, 8), (56, 8), (57, 8), (65, 7), (66, 7), (67, 7), (68, 7), (69, 7), (70, 7), (71, 7), (72, 7), (73, 7), (74, 7), (75, 7), (76, 7), (77, 7), (78, 7), (79, 7), (80, 7), (81, 7), (82, 7), (83, 7), (84, 7), (85, 7), (86, 7), (87, 7), (88, 7), (89, 7), (90, 7), (95, 6), (97, 7), (98, 7), (99, 7), (100, 7), (101, 7), (102, 7), (103, 7), (104, 7), (105, 7), (106, 7), (107, 7), (108, 7), (109, 7), (110, 7), (111, 7), (112, 7), (113, 7), (114, 7), (115, 7), (116, 7), (117, 7), (118, 7), (119, 7), (120, 7), (121, 7), (122, 7), (194, 2), (225, 3), (226, 4), (227, 5)],
+  &[(9, 1), (10, 1), (11, 1), (12, 1), (13, 1), (32, 1), (194, 2), (225, 3), (226, 4), (227, 5)],
+  &[(133, 1), (160, 1)],
+  &[(154, 13)],
+  &[(128, 14), (129, 15)],
+  &[(128, 13)],
+  &[],
+  &[(48, 7), (49, 7), (50, 7), (51, 7), (52, 7), (53, 7), (54, 7), (55, 7), (56, 7), (57, 7), (65, 7), (66, 7), (67, 7), (68, 7), (69, 7), (70, 7), (71, 7), (72, 7), (73, 7), (74, 7), (75, 7), (76, 7), (77, 7), (78, 7), (79, 7), (80, 7), (81, 7), (82, 7), (83, 7), (84, 7), (85, 7), (86, 7), (87, 7), (88, 7), (89, 7), (90, 7), (95, 7), (97, 7), (98, 7), (99, 7), (100, 7), (101, 7), (102, 7), (103, 7), (104, 7), (105, 7), (106, 7), (107, 7), (108, 7), (109, 7), (110, 7), (111, 7), (112, 7), (113, 7), (114, 7), (115, 7), (116, 7), (117, 7), (118, 7), (119, 7), (120, 7), (121, 7), (122, 7)],
+  &[(48, 8), (49, 8), (50, 8), (51, 8), (52, 8), (53, 8), (54, 8), (55, 8), (56, 8), (57, 8)],
+  &[],
+  &[],
+  &[],
+  &[],
+  &[(128, 1)],
+  &[(128, 1), (129, 1), (130, 1), (131, 1), (132, 1), (133, 1), (134, 1), (135, 1), (136, 1), (137, 1), (138, 1), (168, 1), (169, 1), (175, 1)],
+  &[(159, 1)],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+  Shift(usize),
+  Reduce(usize),
+  Accept
+}
+
+/// Actions of the parser state as pairs of terminal and action.
+static ACTION: [&[(usize, Action)]; 13] = [
+  &[(8, Action::Shift(4)), (10, Action::Shift(5)), (11, Action::Shift(6))],
+  &[(1, Action::Accept), (4, Action::Shift(7))],
+  &[(1, Action::Reduce(2)), (4, Action::Reduce(2)), (6, Action::Shift(8)), (9, Action::Reduce(2))],
+  &[(1, Action::Reduce(4)), (4, Action::Reduce(4)), (6, Action::Reduce(4)), (9, Action::Reduce(4))],
+  &[(8, Action::Shift(4)), (10, Action::Shift(5)), (11, Action::Shift(6))],
+  &[(1, Action::Reduce(6)), (4, Action::Reduce(6)), (6, Action::Reduce(6)), (9, Action::Reduce(6))],
+  &[(1, Action::Reduce(7)), (4, Action::Reduce(7)), (6, Action::Reduce(7)), (9, Action::Reduce(7))],
+  &[(8, Action::Shift(4)), (10, Action::Shift(5)), (11, Action::Shift(6))],
+  &[(8, Action::Shift(4)), (10, Action::Shift(5)), (11, Action::Shift(6))],
+  &[(4, Action::Shift(7)), (9, Action::Shift(12))],
+  &[(1, Action::Reduce(1)), (4, Action::Reduce(1)), (6, Action::Shift(8)), (9, Action::Reduce(1))],
+  &[(1, Action::Reduce(3)), (4, Action::Reduce(3)), (6, Action::Reduce(3)), (9, Action::Reduce(3))],
+  &[(1, Action::Reduce(5)), (4, Action::Reduce(5)), (6, Action::Reduce(5)), (9, Action::Reduce(5))],
+];
+/// Goto states of the parser state as pairs of nonterminal and goto state.
+static GOTO: [&[(usize, usize)]; 13] = [
+  &[(3, 1), (5, 2), (7, 3)],
+  &[],
+  &[],
+  &[],
+  &[(3, 9), (5, 2), (7, 3)],
+  &[],
+  &[],
+  &[(5, 10), (7, 3)],
+  &[(7, 11)],
+  &[],
+  &[],
+  &[],
+  &[],
+];
+/// Nonterminal and length of the production.
+pub static PRODUCTIONS: [(usize, usize); 8] = [
+  (2, 1), // E1: E
+  (3, 3), // E: E '+' T
+  (3, 1), // E: T
+  (5, 3), // T: T '*' F
+  (5, 1), // T: F
+  (7, 3), // F: '(' E ')'
+  (7, 1), // F: 'id'
+  (7, 1), // F: 'number'
+];
+
+/// Executes the action of the production, returns the symbol and the value of new symbol.
+#[allow(unused_variables, unused_assignments)]
+fn reduce<A: Actions>(actions: &mut A, production: usize, values: &[Vec<u8>],
+  lookup: Option<&[u8]>) -> Result<(usize, Vec<u8>), String> {
+  let name = PRODUCTIONS[production].0;
+  Ok((name, actions.reduce(production, None, values)?))
+}
+
+/// Returns the symbol by its name.
+pub fn symbol(name: &str) -> Option<usize> {
+  SYMBOLS.iter().position(|item| *item == name)
+}
+
+fn find<T: Copy>(items: &[(usize, T)], key: usize) -> Option<T> {
+  items.binary_search_by_key(&key, |item| item.0).ok().map(|index| items[index].1)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+  /// No lexer rule matches the input starting at the byte offset.
+  LexNoMatch {offset: usize},
+  /// Symbol is not expected in the parser state.
+  UnexpectedSymbol {state: usize, symbol: usize, offset: usize},
+  /// Failure of the lexer or grammar action.
+  Action {message: String}
+}
+
+///
+/// Actions of the lexer rules and the grammar productions
+///
+pub trait Actions {
+  /// Called for the token recognized by the rule with action (the code in braces), returns
+  /// the symbol of token or `None` to skip the token.
+  #[allow(unused_variables)]
+  fn lex_action(&mut self, symbol: usize, action: &str, value: &mut Vec<u8>) -> Result<Option<usize>, String> {
+    Ok(Some(symbol))
+  }
+
+  /// Called on reduce of the production without rust action, `action` is the text of the action
+  /// in braces, `values` are the values of symbols of the production from left to right, returns
+  /// the value of new symbol.
+  #[allow(unused_variables)]
+  fn reduce(&mut self, production: usize, action: Option<&str>, values: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    Ok(vec!())
+  }
+}
+
+///
+/// Lexer of the whole input
+///
+pub struct Lexer<'a> {
+  input: &'a [u8],
+  position: usize
+}
+
+impl<'a> Lexer<'a> {
+  pub fn new(input: &'a [u8]) -> Self {
+    Self {
+      input,
+      position: 0
+    }
+  }
+
+  /// Returns the symbol, the value and the byte offset of next token, `S_TERM` at the end of input.
+  pub fn next_token<A: Actions>(&mut self, actions: &mut A) -> Result<(usize, Vec<u8>, usize), Error> {
+    loop {
+      let start = self.position;
+      if start >= self.input.len() {
+        return Ok((S_TERM, vec!(), start));
+      }
+      let mut state = 0;
+      let mut accepted = None;
+      for position in start..self.input.len() {
+        let transitions = LEX_GOTO[state];
+        match transitions.binary_search_by_key(&self.input[position], |item| item.0) {
+          Ok(index) => state = transitions[index].1,
+          Err(_) => break
+        }
+        if let Some(accept) = LEX_ACCEPT[state] {
+          accepted = Some((accept, position + 1));
+        }
+      }
+      let ((symbol, action), end) = accepted.ok_or(Error::LexNoMatch {offset: start})?;
+      self.position = end;
+      let mut value = self.input[start..end].to_vec();
+      let symbol = match action {
+        Some(action) => actions.lex_action(symbol, action, &mut value).map_err(|message| Error::Action {message})?,
+        _ => Some(symbol)
+      };
+      if let Some(symbol) = symbol {
+        return Ok((symbol, value, start));
+      }
+    }
+  }
+}
+
+/// Parses the input, returns the value of the start symbol.
+pub fn parse<A: Actions>(input: &[u8], actions: &mut A) -> Result<Vec<u8>, Error> {
+  let mut lexer = Lexer::new(input);
+  let mut stack: Vec<(usize, Vec<u8>)> = vec!((0, vec!()));
+  let (mut symbol, mut value, mut offset) = lexer.next_token(actions)?;
+  loop {
+    let state = stack[stack.len() - 1].0;
+    let (action, is_e_symbol) = match find(ACTION[state], symbol) {
+      Some(action) => (action, false),
+      _ => match find(ACTION[state], E_TERM) {
+        Some(action) => (action, true),
+        _ => return Err(Error::UnexpectedSymbol {state, symbol, offset})
+      }
+    };
+    match action {
+      Action::Shift(goto) => {
+        if is_e_symbol {
+          stack.push((goto, vec!()));
+        } else {
+          stack.push((goto, std::mem::take(&mut value)));
+          let token = lexer.next_token(actions)?;
+          symbol = token.0;
+          value = token.1;
+          offset = token.2;
+        }
+      },
+      Action::Reduce(production) => {
+        let len = PRODUCTIONS[production].1;
+        let values: Vec<Vec<u8>> = stack.drain(stack.len() - len..).map(|item| item.1).collect();
+        let lookup = if symbol == S_TERM {None} else {Some(&value[..])};
+        let (name, new_value) = reduce(actions, production, &values, lookup).map_err(|message| Error::Action {message})?;
+        let state = stack[stack.len() - 1].0;
+        let goto = find(GOTO[state], name).ok_or(Error::UnexpectedSymbol {state, symbol: name, offset})?;
+        stack.push((goto, new_value));
+      },
+      Action::Accept => return Ok(stack.pop().map(|item| item.1).unwrap_or_default())
+    }
+  }
+}
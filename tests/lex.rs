@@ -1,16 +1,14 @@
-use wasm_bindgen::prelude::*;
-use wasm_bindgen_test::*;
-
 use server::utils::*;
+use server::error::*;
 use server::lex::*;
 
 mod fixtures;
 use fixtures::*;
 
-#[wasm_bindgen_test]
+#[test]
 fn test_regular_definition_text() {
   let mut lex = Lex::new("".to_string());
-  lex.set_regular_definition_text(reg_exp());
+  lex.set_regular_definition_text(reg_exp()).expect("Error in regular definitions");
   assert_eq!(lex.rules().len(), 7, "Invalid number of rules");
   let mut iter = lex.rules().iter();
   assert_eq!(iter.find(|rule| rule.name() == hash("id")).unwrap().action().is_some(), false, "Found action for 'id' rule");
@@ -19,11 +17,11 @@ fn test_regular_definition_text() {
   assert_eq!(iter.find(|rule| rule.name() == hash("letter")).is_none(), true, "Not found rule 'letter'");
 }
 
-#[wasm_bindgen_test]
+#[test]
 fn test_get_token() {
-  let null_context = &JsValue::NULL;
+  let null_context = &mut CalcLexActions;
   let mut lex = Lex::new(lex_text());
-  lex.set_regular_definition_text(reg_exp());
+  lex.set_regular_definition_text(reg_exp()).expect("Error in regular definitions");
   let tkn = lex.get_token(null_context).expect("Error in get_token").unwrap();
   assert_eq!(tkn.name(), hash("id"), "Invalid token name");
   assert_eq!(tkn.value_to_string(), "name123", "Invalid token value");
@@ -52,3 +50,67 @@ fn test_get_token() {
   let tkn = lex.get_token(null_context).expect("Error in get_token").unwrap();
   assert_eq!(tkn.name(), hash(")"), "Invalid token name");
 }
+
+#[test]
+fn test_lex_errors() {
+  let mut lex = Lex::new("".to_string());
+  let result = lex.set_regular_definition_text("
+    id      [a-z]+
+    id      [0-9]+
+  ".to_string());
+  assert_eq!(result, Err(Error::RegExpSyntax {line: 3, column: 5, message: "Rule 'id' already defined".to_string()}),
+    "Invalid result of duplicate rule");
+
+  let mut lex = Lex::new("name123 #".to_string());
+  lex.set_regular_definition_text(reg_exp()).expect("Error in regular definitions");
+  let null_context = &mut CalcLexActions;
+  let _ = lex.get_token(null_context).expect("Error in get_token");
+  let _ = lex.get_token(null_context).expect("Error in get_token");
+  let result = lex.get_token(null_context);
+  assert_eq!(result, Err(Error::LexNoMatch {offset: 8}), "Invalid result of unknown symbol");
+}
+
+#[test]
+fn test_icase() {
+  let mut lex = Lex::new("".to_string());
+  lex.set_regular_definition_text(icase_reg_exp()).expect("Error in regular definitions");
+  let rule = lex.rules().iter().find(|rule| rule.name() == hash("method")).expect("Not found rule 'method'");
+  assert_eq!(rule.expression().as_str(), "(?i:GET|POST)", "Invalid expression of case-insensitive rule");
+  let rule = lex.rules().iter().find(|rule| rule.name() == hash("word")).expect("Not found rule 'word'");
+  assert_eq!(rule.expression().as_str(), "(?i:[a-zа-я])+", "Invalid expression of case-insensitive definition");
+  assert_eq!(lex.rules().iter().any(|rule| rule.name() == hash("letter")), false, "Found definition 'letter'");
+
+  lex.set_regular_definition_text("
+    get     GET     ICASE {set_name('GET')}
+  ".to_string()).expect("Error in regular definitions");
+  assert_eq!(lex.rules()[0].action().as_deref(), Some("set_name('GET')"), "Invalid action of case-insensitive rule");
+}
+
+#[test]
+fn test_modes() {
+  let mut lex = Lex::new("".to_string());
+  lex.set_regular_definition_text(modes_reg_exp()).expect("Error in regular definitions");
+  let modes = |name: &str| {
+    let rule = lex.rules().iter().find(|rule| rule.name() == hash(name)).expect("Not found rule");
+    let mut modes: Vec<String> = rule.modes().iter().map(|mode| get_original_name(*mode).unwrap_or_default()).collect();
+    modes.sort();
+    modes
+  };
+  assert_eq!(modes("word"), vec!("INITIAL"), "Invalid modes of rule without modes");
+  assert_eq!(modes("text"), vec!("string"), "Invalid modes of rule");
+  assert_eq!(modes("newline"), vec!("INITIAL", "comment", "string"), "Invalid modes of rule in all modes");
+
+  let mut lex = Lex::new("say \"a b\" ok".to_string());
+  lex.set_regular_definition_text(modes_reg_exp()).expect("Error in regular definitions");
+  let mut tokens = vec!();
+  while let Some(tkn) = lex.get_token(&mut ModeLexActions).expect("Error in get_token") {
+    if tkn.name() == GrammarSymbol::s_term().name() {break}
+    tokens.push(get_original_name(tkn.name()).unwrap_or_default());
+  }
+  assert_eq!(tokens, vec!("word", "space", "quote", "text", "close", "space", "word"), "Invalid tokens of modes");
+
+  assert_eq!(lex.change_mode(&ModeAction::Push(hash("comment"))), Ok(()), "Invalid result of push_mode");
+  assert_eq!(lex.change_mode(&ModeAction::Pop), Ok(()), "Invalid result of pop_mode");
+  assert_eq!(lex.change_mode(&ModeAction::Pop).is_err(), true, "Empty stack of modes is accepted");
+  assert_eq!(lex.change_mode(&ModeAction::Begin(hash("unknown"))).is_err(), true, "Unknown mode is accepted");
+}
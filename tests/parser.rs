@@ -1,12 +1,14 @@
-use wasm_bindgen_test::*;
-
+use server::utils::*;
+use server::error::*;
+use server::action::*;
 use server::lex::*;
+use server::lalr::*;
 use server::parser::*;
-use server::dfa_grammar::*;
+use server::stream_lex::*;
 
 mod fixtures;
 
-#[wasm_bindgen_test]
+#[test]
 fn test_lalr1() {
   let lex = Lex::new("".to_string());
   let mut parser = Parser::new(Box::new(lex));
@@ -18,7 +20,7 @@ fn test_lalr1() {
   assert_eq!(result.is_err(), true, "Invalid build of parser");
 }
 
-#[wasm_bindgen_test]
+#[test]
 fn test_lr1() {
   let lex = Lex::new("".to_string());
   let mut parser = Parser::new(Box::new(lex));
@@ -30,22 +32,513 @@ fn test_lr1() {
   assert_eq!(result.is_ok(), true, "Invalid build of parser");
 }
 
-#[wasm_bindgen_test]
+#[test]
 fn test_parse() {
-  let ctx = ExecContext::new();
-  let js_ctx = cast_exec_context_to_js_value(0, ctx);
   let text = "(1+2*a14)";
   let mut lex = Lex::new(text.to_string());
-  lex.set_regular_definition_text(fixtures::reg_exp());
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
 
   let mut parser = Parser::new(Box::new(lex));
   let _ = parser.set_grammar(fixtures::grammar(), ParserType::LALR1);
   parser.disable_state_logging();
 
-  let res = parser.parse(&js_ctx);
+  let res = parser.parse(&mut fixtures::CalcLexActions);
   let is_ok = match res {
     Ok(res) => res == ParseResult::ParseSuccess,
     _ => false
   };
   assert_eq!(is_ok, true, "Invalid parsed: {:?}", text);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_parser_actions() {
+  let text = "(1+2)*3+4";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+
+  let mut parser = Parser::new(Box::new(lex));
+  let _ = parser.set_grammar(fixtures::grammar(), ParserType::LALR1);
+  parser.disable_state_logging();
+
+  let mut actions = fixtures::CalcActions {result: None};
+  let res = parser.parse(&mut actions);
+  assert_eq!(res.ok(), Some(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+  assert_eq!(actions.result, Some(13), "Invalid result of actions");
+}
+
+#[test]
+fn test_conflicts() {
+  let lex = Lex::new("".to_string());
+  let mut parser = Parser::new(Box::new(lex));
+  let result = parser.set_grammar(fixtures::not_lalr1_grammar1(), ParserType::LALR1);
+  match result {
+    Err(Error::Conflicts(conflicts)) => {
+      assert_eq!(conflicts.len(), 1, "Invalid number of conflicts");
+      assert_eq!(conflicts[0].kind(), &Conflict::ShiftReduce, "Invalid kind of conflict");
+      assert_eq!(conflicts[0].symbol(), hash("else"), "Invalid symbol of conflict");
+    },
+    _ => panic!("Invalid result of build: {:?}", result)
+  }
+}
+
+#[test]
+fn test_grammar_syntax_error() {
+  let lex = Lex::new("".to_string());
+  let mut parser = Parser::new(Box::new(lex));
+  let result = parser.set_grammar("S: 'a';\nS 'b';".to_string(), ParserType::LALR1);
+  match result {
+    Err(Error::GrammarSyntax {line, column, ..}) => {
+      assert_eq!((line, column), (2, 3), "Invalid position of error");
+    },
+    _ => panic!("Invalid result of build: {:?}", result)
+  }
+}
+
+#[test]
+fn test_parse_error() {
+  let text = "(1+)";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+
+  let mut parser = Parser::new(Box::new(lex));
+  let _ = parser.set_grammar(fixtures::grammar(), ParserType::LALR1);
+  parser.disable_state_logging();
+
+  let res = parser.parse(&mut fixtures::CalcLexActions);
+  match res {
+    Err(Error::UnexpectedSymbol {symbol, value, offset, expected, ..}) => {
+      assert_eq!(symbol, hash(")"), "Invalid unexpected symbol");
+      assert_eq!(value, Some(vec!(b')')), "Invalid value of unexpected symbol");
+      assert_eq!(offset, 3, "Invalid offset of unexpected symbol");
+      let mut expected = expected.clone();
+      expected.sort();
+      let mut names = vec!(hash("("), hash("id"), hash("number"));
+      names.sort();
+      assert_eq!(expected, names, "Invalid expected symbols");
+    },
+    _ => panic!("Invalid result of parse: {:?}", res)
+  }
+}
+
+#[test]
+fn test_stream_parse_error() {
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+
+  let mut parser = Parser::new(Box::new(lex));
+  let _ = parser.set_grammar(fixtures::grammar(), ParserType::LALR1);
+  parser.disable_state_logging();
+
+  parser.set_data("(1+".as_bytes().to_vec());
+  let res = parser.parse(&mut fixtures::CalcLexActions);
+  assert_eq!(res, Ok(ParseResult::ParseWait), "Invalid result of parse");
+
+  parser.set_data("2*)".as_bytes().to_vec());
+  let res = parser.parse(&mut fixtures::CalcLexActions);
+  match res {
+    Err(ref err @ Error::UnexpectedSymbol {offset, ..}) => {
+      assert_eq!(offset, 5, "Invalid offset of unexpected symbol");
+      assert_eq!(err.to_string().starts_with("Expected "), true, "Invalid message: {}", err);
+      assert_eq!(err.to_string().contains(" at byte 5, got ')'"), true, "Invalid message: {}", err);
+    },
+    _ => panic!("Invalid result of parse: {:?}", res)
+  }
+}
+
+#[test]
+fn test_error_recovery() {
+  let text = "1+2;1+*3;4;";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::statements_reg_exp()).expect("Error in regular definitions");
+
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::statements_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+
+  let mut actions = fixtures::StatementActions {statements: 0, errors: vec!()};
+  let result = parser.parse(&mut actions);
+  assert_eq!(result, Ok(ParseResult::ParseSuccess), "Invalid result of parse: {:?}", text);
+  assert_eq!(actions.statements, 3, "Invalid number of statements");
+  assert_eq!(actions.errors.len(), 1, "Invalid number of errors");
+  match &actions.errors[0] {
+    Error::UnexpectedSymbol {symbol, offset, ..} => {
+      assert_eq!(*symbol, hash("*"), "Invalid unexpected symbol");
+      assert_eq!(*offset, 6, "Invalid offset of unexpected symbol");
+    },
+    err => panic!("Invalid error: {:?}", err)
+  }
+}
+
+#[test]
+fn test_precedence() {
+  let text = "2+3*4*(1+1)+5";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::precedence_reg_exp()).expect("Error in regular definitions");
+
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::precedence_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let resolutions = parser.resolutions();
+  assert_eq!(resolutions.iter().all(|item| item.conflict().kind() == &Conflict::ShiftReduce), true,
+    "Invalid kind of resolved conflicts");
+  let is_reduce = |symbol: &str| resolutions.iter().any(|item| item.conflict().symbol() == hash(symbol) &&
+    item.conflict().production2().find(hash(symbol)).len() > 0 &&
+    matches!(item.resolution(), Resolution::Reduce(_)));
+  assert_eq!(is_reduce("+") && is_reduce("*"), true, "Invalid resolution of left associativity");
+
+  let mut actions = fixtures::CalcActions {result: None};
+  let result = parser.parse(&mut actions);
+  assert_eq!(result, Ok(ParseResult::ParseSuccess), "Invalid result of parse: {:?}", text);
+  assert_eq!(actions.result, Some(31), "Invalid result of calculation: {:?}", text);
+
+  let text = "1<2<3";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::precedence_reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::precedence_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let result = parser.parse(&mut fixtures::CalcLexActions);
+  match result {
+    Err(Error::UnexpectedSymbol {symbol, offset, ..}) => {
+      assert_eq!(symbol, hash("<"), "Invalid unexpected symbol");
+      assert_eq!(offset, 3, "Invalid offset of unexpected symbol");
+    },
+    result => panic!("Invalid result of nonassoc operator: {:?}", result)
+  }
+}
+
+#[test]
+fn test_analyze_grammar() {
+  let reports = analyze_grammar(fixtures::ambiguous_grammar(), ParserType::LALR1).expect("Error in grammar");
+  assert_eq!(reports.len(), 1, "Invalid number of conflicts");
+  let report = &reports[0];
+  assert_eq!(report.kind(), &Conflict::ShiftReduce, "Invalid kind of conflict");
+  assert_eq!(report.lookahead(), hash("+"), "Invalid lookahead of conflict");
+  assert_eq!(report.example(), &vec!(hash("id"), hash("+"), hash("id")), "Invalid example of conflict");
+  let items: Vec<String> = report.items().iter().map(|item| item.to_string()).collect();
+  assert_eq!(items.contains(&"E: E '+' E ., '+'".to_string()), true, "Invalid items of conflict: {:?}", items);
+  assert_eq!(items.contains(&"E: E . '+' E, '+'".to_string()), true, "Invalid items of conflict: {:?}", items);
+
+  let reports = analyze_grammar(fixtures::not_lalr1_grammar3(), ParserType::LALR1).expect("Error in grammar");
+  assert_eq!(reports.len(), 2, "Invalid number of conflicts");
+  assert_eq!(reports.iter().all(|report| report.kind() == &Conflict::ReduceReduce), true, "Invalid kind of conflicts");
+  let reports = analyze_grammar(fixtures::not_lalr1_grammar3(), ParserType::LR1).expect("Error in grammar");
+  assert_eq!(reports.len(), 0, "Invalid number of conflicts");
+}
+
+#[test]
+fn test_lalr1_builders() {
+  let grammars = vec!(fixtures::grammar(), fixtures::precedence_grammar(), fixtures::statements_grammar());
+  for text in grammars {
+    let grammar = GrammarBuilder::from_text(text).expect("Error in grammar");
+    let goto_states = LALRBuilder::build_goto_states(&grammar);
+    let naive_goto_states = NaiveLALRBuilder::build_goto_states(&grammar);
+    assert_eq!(goto_states.len(), naive_goto_states.len(), "Invalid number of states");
+    let action_states = LALRBuilder::build_action_states(&grammar, &goto_states).expect("Error in build");
+    let naive_action_states = NaiveLALRBuilder::build_action_states(&grammar, &naive_goto_states).expect("Error in build");
+    assert_eq!(action_states.len(), naive_action_states.len(), "Invalid number of states");
+    for state in 0..action_states.len() {
+      assert_eq!(goto_states.transitions(state), naive_goto_states.transitions(state), "Invalid transitions of state {}", state);
+      let actions: Vec<_> = action_states.actions(state).into_iter()
+        .map(|(name, action)| (name, action.state().clone(), *action.goto(), action.production_name())).collect();
+      let naive_actions: Vec<_> = naive_action_states.actions(state).into_iter()
+        .map(|(name, action)| (name, action.state().clone(), *action.goto(), action.production_name())).collect();
+      assert_eq!(actions, naive_actions, "Invalid actions of state {}", state);
+    }
+  }
+
+  for text in vec!(fixtures::not_lalr1_grammar1(), fixtures::not_lalr1_grammar2(), fixtures::ambiguous_grammar()) {
+    let grammar = GrammarBuilder::from_text(text).expect("Error in grammar");
+    let conflicts = |reports: Vec<ConflictReport>| {
+      let mut conflicts: Vec<String> = reports.iter().map(|report| report.conflict().to_string()).collect();
+      conflicts.sort();
+      conflicts.dedup();
+      conflicts
+    };
+    assert_eq!(conflicts(LALRBuilder::analyze(&grammar).expect("Error in analyze")),
+      conflicts(NaiveLALRBuilder::analyze(&grammar).expect("Error in analyze")), "Invalid conflicts");
+  }
+}
+
+#[test]
+fn test_minimal_lr1() {
+  let lex = Lex::new("".to_string());
+  let mut parser = Parser::new(Box::new(lex));
+  let result = parser.set_grammar(fixtures::not_lalr1_grammar1(), ParserType::MinimalLR1);
+  assert_eq!(result.is_err(), true, "Invalid build of parser");
+  let result = parser.set_grammar(fixtures::not_lalr1_grammar2(), ParserType::MinimalLR1);
+  assert_eq!(result.is_err(), true, "Invalid build of parser");
+  let result = parser.set_grammar(fixtures::not_lalr1_grammar3(), ParserType::MinimalLR1);
+  assert_eq!(result.is_ok(), true, "Invalid build of parser");
+
+  let states_count = |text: String| {
+    let grammar = GrammarBuilder::from_text(text).expect("Error in grammar");
+    let lalr = LALRBuilder::build_collection_items(&grammar, &LALRBuilder::build_goto_states(&grammar))
+      .expect("Error in collection items");
+    let lr = LRBuilder::build_collection_items(&grammar, &LRBuilder::build_goto_states(&grammar))
+      .expect("Error in collection items");
+    let minimal = MinimalLRBuilder::build_collection_items(&grammar, &MinimalLRBuilder::build_goto_states(&grammar))
+      .expect("Error in collection items");
+    (lalr.len(), lr.len(), minimal.len())
+  };
+  assert_eq!(states_count(fixtures::grammar()), (13, 24, 13), "Invalid number of states");
+  assert_eq!(states_count(fixtures::not_lalr1_grammar3()), (12, 13, 13), "Invalid number of states");
+
+  let text = "(1+2)*3+4";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::grammar(), ParserType::MinimalLR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let mut actions = fixtures::CalcActions {result: None};
+  let res = parser.parse(&mut actions);
+  assert_eq!(res.ok(), Some(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+  assert_eq!(actions.result, Some(13), "Invalid result of actions");
+}
+
+#[test]
+fn test_unproductive_nonterminal() {
+  for text in ["aab", ""] {
+    for (index, parser_type) in vec!(ParserType::LALR1, ParserType::LR1, ParserType::MinimalLR1).into_iter().enumerate() {
+      let mut lex = Lex::new(text.to_string());
+      lex.set_regular_definition_text("
+        'a' a
+        'b' b
+        'd' d
+      ".to_string()).expect("Error in regular definitions");
+      let mut parser = Parser::new(Box::new(lex));
+      parser.set_grammar(fixtures::unproductive_grammar(), parser_type).expect("Error in grammar");
+      parser.disable_state_logging();
+      let res = parser.parse(&mut NoActions);
+      assert_eq!(res, Ok(ParseResult::ParseSuccess), "Invalid parsed: {:?} by parser {}", text, index);
+    }
+  }
+}
+
+#[test]
+fn test_glr() {
+  let lex = Lex::new("".to_string());
+  let mut parser = Parser::new(Box::new(lex));
+  let result = parser.set_grammar(fixtures::glr_grammar(), ParserType::LALR1);
+  assert_eq!(result.is_err(), true, "Invalid build of parser");
+  let result = parser.set_grammar(fixtures::glr_grammar(), ParserType::GLR);
+  assert_eq!(result.is_ok(), true, "Invalid build of parser");
+
+  let parse_all = |text: &str| {
+    let mut lex = Lex::new(text.to_string());
+    lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+    let mut parser = Parser::new(Box::new(lex));
+    parser.set_grammar(fixtures::glr_grammar(), ParserType::GLR).expect("Error in grammar");
+    parser.set_parse_trees(ParseTrees::All);
+    parser.disable_state_logging();
+    let mut actions = fixtures::GLRActions {trees: vec!()};
+    let res = parser.parse(&mut actions);
+    assert_eq!(res.ok(), Some(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+    actions.trees
+  };
+  let trees = parse_all("2+3*4");
+  assert_eq!(trees.len(), 2, "Invalid number of parse trees");
+  assert_eq!(trees.iter().all(|tree| tree.symbol().name() == hash("E")), true, "Invalid symbol of parse tree");
+  assert_eq!(parse_all("1+2+3+4").len(), 5, "Invalid number of parse trees");
+  assert_eq!(parse_all("(1+2)").len(), 1, "Invalid number of parse trees");
+
+  for (text, result) in [("(1+2)*3", 9), ("2+3*4", 20), ("7", 7)] {
+    let mut lex = Lex::new(text.to_string());
+    lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+    let mut parser = Parser::new(Box::new(lex));
+    parser.set_grammar(fixtures::glr_grammar(), ParserType::GLR).expect("Error in grammar");
+    parser.disable_state_logging();
+    let mut actions = fixtures::CalcActions {result: None};
+    let res = parser.parse(&mut actions);
+    assert_eq!(res.ok(), Some(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+    assert_eq!(actions.result, Some(result), "Invalid result of actions: {:?}", text);
+  }
+
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::glr_grammar(), ParserType::GLR).expect("Error in grammar");
+  parser.disable_state_logging();
+  parser.set_data("2+3*".as_bytes().to_vec());
+  let res = parser.parse(&mut fixtures::CalcLexActions);
+  assert_eq!(res, Ok(ParseResult::ParseWait), "Invalid result of parse");
+  parser.set_data("4)".as_bytes().to_vec());
+  let res = parser.parse(&mut fixtures::CalcLexActions);
+  match res {
+    Err(Error::UnexpectedSymbol {offset, ..}) => assert_eq!(offset, 5, "Invalid offset of unexpected symbol"),
+    _ => panic!("Invalid result of parse: {:?}", res)
+  }
+}
+
+#[test]
+fn test_glr_lookup() {
+  let parse = |grammar: String| {
+    let text = "1+2+3*4";
+    let mut lex = Lex::new(text.to_string());
+    lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+    let mut parser = Parser::new(Box::new(lex));
+    parser.set_grammar(grammar, ParserType::GLR).expect("Error in grammar");
+    parser.disable_state_logging();
+    let mut actions = fixtures::LookupActions {result: None};
+    parser.parse(&mut actions).map(|_| actions.result)
+  };
+  let res = parse(fixtures::glr_lookup_grammar());
+  assert_eq!(res, Ok(Some("*".to_string())), "Invalid lookahead of deferred action");
+
+  let grammar = fixtures::glr_lookup_grammar().replace("set(lookup())", "push_after('number')");
+  match parse(grammar) {
+    Err(Error::Action {..}) => {},
+    res => panic!("Invalid result of parse: {:?}", res)
+  }
+}
+
+#[test]
+fn test_glr_cyclic() {
+  let cyclic = |grammar: String| {
+    let lex = Lex::new("".to_string());
+    let mut parser = Parser::new(Box::new(lex));
+    parser.set_grammar(grammar, ParserType::GLR)
+  };
+  let message = "GLR parser does not support cyclic grammars, 'S' derives itself".to_string();
+  assert_eq!(cyclic(fixtures::cyclic_grammar()), Err(Error::Unsupported {message: message.clone()}),
+    "Invalid result of cyclic grammar");
+  // the cycle through the nullable nonterminal
+  let grammar = fixtures::cyclic_grammar().replace("S: S;", "S: C S; C: ;");
+  assert_eq!(cyclic(grammar), Err(Error::Unsupported {message}), "Invalid result of cyclic grammar");
+
+  let text = "babb";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text("
+    'a' a
+    'b' b
+  ".to_string()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::cyclic_grammar().replace("S: S;", ""), ParserType::GLR).expect("Error in grammar");
+  parser.disable_state_logging();
+  let res = parser.parse(&mut NoActions);
+  assert_eq!(res, Ok(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+}
+
+#[test]
+fn test_lexer_modes() {
+  let text = "host: a b:c\nname: x\n";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::headers_reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::headers_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let res = parser.parse(&mut NoActions);
+  assert_eq!(res, Ok(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::headers_reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::headers_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  for data in ["host: a", " b:c\nna", "me: x\n"] {
+    parser.set_data(data.as_bytes().to_vec());
+    let res = parser.parse(&mut NoActions);
+    assert_eq!(res, Ok(ParseResult::ParseWait), "Invalid result of parse: {:?}", data);
+  }
+  parser.set_end_of_data();
+  let res = parser.parse(&mut NoActions);
+  assert_eq!(res, Ok(ParseResult::ParseSuccess), "Invalid result of parse at the end of data");
+}
+
+#[test]
+fn test_context_lexing() {
+  let parse = |text: &str, context_lexing: bool| {
+    let mut lex = Lex::new(text.to_string());
+    lex.set_regular_definition_text(fixtures::hex_reg_exp()).expect("Error in regular definitions");
+    let mut parser = Parser::new(Box::new(lex));
+    parser.set_grammar(fixtures::hex_grammar(), ParserType::LALR1).expect("Error in grammar");
+    parser.disable_state_logging();
+    parser.set_context_lexing(context_lexing);
+    parser.parse(&mut NoActions)
+  };
+  assert_eq!(parse("0x0A 0x00", true), Ok(ParseResult::ParseSuccess), "Invalid result of context lexing");
+  assert_eq!(parse("0x0A", false).is_err(), true, "Invalid result without context lexing");
+  // none of the expected tokens matches, the token is recognized by all the rules
+  match parse("x0", true) {
+    Err(Error::UnexpectedSymbol {symbol, offset, ..}) => {
+      assert_eq!((symbol, offset), (hash("x"), 0), "Invalid unexpected symbol");
+    },
+    res => panic!("Invalid result of parse: {:?}", res)
+  }
+
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::hex_reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::hex_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  parser.set_context_lexing(true);
+  for data in ["0x", "00 0", "x0f"] {
+    parser.set_data(data.as_bytes().to_vec());
+    let res = parser.parse(&mut NoActions);
+    assert_eq!(res, Ok(ParseResult::ParseWait), "Invalid result of parse: {:?}", data);
+  }
+  parser.set_end_of_data();
+  let res = parser.parse(&mut NoActions);
+  assert_eq!(res, Ok(ParseResult::ParseSuccess), "Invalid result of parse at the end of data");
+}
+
+#[test]
+fn test_ebnf() {
+  for (text, result) in [("(1+2;3;4*5+6)", Some("1+2;3;4*5+6")), ("(7)", Some("7")), ("()", None)] {
+    let mut lex = Lex::new(text.to_string());
+    lex.set_regular_definition_text(fixtures::statements_reg_exp()).expect("Error in regular definitions");
+    let mut parser = Parser::new(Box::new(lex));
+    parser.set_grammar(fixtures::ebnf_grammar(), ParserType::LALR1).expect("Error in grammar");
+    parser.disable_state_logging();
+    let mut actions = fixtures::EBNFActions {result: None};
+    let res = parser.parse(&mut actions);
+    assert_eq!(res.ok(), Some(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+    assert_eq!(actions.result.as_deref(), result, "Invalid result of actions: {:?}", text);
+  }
+
+  let grammar = GrammarBuilder::from_text("S: ('a' 'b' 'c' 'd' 'e' 'f' 'g')+;".to_string()).expect("Error in grammar");
+  assert_eq!(grammar.len(), 4, "Invalid number of productions");
+
+  let lex = Lex::new("".to_string());
+  let mut parser = Parser::new(Box::new(lex));
+  for (text, position) in [("S: ('a' | 'b';", (1, 14)), ("S: 'a' );", (1, 8)), ("S: 'a' (* 'b');", (1, 9))] {
+    match parser.set_grammar(text.to_string(), ParserType::LALR1) {
+      Err(Error::GrammarSyntax {line, column, ..}) => {
+        assert_eq!((line, column), position, "Invalid position of error: {:?}", text);
+      },
+      result => panic!("Invalid result of build: {:?}", result)
+    }
+  }
+}
+
+#[test]
+fn test_labels() {
+  let text = "(1+2)*3+4";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::labels_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let mut actions = fixtures::LabelActions {result: None};
+  let res = parser.parse(&mut actions);
+  assert_eq!(res.ok(), Some(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+  assert_eq!(actions.result, Some(13), "Invalid result of actions");
+
+  let grammar = GrammarBuilder::from_text("S: 'a' name=N ':' value=(V | 'b')* {set(value)};".to_string())
+    .expect("Error in grammar");
+  let production = grammar.production(0);
+  assert_eq!((production.label("name"), production.label("value"), production.label("a")), (Some(2), Some(0), None),
+    "Invalid indices of labels");
+
+  let lex = Lex::new("".to_string());
+  let mut parser = Parser::new(Box::new(lex));
+  for (text, position) in [("S: a=A a=B;", (1, 8)), ("S: get=A;", (1, 4)), ("S: a=A [set(b)];", (1, 8)),
+    ("S: (a=A)*;", (1, 5)), ("S: a=;", (1, 6)), ("S: a=A | b=B [set(b)];", (1, 14))] {
+    match parser.set_grammar(text.to_string(), ParserType::LALR1) {
+      Err(Error::GrammarSyntax {line, column, ..}) => {
+        assert_eq!((line, column), position, "Invalid position of error: {:?}", text);
+      },
+      result => panic!("Invalid result of build: {:?}", result)
+    }
+  }
+}
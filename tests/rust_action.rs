@@ -0,0 +1,214 @@
+use server::utils::*;
+use server::error::*;
+use server::action::*;
+use server::lex::*;
+use server::stream_lex::*;
+use server::parser::*;
+use server::rust_action::*;
+use server::binary::*;
+
+mod fixtures;
+
+///
+/// Context of the reduce of the production with the values of symbols from left to right.
+///
+#[derive(Default)]
+struct TestContext {
+  values: Vec<Option<Vec<u8>>>,
+  value: Option<Vec<u8>>,
+  name: Option<String>,
+  id: Option<usize>,
+  nexts: Vec<(String, Option<String>, Option<Vec<u8>>, Option<usize>)>,
+  reads: Vec<(String, String, usize)>,
+  modes: Vec<String>
+}
+
+impl ParserActionContext for TestContext {
+  fn bind(&mut self, id: usize) {
+    self.id = Some(id);
+  }
+
+  fn id(&self, _index: usize) -> Option<usize> {
+    None
+  }
+
+  fn lookup(&self) -> Option<&[u8]> {
+    Some(b"$")
+  }
+
+  fn get(&self, index: usize) -> Option<&[u8]> {
+    self.values.len().checked_sub(index + 1).and_then(|idx| self.values[idx].as_deref())
+  }
+
+  fn set(&mut self, index: usize) {
+    self.value = self.get(index).map(|value| value.to_vec());
+  }
+
+  fn set_val(&mut self, value: Vec<u8>) {
+    self.value = Some(value);
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name = Some(name.to_string());
+  }
+
+  fn set_name_from_hash(&mut self, name: usize) {
+    self.name = get_original_name(name);
+  }
+
+  fn push_after(&mut self, name: &str, insert_name: Option<&str>,
+    insert_value: Option<Vec<u8>>, size: Option<usize>, _stop_codes: Option<Vec<u8>>) {
+    self.nexts.push((name.to_string(), insert_name.map(|name| name.to_string()), insert_value, size));
+  }
+
+  fn read_bytes(&mut self, name: &str, insert_name: &str, size: usize) {
+    self.reads.push((name.to_string(), insert_name.to_string(), size));
+  }
+
+  fn begin(&mut self, mode: &str) {
+    self.modes.push(format!("begin {}", mode));
+  }
+
+  fn push_mode(&mut self, mode: &str) {
+    self.modes.push(format!("push {}", mode));
+  }
+
+  fn pop_mode(&mut self) {
+    self.modes.push("pop".to_string());
+  }
+}
+
+fn execute(text: &str, labels: Option<&Labels>, values: &[&str]) -> Result<TestContext, String> {
+  let action = RustAction::parse(text, labels)?;
+  let mut ctx = TestContext {values: values.iter().map(|value| Some(value.as_bytes().to_vec())).collect(), ..Default::default()};
+  action.execute(&mut ctx)?;
+  Ok(ctx)
+}
+
+#[test]
+fn test_parse() {
+  let action = RustAction::parse("set(1, 0)", None).expect("Error in parse");
+  assert_eq!(action.symbols(), Some(vec!(1, 0)), "Invalid symbols of action");
+  let action = RustAction::parse("set(trim(1)); if eq(0, 'a') {bind(parse_int(1))} else set_name('b');", None)
+    .expect("Error in parse");
+  assert_eq!(action.symbols(), None, "Invalid symbols of action");
+  assert_eq!(action.statements().len(), 2, "Invalid number of statements");
+
+  let labels = Labels::new(vec!(("name".to_string(), 2), ("value".to_string(), 0)));
+  let action = RustAction::parse("set(value, name)", Some(&labels)).expect("Error in parse");
+  assert_eq!(action.symbols(), Some(vec!(0, 2)), "Invalid symbols of labels");
+
+  for text in ["", "set(", "set(1", "set(1) set(0)", "get(0)", "set(foo)", "set(foo(1))", "trim(1, 2)",
+    "set(trim(1, 2))", "if 0 set(1)", "set('a)", "set(1) }", "begin()", "pop_mode(0)"] {
+    assert_eq!(RustAction::parse(text, None).is_err(), true, "Invalid parse of wrong action: {:?}", text);
+  }
+}
+
+#[test]
+fn test_read_invalid_arguments() {
+  let read = |statement: RustStatement| {
+    let mut writer = BinaryWriter::new();
+    RustAction::new("".to_string(), vec!(statement)).write(&mut writer);
+    let data = writer.into_bytes();
+    let mut reader = BinaryReader::new(&data).expect("Error in reader");
+    RustAction::read(&mut reader).map(|_| ())
+  };
+  assert_eq!(read(RustStatement::ReadBytes(vec!(RustExpr::Symbol(0), RustExpr::Symbol(1)))), Ok(()),
+    "Invalid read of action");
+  for statement in [
+    RustStatement::Set(vec!()),
+    RustStatement::PushAfter(vec!()),
+    RustStatement::PushAfter(vec!(RustExpr::None; 6)),
+    RustStatement::ReadBytes(vec!(RustExpr::Symbol(0))),
+    RustStatement::Set(vec!(RustExpr::Call(RustFunction::Eq, vec!(RustExpr::Symbol(0))))),
+    RustStatement::SetName(RustExpr::Call(RustFunction::Lookup, vec!(RustExpr::Symbol(0))))
+  ] {
+    assert_eq!(matches!(read(statement.clone()), Err(Error::InvalidBinary {..})), true,
+      "Invalid read of wrong action: {:?}", statement);
+  }
+}
+
+#[test]
+fn test_execute() {
+  let ctx = execute("set(concat(lower(2), upper(1), trim(0)))", None, &["Ab", "cD", "  e f "]).unwrap();
+  assert_eq!(ctx.value, Some(b"abCDe f".to_vec()), "Invalid value of concat");
+
+  let ctx = execute("set(parse_int(1), parse_hex(0)); bind(parse_hex(0))", None, &[" 012", "1f"]).unwrap();
+  assert_eq!((ctx.value, ctx.id), (Some(b"1231".to_vec()), Some(31)), "Invalid value of numbers");
+
+  let text = "if and(not(empty(1)), gt(parse_int(1), '9')) {set_name('big')} else if eq(lookup(), '$') {set(0)}";
+  let ctx = execute(text, None, &["10", "x"]).unwrap();
+  assert_eq!((ctx.name, ctx.value), (Some("big".to_string()), None), "Invalid result of condition");
+  let ctx = execute(text, None, &["9", "x"]).unwrap();
+  assert_eq!((ctx.name, ctx.value), (None, Some(b"x".to_vec())), "Invalid result of condition");
+
+  let ctx = execute("push_after('crlf', 'chunk_data', none, parse_hex(0))", None, &["1A"]).unwrap();
+  assert_eq!(ctx.nexts, vec!(("crlf".to_string(), Some("chunk_data".to_string()), None, Some(26))),
+    "Invalid result of push_after");
+
+  let ctx = execute("read_bytes(parse_be(1), 'value'); read_bytes(parse_le(0), 'data', 'crlf')", None,
+    &["\x01\x02", "\x01\x02"]).unwrap();
+  assert_eq!(ctx.reads, vec!(("".to_string(), "value".to_string(), 258), ("crlf".to_string(), "data".to_string(), 513)),
+    "Invalid result of read_bytes");
+
+  let ctx = execute("begin('INITIAL'); if eq(0, 'q') {push_mode(concat(0, 'str'))} else pop_mode()", None, &["q"]).unwrap();
+  assert_eq!(ctx.modes, vec!("begin INITIAL".to_string(), "push qstr".to_string()), "Invalid changes of modes");
+  let ctx = execute("if eq(0, 'q') {push_mode(0)} else pop_mode()", None, &["x"]).unwrap();
+  assert_eq!(ctx.modes, vec!("pop".to_string()), "Invalid changes of modes");
+
+  let ctx = execute("set('\\x41\\'b\\n')", None, &[]).unwrap();
+  assert_eq!(ctx.value, Some(b"A'b\n".to_vec()), "Invalid value of string");
+
+  assert_eq!(execute("bind(parse_int(0))", None, &["x"]).is_err(), true, "Invalid result of wrong number");
+  assert_eq!(execute("bind(parse_int(0))", None, &["-1"]).is_err(), true, "Invalid result of wrong id");
+  assert_eq!(execute("read_bytes(0, 'data')", None, &["-1"]).is_err(), true, "Invalid result of wrong size");
+  assert_eq!(execute("bind(parse_be(0))", None, &["123456789"]).is_err(), true, "Invalid result of wrong number");
+}
+
+#[test]
+fn test_parse_with_rust_actions() {
+  let text = "10*2+11";
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(fixtures::reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::rust_action_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let mut actions = fixtures::CalcActions {result: None};
+  let res = parser.parse(&mut actions);
+  assert_eq!(res.ok(), Some(ParseResult::ParseSuccess), "Invalid parsed: {:?}", text);
+  assert_eq!(actions.result, Some(49), "Invalid result of actions");
+}
+
+#[test]
+fn test_read_bytes() {
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::netstring_reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::netstring_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let mut actions = fixtures::NetstringActions {items: vec!()};
+  for data in ["3:a", "b", "c,0:,11:1,2:", "34567,8,", "1:x,"] {
+    parser.set_data(data.as_bytes().to_vec());
+    let res = parser.parse(&mut actions);
+    assert_eq!(res, Ok(ParseResult::ParseWait), "Invalid result of parse: {:?}", data);
+  }
+  // the last item is reduced by the lookahead of the next item
+  let items: Vec<&[u8]> = actions.items.iter().map(|item| item.as_slice()).collect();
+  assert_eq!(items, vec!(&b"abc"[..], b"", b"1,2:34567,8"), "Invalid values of items");
+}
+
+#[test]
+fn test_push_after_stop_codes() {
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(fixtures::netstring_reg_exp()).expect("Error in regular definitions");
+  let mut parser = Parser::new(Box::new(lex));
+  parser.set_grammar(fixtures::lines_grammar(), ParserType::LALR1).expect("Error in grammar");
+  parser.disable_state_logging();
+  let mut actions = fixtures::DataActions::default();
+  for data in ["1:one\r", "\n2:", "t\rwo\r\n3:"] {
+    parser.set_data(data.as_bytes().to_vec());
+    let res = parser.parse(&mut actions);
+    assert_eq!(res, Ok(ParseResult::ParseWait), "Invalid result of parse: {:?}", data);
+  }
+  assert_eq!(actions.parts, vec!(b"one".to_vec(), b"t\rwo".to_vec()), "Invalid data of tokens");
+}
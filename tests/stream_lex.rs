@@ -1,19 +1,21 @@
-use wasm_bindgen::prelude::*;
-use wasm_bindgen_test::*;
-
 use server::utils::*;
+use server::action::*;
+use server::error::*;
 use server::lex::*;
 use server::stream_lex::*;
+use server::dfa::*;
+use std::rc::Rc;
+use std::collections::HashSet;
 
 mod fixtures;
 use fixtures::*;
 
-#[wasm_bindgen_test]
-async fn test_regular_definition_text() {
-  let null_context = &JsValue::NULL;
+#[test]
+fn test_regular_definition_text() {
+  let null_context = &mut NoActions;
   let data: Vec<u8> = vec!(72, 84, 84, 80, 226, 157, 164, 71, 69, 84);
 
-  let mut lex = StreamLex::new(None);
+  let mut lex = StreamLex::new();
   let _ = lex.set_regular_definition_text(regular_definition_text1());
   lex.set_data(data);
 
@@ -28,3 +30,312 @@ async fn test_regular_definition_text() {
   let tkn = lex.get_token(null_context).expect("Error in get_token").expect("No token");
   assert_eq!(tkn.name() == w_term.name(), true, "Invalid token value: {:?}", tkn.name());
 }
+
+#[test]
+fn test_no_match() {
+  let null_context = &mut NoActions;
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(regular_definition_text1()).expect("Error in regular definitions");
+  lex.set_data("GE".as_bytes().to_vec());
+
+  let w_term = GrammarSymbol::w_term();
+  let tkn = lex.get_token(null_context).expect("Error in get_token").expect("No token");
+  assert_eq!(tkn.name() == w_term.name(), true, "Invalid token value: {:?}", tkn.name());
+
+  lex.set_data("T!".as_bytes().to_vec());
+  let tkn = lex.get_token(null_context).expect("Error in get_token").expect("No token");
+  assert_eq!(tkn.value() == &vec!(71u8, 69, 84), true, "Invalid token value: {:?}", tkn.value());
+
+  let result = lex.get_token(null_context);
+  assert_eq!(result, Err(Error::LexNoMatch {offset: 3}), "Invalid result of unknown symbol");
+}
+
+#[test]
+fn test_minimization() {
+  let (_, _, stats) = build_with_stats(regular_definition_text1()).expect("Error in regular definitions");
+  assert_eq!(stats.states_before(), 38, "Invalid number of states before minimization");
+  assert_eq!(stats.states_after(), 34, "Invalid number of states after minimization");
+
+  let (_, _, stats) = build_with_stats("
+    ab      xa|ya
+    cd      xc|yc
+  ".to_string()).expect("Error in regular definitions");
+  assert_eq!(stats.states_before(), 5, "Invalid number of states before minimization");
+  assert_eq!(stats.states_after(), 4, "Invalid number of states after minimization");
+
+  let (states, goto_states, stats) = build_with_stats("
+    ab      xa|ya
+    cd      xc|yc
+    ef      xe {set_name('e')}
+    gh      ye {set_name('g')}
+  ".to_string()).expect("Error in regular definitions");
+  assert_eq!(stats.states_before(), 7, "Invalid number of states before minimization");
+  assert_eq!(stats.states_after(), 7, "Invalid number of states after minimization");
+
+  let accept = |data: &str| {
+    let mut state = 0;
+    for code in data.bytes() {
+      state = *goto_states.state(state, code as usize).expect("Invalid transition");
+    }
+    let state = states[state].as_ref().expect("Not accepting state");
+    (state.accept(), state.action().as_ref().map(|action| action.to_string()))
+  };
+  assert_eq!(accept("xa"), (hash("ab"), None), "Invalid accept state");
+  assert_eq!(accept("ya"), (hash("ab"), None), "Invalid accept state");
+  assert_eq!(accept("yc"), (hash("cd"), None), "Invalid accept state");
+  assert_eq!(accept("xe").0, hash("ef"), "Invalid accept state");
+  assert_eq!(accept("ye").0, hash("gh"), "Invalid accept state");
+}
+
+#[test]
+fn test_read_int() {
+  let actions = &mut BinaryLexActions;
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(binary_reg_exp()).expect("Error in regular definitions");
+
+  let w_term = GrammarSymbol::w_term();
+  let mut payload = vec!(0x80, 0x01);
+  payload.extend(vec!(7; 128));
+  let mut tokens = vec!();
+  for data in [vec!(0x31, 0x01), vec!(0x02, 0x30), payload, vec!(0x30, 0x00)] {
+    lex.set_data(data);
+    loop {
+      let tkn = lex.get_token(actions).expect("Error in get_token").expect("No token");
+      if tkn.name() == w_term.name() {break}
+      tokens.push((get_original_name(tkn.name()).unwrap(), tkn.value().clone()));
+    }
+  }
+  let expected = vec!(
+    ("short".to_string(), vec!(0x31)),
+    ("length".to_string(), b"258".to_vec()),
+    ("publish".to_string(), vec!(0x30)),
+    ("payload".to_string(), vec!(7; 128)),
+    ("publish".to_string(), vec!(0x30)),
+    ("payload".to_string(), vec!())
+  );
+  assert_eq!(tokens, expected, "Invalid tokens");
+
+  lex.set_data(vec!(0x30, 0xff, 0xff, 0xff, 0xff));
+  let tkn = lex.get_token(actions).expect("Error in get_token").expect("No token");
+  assert_eq!(tkn.value(), &vec!(0x30), "Invalid token value: {:?}", tkn.value());
+  let result = lex.get_token(actions);
+  assert_eq!(result, Err(Error::LexNoMatch {offset: 137}), "Invalid result of wrong integer");
+}
+
+#[test]
+fn test_read_to_codes() {
+  let mut actions = DataActions::default();
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(netstring_reg_exp()).expect("Error in regular definitions");
+  let w_term = GrammarSymbol::w_term();
+
+  lex.set_read_to_codes(hash("body"), b"\r\n\r\n".to_vec());
+  for data in ["ab\r\n", "c\r\n\r", "\r", "\n12"] {
+    lex.set_data(data.as_bytes().to_vec());
+    let tkn = lex.get_token(&mut actions).expect("Error in get_token").expect("No token");
+    assert_eq!(tkn.name(), w_term.name(), "Invalid token of data {:?}", data);
+  }
+  lex.set_data("\r\n\r\n:".as_bytes().to_vec());
+  let tkn = lex.get_token(&mut actions).expect("Error in get_token").expect("No token");
+  assert_eq!(tkn.name(), hash("body"), "Invalid token read up to delimiter");
+  assert_eq!(actions.parts, vec!(b"ab\r\nc\r\n\r\r\n12".to_vec()), "Invalid data of token");
+
+  let tkn = lex.get_token(&mut actions).expect("Error in get_token").expect("No token");
+  assert_eq!(tkn.name(), hash("colon"), "Invalid token after delimiter");
+}
+
+/// Reads all tokens of the text by `StreamLex` and `Lex`.
+fn read_tokens(reg_exp: String, text: &str) -> (Vec<(String, String)>, Vec<(String, String)>) {
+  read_tokens_with_actions(reg_exp, text, &mut NoActions)
+}
+
+fn read_tokens_with_actions(reg_exp: String, text: &str, actions: &mut dyn LexActions)
+  -> (Vec<(String, String)>, Vec<(String, String)>) {
+  let s_term = GrammarSymbol::s_term();
+
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(reg_exp.clone()).expect("Error in regular definitions");
+  lex.set_data(text.as_bytes().to_vec());
+  lex.set_end_of_data();
+  let mut tokens = vec!();
+  while let Some(tkn) = lex.get_token(actions).expect("Error in get_token") {
+    if tkn.name() == s_term.name() {break}
+    tokens.push((get_original_name(tkn.name()).unwrap_or_default(), tkn.value_to_string()));
+  }
+
+  let mut lex = Lex::new(text.to_string());
+  lex.set_regular_definition_text(reg_exp).expect("Error in regular definitions");
+  let mut lex_tokens = vec!();
+  while let Some(tkn) = lex.get_token(actions).expect("Error in get_token") {
+    if tkn.name() == s_term.name() {break}
+    lex_tokens.push((get_original_name(tkn.name()).unwrap_or_default(), tkn.value_to_string()));
+  }
+  (tokens, lex_tokens)
+}
+
+fn to_tokens(tokens: Vec<(&str, &str)>) -> Vec<(String, String)> {
+  tokens.into_iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+#[test]
+fn test_classes() {
+  let (tokens, lex_tokens) = read_tokens(classes_reg_exp(), "abc_1 42\t#note é\nмир 7!");
+  let expected = to_tokens(vec!(
+    ("word", "abc_1"), ("space", " "), ("number", "42"), ("space", "\t"), ("comment", "#note é"),
+    ("space", "\n"), ("other", "мир"), ("space", " "), ("number", "7"), ("punct", "!")
+  ));
+  assert_eq!(tokens, expected, "Invalid tokens of classes");
+  assert_eq!(lex_tokens, expected, "Invalid tokens of classes in Lex");
+
+  let result = build("t [[:letter:]]".to_string());
+  assert_eq!(result.is_err(), true, "Unknown POSIX class is accepted");
+}
+
+#[test]
+fn test_unicode_escape_classes() {
+  let reg_exp = "
+    number        \\d+
+    word          \\w+
+    space         \\s+
+    punct         [^\\w\\s]+
+  ".to_string();
+  let (tokens, lex_tokens) = read_tokens(reg_exp, "мир_1 ٣٤\u{a0}\u{2003}x\u{660} é!");
+  let expected = to_tokens(vec!(
+    ("word", "мир_1"), ("space", " "), ("number", "٣٤"), ("space", "\u{a0}\u{2003}"), ("word", "x\u{660}"),
+    ("space", " "), ("word", "é"), ("punct", "!")
+  ));
+  assert_eq!(tokens, expected, "Invalid tokens of unicode escape classes");
+  assert_eq!(lex_tokens, expected, "Invalid tokens of unicode escape classes in Lex");
+}
+
+#[test]
+fn test_unicode_classes() {
+  let (tokens, lex_tokens) = read_tokens(unicode_reg_exp(), "привет ёж 😀 abcя\u{0430}");
+  let expected = to_tokens(vec!(
+    ("word", "привет"), ("space", " "), ("word", "ёж"), ("space", " "), ("emoji", "😀"),
+    ("space", " "), ("other", "abc"), ("word", "яа")
+  ));
+  assert_eq!(tokens, expected, "Invalid tokens of unicode classes");
+  assert_eq!(lex_tokens, expected, "Invalid tokens of unicode classes in Lex");
+
+  let null_context = &mut NoActions;
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(unicode_reg_exp()).expect("Error in regular definitions");
+  lex.set_data(b"ab".to_vec());
+  let tkn = lex.get_token(null_context).expect("Error in get_token").expect("No token");
+  assert_eq!(tkn.name(), GrammarSymbol::w_term().name(), "Invalid token of incomplete data");
+  lex.set_data(b"c\xD1".to_vec());
+  let tkn = lex.get_token(null_context).expect("Error in get_token").expect("No token");
+  assert_eq!(tkn.name(), GrammarSymbol::w_term().name(), "Invalid token of incomplete character");
+  lex.set_data(b"\x8F ".to_vec());
+  let tkn = lex.get_token(null_context).expect("Error in get_token").expect("No token");
+  assert_eq!((tkn.name(), tkn.value_to_string()), (hash("other"), "abc".to_string()), "Invalid token before character");
+  let tkn = lex.get_token(null_context).expect("Error in get_token").expect("No token");
+  assert_eq!((tkn.name(), tkn.value_to_string()), (hash("word"), "я".to_string()), "Invalid token of split character");
+
+  let (states, goto_states) = build("t [\\u0400-\\u04ff]|[^\\x00-\\x7f\\U00010000-\\U0010FFFF]".to_string())
+    .expect("Error in regular definitions");
+  let accept = |data: &str| {
+    let mut state = 0;
+    for code in data.bytes() {
+      state = match goto_states.state(state, code as usize) {
+        Some(state) => *state,
+        _ => return false
+      };
+    }
+    states[state].is_some()
+  };
+  assert_eq!(accept("Ѐ") && accept("ӿ") && accept("߿") && accept("\u{ffff}"), true, "Character of class is not accepted");
+  assert_eq!(accept("a") || accept("😀") || accept("\u{10ffff}"), false, "Character out of class is accepted");
+}
+
+#[test]
+fn test_icase() {
+  let (tokens, lex_tokens) = read_tokens(icase_reg_exp(), "get Post HOST: ПРивет abc V2");
+  let expected = to_tokens(vec!(
+    ("method", "get"), ("space", " "), ("method", "Post"), ("space", " "), ("host", "HOST:"), ("space", " "),
+    ("word", "ПРивет"), ("space", " "), ("word", "abc"), ("space", " "), ("version", "V2")
+  ));
+  assert_eq!(tokens, expected, "Invalid tokens of case-insensitive rules");
+  assert_eq!(lex_tokens, expected, "Invalid tokens of case-insensitive rules in Lex");
+}
+
+#[test]
+fn test_modes() {
+  let text = "say \"a\\\"b\" /* x * y */ ok\n\"q\n\"";
+  let (tokens, lex_tokens) = read_tokens_with_actions(modes_reg_exp(), text, &mut ModeLexActions);
+  let expected = to_tokens(vec!(
+    ("word", "say"), ("space", " "), ("quote", "\""), ("text", "a"), ("escape", "\\\""), ("text", "b"),
+    ("close", "\""), ("space", " "), ("comment_start", "/*"), ("comment_text", " x "), ("comment_text", "*"),
+    ("comment_text", " y "), ("comment_end", "*/"), ("space", " "), ("word", "ok"), ("newline", "\n"),
+    ("quote", "\""), ("text", "q"), ("newline", "\n"), ("close", "\"")
+  ));
+  assert_eq!(tokens, expected, "Invalid tokens of modes");
+  assert_eq!(lex_tokens, expected, "Invalid tokens of modes in Lex");
+
+  // the mode is kept between the chunks of the data
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(modes_reg_exp()).expect("Error in regular definitions");
+  let mut tokens = vec!();
+  for data in ["say \"a", " b\" c"] {
+    lex.set_data(data.as_bytes().to_vec());
+    while let Some(tkn) = lex.get_token(&mut ModeLexActions).expect("Error in get_token") {
+      if tkn.name() == GrammarSymbol::w_term().name() {break}
+      tokens.push((get_original_name(tkn.name()).unwrap_or_default(), tkn.value_to_string()));
+    }
+  }
+  assert_eq!(tokens, to_tokens(vec!(("word", "say"), ("space", " "), ("quote", "\""), ("text", "a b"), ("close", "\""),
+    ("space", " "))), "Invalid tokens of modes in chunks");
+
+  let result = lex.change_mode(&ModeAction::Begin(hash("unknown")));
+  assert_eq!(result.is_err(), true, "Unknown mode is accepted");
+  let result = lex.change_mode(&ModeAction::Pop);
+  assert_eq!(result.is_err(), true, "Empty stack of modes is accepted");
+}
+
+#[test]
+fn test_valid_terminals() {
+  let terminals: Rc<HashSet<usize>> = Rc::new(["zero", "x", "hexdig"].iter().map(|name| hash(name)).collect());
+  let valid = |names: &[&str]| {
+    Some(Rc::new(ValidTerminals::new(names.iter().map(|name| hash(name)).collect(), terminals.clone())))
+  };
+  let mut lex = StreamLex::new();
+  lex.set_regular_definition_text(hex_reg_exp()).expect("Error in regular definitions");
+  lex.set_data(b"00 0".to_vec());
+  let mut tokens = vec!();
+  for names in [Some(&["x", "hexdig"][..]), Some(&["x"]), Some(&["zero"]), None] {
+    lex.set_valid_terminals(names.and_then(valid));
+    let tkn = lex.get_token(&mut NoActions).expect("Error in get_token").expect("Not found token");
+    tokens.push(get_original_name(tkn.name()).unwrap_or_default());
+  }
+  // none of the valid tokens matches the second digit and it is recognized by all the rules,
+  // the token which is not the terminal of the grammar is always valid
+  assert_eq!(tokens, vec!("hexdig", "zero", "space", "zero"), "Invalid tokens of valid terminals");
+}
+
+#[test]
+fn test_bad_escapes() {
+  let mut lex = StreamLex::new();
+  let result = lex.set_regular_definition_text("
+    byte  \\x{41}
+  ".to_string());
+  assert_eq!(result, Err(Error::RegExpSyntax {line: 2, column: 11,
+    message: "Error in action: Bad escape sequence \"\\\\x\"".to_string()}), "Invalid result of bad hex escape");
+
+  let mut lex = StreamLex::new();
+  let result = lex.set_regular_definition_text("
+    id    [a-z]+
+    char  a\\u{263A}
+  ".to_string());
+  assert_eq!(result, Err(Error::RegExpSyntax {line: 3, column: 11,
+    message: "Error in action: Bad escape sequence \"\\\\u\"".to_string()}), "Invalid result of bad unicode escape");
+
+  let mut lex = StreamLex::new();
+  let result = lex.set_regular_definition_text("
+    byte  \\xZ0
+  ".to_string());
+  match result {
+    Err(Error::RegExpSyntax {line: 2, column: 11, ..}) => (),
+    _ => panic!("Invalid result of invalid hex digits: {:?}", result)
+  }
+}
@@ -1,5 +1,4 @@
 use std::rc::Rc;
-use wasm_bindgen_test::*;
 
 use server::utils::*;
 
@@ -67,7 +66,7 @@ fn create_grammar1() -> Grammar {
   grammar
 }
 
-#[wasm_bindgen_test]
+#[test]
 fn test_lr_items0_canonical() {
   let grammar = create_grammar();
 
@@ -81,7 +80,7 @@ fn test_lr_items0_canonical() {
   };
 }
 
-#[wasm_bindgen_test]
+#[test]
 fn test_lr_items1_canonical() {
   let grammar = create_grammar1();
 